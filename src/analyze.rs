@@ -0,0 +1,213 @@
+use crate::btree::btree_pages;
+use crate::cell::CellInfo;
+use crate::db::DB;
+use crate::error::Result;
+use crate::freelist::read_freelist;
+use crate::page::{Page, PageType};
+use crate::schema::read_schema;
+
+/// Space used by one table or index b-tree, like a section of sqlite3_analyzer's report.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TreeStats {
+    pub name: String,
+    /// `table` or `index`.
+    pub kind: String,
+    pub root: u32,
+    pub interior_pages: usize,
+    pub leaf_pages: usize,
+    pub overflow_pages: usize,
+    /// Rows of a table, keys of an index including those on interior pages.
+    pub entries: usize,
+    pub payload_bytes: usize,
+    pub max_payload: usize,
+    /// Cells whose payload spills onto overflow pages.
+    pub overflow_cells: usize,
+    /// Free bytes on b-tree pages plus the unused tails of overflow pages.
+    pub unused_bytes: usize,
+    /// Sum of the fragmented byte counts of the page headers.
+    pub fragmented_bytes: usize,
+    /// Levels from the root down to the leaves.
+    pub depth: usize,
+    /// Average number of children of an interior page.
+    pub fanout: f64,
+    /// Share of leaf pages not directly following the previous leaf in the file.
+    pub fragmentation: f64,
+}
+
+impl TreeStats {
+    pub fn pages(&self) -> usize {
+        self.interior_pages + self.leaf_pages + self.overflow_pages
+    }
+
+    pub fn average_payload(&self) -> f64 {
+        if self.entries == 0 {
+            0.0
+        } else {
+            self.payload_bytes as f64 / self.entries as f64
+        }
+    }
+}
+
+/// The whole file: every b-tree and the pages outside of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Analysis {
+    pub page_size: u32,
+    pub page_count: usize,
+    pub freelist_pages: usize,
+    pub trees: Vec<TreeStats>,
+}
+
+impl Analysis {
+    pub fn btree_pages(&self) -> usize {
+        self.trees.iter().map(|t| t.pages()).sum()
+    }
+
+    pub fn unused_bytes(&self) -> usize {
+        self.trees.iter().map(|t| t.unused_bytes).sum()
+    }
+
+    /// Pages neither in a b-tree nor on the freelist: ptrmap pages, the
+    /// pending-byte page or pages lost by corruption.
+    pub fn other_pages(&self) -> usize {
+        self.page_count.saturating_sub(self.btree_pages() + self.freelist_pages)
+    }
+}
+
+/// Walks `sqlite_schema` and every table and index b-tree it lists.
+pub fn analyze(db: &mut DB) -> Result<Analysis> {
+    let mut analysis = Analysis {
+        page_size: db.header.page_size,
+        page_count: db.page_count as usize,
+        freelist_pages: read_freelist(db)?.len(),
+        trees: vec![],
+    };
+    analysis.trees.push(analyze_tree(db, "sqlite_schema", "table", 1)?);
+    for entry in read_schema(db)? {
+        if entry.rootpage != 0 && (entry.kind == "table" || entry.kind == "index") {
+            analysis.trees.push(analyze_tree(db, &entry.name, &entry.kind, entry.rootpage)?);
+        }
+    }
+    Ok(analysis)
+}
+
+fn analyze_tree(db: &mut DB, name: &str, kind: &str, root: u32) -> Result<TreeStats> {
+    let mut stats = TreeStats {
+        name: name.to_string(),
+        kind: kind.to_string(),
+        root,
+        ..Default::default()
+    };
+    let overflow_size = db.header.usable_size() as usize - 4;
+    let mut children = 0;
+    let mut leaves = vec![];
+    for page_ind in btree_pages(db, root as u64)? {
+        let page = Page::new(db, page_ind)?;
+        let header_start = if page_ind == 1 { 100 } else { 0 };
+        let usable = (page.usable_size as usize).min(page.raw.len());
+        let array_end = header_start + page.header_offset + 2 * page.cell_ptrs.len();
+        let content_start = (page.cell_start as usize).clamp(array_end, usable);
+        let free: usize = page.free_blocks.iter().map(|b| b.size).sum();
+        stats.unused_bytes += content_start - array_end + free + page.free_block_size as usize;
+        stats.fragmented_bytes += page.free_block_size as usize;
+
+        match page.page_type {
+            PageType::InteriorIndex | PageType::InteriorTable => {
+                stats.interior_pages += 1;
+                children += page.cell_ptrs.len() + 1;
+            }
+            PageType::LeafIndex | PageType::LeafTable => {
+                stats.leaf_pages += 1;
+                leaves.push(page_ind);
+            }
+        }
+        if let PageType::InteriorTable = page.page_type {
+            continue;
+        }
+        for &offset in &page.cell_ptrs {
+            let info = CellInfo::new(&page, offset).map_err(|e| e.at(page_ind, offset))?;
+            let payload = info.payload_size.unwrap_or_default();
+            stats.entries += 1;
+            stats.payload_bytes += payload;
+            stats.max_payload = stats.max_payload.max(payload);
+            if let Some((rest, _)) = info.overflow {
+                let pages = rest.div_ceil(overflow_size);
+                stats.overflow_cells += 1;
+                stats.overflow_pages += pages;
+                stats.unused_bytes += pages * overflow_size - rest;
+            }
+        }
+    }
+
+    stats.depth = depth(db, root)?;
+    if stats.interior_pages > 0 {
+        stats.fanout = children as f64 / stats.interior_pages as f64;
+    }
+    if leaves.len() > 1 {
+        let gaps = leaves.windows(2).filter(|w| w[1] != w[0] + 1).count();
+        stats.fragmentation = gaps as f64 / (leaves.len() - 1) as f64;
+    }
+    Ok(stats)
+}
+
+/// Every leaf is equally deep, so following the right-most pointers is enough.
+fn depth(db: &mut DB, root: u32) -> Result<usize> {
+    let mut depth = 1;
+    let mut page = Page::new(db, root as u64)?;
+    while let Some(right) = page.right_most_ptr {
+        // btree_pages has already rejected trees with loops
+        page = Page::new(db, right as u64)?;
+        depth += 1;
+    }
+    Ok(depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::open;
+
+    fn analyze_file(file: &str) -> Analysis {
+        let raw = std::fs::read(format!("{}/{}", env!("CARGO_MANIFEST_DIR"), file)).unwrap();
+        analyze(&mut open(raw)).unwrap()
+    }
+
+    // expected numbers are from sqlite's dbstat table
+
+    #[test]
+    fn counts_leaf_pages() {
+        let analysis = analyze_file("sample.db");
+        assert_eq!((analysis.page_count, analysis.btree_pages(), analysis.other_pages()), (4, 4, 0));
+        let names: Vec<&str> = analysis.trees.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["sqlite_schema", "apples", "sqlite_sequence", "oranges"]);
+        let apples = &analysis.trees[1];
+        assert_eq!((apples.entries, apples.leaf_pages, apples.depth), (4, 1, 1));
+        assert_eq!(apples.payload_bytes, 27 + 11 + 23 + 26);
+        assert_eq!(apples.unused_bytes, 4001 - 16);
+    }
+
+    #[test]
+    fn counts_interior_pages_and_freeblocks() {
+        let t = &analyze_file("tests/fixtures/deleted.db").trees[1];
+        assert_eq!((t.interior_pages, t.leaf_pages, t.depth, t.fanout, t.fragmentation), (1, 3, 2, 3.0, 0.0));
+        assert_eq!((t.entries, t.payload_bytes, t.max_payload), (48, 337 + 340 + 260, 20));
+        // interior cells hold no payload, and freeblocks left by deleted rows are unused
+        assert_eq!(t.unused_bytes, 486 + 95 + 96 + 192);
+    }
+
+    #[test]
+    fn counts_overflow_and_freelist_pages() {
+        let analysis = analyze_file("tests/fixtures/overflow.db");
+        let [_, notes, body] = &analysis.trees[..] else {
+            panic!("{:?}", analysis.trees);
+        };
+        assert_eq!((notes.overflow_cells, notes.overflow_pages, notes.pages()), (1, 2, 3));
+        assert_eq!((notes.payload_bytes, notes.max_payload, notes.unused_bytes), (209 + 2 * 508, 1204, 278));
+        // the index repeats the text with a smaller local part, and leaves the end of its last overflow page unused
+        assert_eq!((body.overflow_cells, body.overflow_pages, body.pages()), (1, 3, 4));
+        assert_eq!((body.payload_bytes, body.unused_bytes), (61 + 2 * 508 + 150, 429 + 358));
+
+        let analysis = analyze_file("tests/fixtures/dropped.db");
+        assert_eq!((analysis.page_count, analysis.btree_pages(), analysis.freelist_pages), (6, 2, 4));
+        assert_eq!(analysis.other_pages(), 0);
+    }
+}
@@ -0,0 +1,135 @@
+use std::cmp::Ordering;
+
+use crate::error::{DbError, Result};
+
+use crate::cell::{Cell, ContentVariant};
+use crate::db::DB;
+use crate::page::{cells_from_page, Page, PageType};
+
+/// B-trees deeper than this can only come from a page referencing one of its ancestors.
+pub(crate) const MAX_DEPTH: usize = 64;
+
+/// Collects the cells of the b-tree rooted at `root` in key order: the leaf
+/// cells of a table b-tree, or every cell of an index b-tree.
+pub fn btree_cells(db: &mut DB, root: u64, content_type: ContentVariant) -> Result<Vec<Cell>> {
+    let mut cells = vec![];
+    walk(db, root, content_type, &mut cells, 0)?;
+    Ok(cells)
+}
+
+/// Collects the cells of the index b-tree rooted at `root` whose key
+/// `compare` finds Equal to the searched key, in key order. `compare` tells
+/// where a cell lies relative to the searched range, so only the subtrees
+/// that can hold matches are read.
+pub fn index_seek(db: &mut DB, root: u64, content_type: ContentVariant, compare: &dyn Fn(&Cell) -> Ordering) -> Result<Vec<Cell>> {
+    let mut cells = vec![];
+    seek(db, root, content_type, compare, &mut cells, 0)?;
+    Ok(cells)
+}
+
+fn seek(
+    db: &mut DB,
+    page_ind: u64,
+    content_type: ContentVariant,
+    compare: &dyn Fn(&Cell) -> Ordering,
+    out: &mut Vec<Cell>,
+    depth: usize,
+) -> Result<()> {
+    if depth > MAX_DEPTH {
+        return Err(DbError::corrupt_page(
+            page_ind,
+            0,
+            format!("b-tree is deeper than {} pages, it likely contains a loop", MAX_DEPTH),
+        ));
+    }
+    let page = Page::new(db, page_ind)?;
+    if !matches!(page.page_type, PageType::InteriorIndex | PageType::LeafIndex) {
+        return Err(DbError::corrupt_page(page_ind, 0, "expected an index b-tree page"));
+    }
+    for cell in cells_from_page(db, &page, content_type)? {
+        let ord = compare(&cell);
+        // keys in the left child sort before the cell's own key
+        if ord.is_ge() {
+            if let Some(child) = cell.left_child {
+                seek(db, child as u64, content_type, compare, out, depth + 1)?;
+            }
+        }
+        match ord {
+            Ordering::Less => (),
+            Ordering::Equal => out.push(cell),
+            Ordering::Greater => return Ok(()),
+        }
+    }
+    if let Some(right) = page.right_most_ptr {
+        seek(db, right as u64, content_type, compare, out, depth + 1)?;
+    }
+    Ok(())
+}
+
+/// Numbers of the interior and leaf pages of the b-tree rooted at `root`,
+/// parents before their children.
+pub fn btree_pages(db: &mut DB, root: u64) -> Result<Vec<u64>> {
+    let mut pages = vec![];
+    walk_pages(db, root, &mut pages, 0)?;
+    Ok(pages)
+}
+
+fn walk_pages(db: &mut DB, page_ind: u64, out: &mut Vec<u64>, depth: usize) -> Result<()> {
+    if depth > MAX_DEPTH {
+        return Err(DbError::corrupt_page(
+            page_ind,
+            0,
+            format!("b-tree is deeper than {} pages, it likely contains a loop", MAX_DEPTH),
+        ));
+    }
+    let page = Page::new(db, page_ind)?;
+    out.push(page_ind);
+    if let PageType::InteriorIndex | PageType::InteriorTable = page.page_type {
+        for &cell_start in &page.cell_ptrs {
+            let ptr = page.raw.get(cell_start..cell_start + 4).ok_or_else(|| {
+                DbError::corrupt_page(page_ind, cell_start, "cell is cut off by the end of the page")
+            })?;
+            walk_pages(db, u32::from_be_bytes([ptr[0], ptr[1], ptr[2], ptr[3]]) as u64, out, depth + 1)?;
+        }
+    }
+    if let Some(right) = page.right_most_ptr {
+        walk_pages(db, right as u64, out, depth + 1)?;
+    }
+    Ok(())
+}
+
+fn walk(db: &mut DB, page_ind: u64, content_type: ContentVariant, out: &mut Vec<Cell>, depth: usize) -> Result<()> {
+    if depth > MAX_DEPTH {
+        return Err(DbError::corrupt_page(
+            page_ind,
+            0,
+            format!("b-tree is deeper than {} pages, it likely contains a loop", MAX_DEPTH),
+        ));
+    }
+    let page = Page::new(db, page_ind)?;
+    let cells = cells_from_page(db, &page, content_type)?;
+
+    match page.page_type {
+        PageType::LeafTable | PageType::LeafIndex => out.extend(cells),
+        PageType::InteriorTable => {
+            for cell in cells {
+                if let Some(child) = cell.left_child {
+                    walk(db, child as u64, content_type, out, depth + 1)?;
+                }
+            }
+        }
+        PageType::InteriorIndex => {
+            for cell in cells {
+                if let Some(child) = cell.left_child {
+                    walk(db, child as u64, content_type, out, depth + 1)?;
+                }
+                out.push(cell);
+            }
+        }
+    }
+    if let Some(right) = page.right_most_ptr {
+        walk(db, right as u64, content_type, out, depth + 1)?;
+    }
+
+    Ok(())
+}
@@ -1,9 +1,12 @@
 use crate::utils::VarInt;
 // use core::{panic;
-use crate::page::PageType;
-use anyhow::{bail, Ok, Result};
+use crate::db::DB;
+use crate::page::{Page, PageType};
+use crate::error::{DbError, Result};
+use crate::pager::PageBuf;
 // use core::slice::SlicePattern;
-use std::{fmt, usize};
+use std::borrow::Cow;
+use std::fmt;
 
 impl fmt::Display for Cell {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -16,19 +19,9 @@ impl fmt::Display for Cell {
 }
 
 
-pub fn find_table_by_name<'a>(cells: &'a Vec<Cell>, target: &str) -> Option<&'a Cell> {
-    cells.iter().find(|t|{
-            if let Content::TableCell(content) = &t.content {
-                content.get_table_name().ok().map_or(false, |name| name == target)
-            }
-            else {
-                false
-            }
-        }
-    )
-}
-
-
+/// A decoded field of a record. Text and blobs are windows into the payload,
+/// which for a cell without overflow is the page itself, so decoding them
+/// copies nothing. Text is kept as stored and may not be valid UTF-8.
 #[derive(Debug)]
 pub enum Record {
     Null,
@@ -42,20 +35,20 @@ pub enum Record {
     Val0,
     Val1,
     Reserved,
-    Blob,
-    String(String),
+    Blob(PageBuf),
+    String(PageBuf),
 }
 
 impl Record {
-    pub fn get_string_val(&self) -> &String {
+    pub fn get_string_val(&self) -> Result<Cow<'_, str>> {
         match self {
-            Record::String(s) => s,
-            _ => panic!("Record is not Strign!"),
+            Record::String(s) => Ok(String::from_utf8_lossy(s)),
+            r => Err(DbError::Corrupt(format!("expected a string record, got {:?}", r))),
         }
     }
 
-    pub fn get_numeric_val(&self) -> u64 {
-        match self {
+    pub fn get_numeric_val(&self) -> Result<u64> {
+        let v = match self {
             Record::I8(v) => *v as u64,
             Record::I16(v) => *v as u64,
             Record::I24(v) => *v as u64,
@@ -65,8 +58,9 @@ impl Record {
             Record::F64(v) => *v as u64,
             Record::Val0 => 0,
             Record::Val1 => 1,
-            _ => panic!("Record is not numeric!"),
-        }
+            r => return Err(DbError::Corrupt(format!("expected a numeric record, got {:?}", r))),
+        };
+        Ok(v)
     }
 
     pub fn mem_size(&self) -> usize {
@@ -81,42 +75,44 @@ impl Record {
             Record::F64(_) => 8,
             Record::Val0 => 0,
             Record::Val1 => 0,
-            Record::Reserved => panic!("Should never encounter Reserved Record"),
-            Record::Blob => todo!(),
+            Record::Reserved => 0,
+            Record::Blob(b) => b.len(),
             Record::String(s) => s.len(),
         }
     }
 
-    fn new(bytes: &[u8], record_type: &VarInt) -> Result<Self> {
+    /// Decodes the field of `record_type` starting at `offset` in `payload`.
+    pub(crate) fn new(payload: &PageBuf, offset: usize, record_type: &VarInt) -> Result<Self> {
+        let bytes = payload.get(offset..).unwrap_or_default();
         let res = match record_type.val {
             0 => Self::Null,
             1 => {
-                if bytes.len() < 1 {
-                    bail!("expect I8 but buffer only size of {}", bytes.len());
+                if bytes.is_empty() {
+                    return Err(DbError::Corrupt(format!("expect I8 but buffer only size of {}", bytes.len())));
                 };
                 Self::I8(bytes[0] as i8)
             }
             2 => {
                 if bytes.len() < 2 {
-                    bail!("expect I16 but buffer only size of {}", bytes.len());
+                    return Err(DbError::Corrupt(format!("expect I16 but buffer only size of {}", bytes.len())));
                 };
                 Self::I16(i16::from_be_bytes([bytes[0], bytes[1]]))
             }
             3 => {
                 if bytes.len() < 3 {
-                    bail!("expect I24 but buffer only size of {}", bytes.len());
+                    return Err(DbError::Corrupt(format!("expect I24 but buffer only size of {}", bytes.len())));
                 }
                 Self::I24(i32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
             }
             4 => {
                 if bytes.len() < 4 {
-                    bail!("expect I32 but buffer only size of {}", bytes.len());
+                    return Err(DbError::Corrupt(format!("expect I32 but buffer only size of {}", bytes.len())));
                 }
                 Self::I32(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
             }
             5 => {
                 if bytes.len() < 6 {
-                    bail!("expect I48 but buffer only size of {}", bytes.len());
+                    return Err(DbError::Corrupt(format!("expect I48 but buffer only size of {}", bytes.len())));
                 }
                 Self::I48(i64::from_be_bytes([
                     0, 0, bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5],
@@ -124,7 +120,7 @@ impl Record {
             }
             6 => {
                 if bytes.len() < 8 {
-                    bail!("expect I64 but buffer only size of {}", bytes.len());
+                    return Err(DbError::Corrupt(format!("expect I64 but buffer only size of {}", bytes.len())));
                 }
                 Self::I64(i64::from_be_bytes([
                     bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
@@ -132,7 +128,7 @@ impl Record {
             }
             7 => {
                 if bytes.len() < 8 {
-                    bail!("expect F64 but buffer only size of {}", bytes.len());
+                    return Err(DbError::Corrupt(format!("expect F64 but buffer only size of {}", bytes.len())));
                 }
                 Self::F64(f64::from_be_bytes([
                     bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
@@ -140,54 +136,59 @@ impl Record {
             }
             8 => Self::Val0,
             9 => Self::Val1,
-            10 | 11 => Self::Reserved,
+            10 | 11 => {
+                return Err(DbError::Corrupt(format!(
+                    "reserved serial type {}",
+                    record_type.val
+                )))
+            }
             val => match val % 2 {
                 1 => {
                     let str_len = ((val - 13) / 2) as usize;
                     if bytes.len() < str_len {
-                        println!(
-                            "Error this is the entry \"{}\"",
-                            String::from_utf8_lossy(bytes)
-                        );
-                        bail!(
+                        return Err(DbError::Corrupt(format!(
                             "expected String of size {}, but buffer only size of {}",
-                            ((val - 13) / 2) as usize,
+                            str_len,
                             bytes.len()
-                        );
+                        )));
                     }
-                    let s = String::from_utf8_lossy(&bytes[..str_len]);
-                    Self::String(s.to_string())
+                    Self::String(payload.slice(offset..offset + str_len))
                 }
-                0 => {
-                    if bytes.len() < ((val - 12) / 2) as usize {
-                        bail!(
+                _ => {
+                    let blob_len = ((val - 12) / 2) as usize;
+                    if bytes.len() < blob_len {
+                        return Err(DbError::Corrupt(format!(
                             "expected Blob of size {}, but buffer only size of {}",
-                            ((val - 12) / 2) as usize,
+                            blob_len,
                             bytes.len()
-                        );
+                        )));
                     }
-                    Self::Blob
+                    Self::Blob(payload.slice(offset..offset + blob_len))
                 }
-                err => panic!("modulo 2 of {} returned neither 0 nor 1: {}", val, err),
             },
         };
         Ok(res)
     }
 }
 
-fn parse_cell_header(bytes: &[u8]) -> Result<(Vec<VarInt>, usize)> {
+pub(crate) fn parse_cell_header(bytes: &[u8]) -> Result<(Vec<VarInt>, usize)> {
     let mut ind: usize = 0;
-    let header_size = VarInt::from_mem(&bytes[..10])?;
+    let header_size = VarInt::from_mem(bytes)?;
     ind += header_size.len;
 
     let mut types = Vec::new();
     while ind < header_size.val as usize {
-        let serial_type = VarInt::from_mem(&bytes[ind..ind + 10])?;
+        let serial_type = VarInt::from_mem(bytes.get(ind..).unwrap_or_default())?;
         ind += serial_type.len;
         types.push(serial_type);
     }
 
-    assert!(ind == header_size.val as usize);
+    if ind != header_size.val as usize {
+        return Err(DbError::Corrupt(format!(
+            "record header overruns its size of {}",
+            header_size.val
+        )));
+    }
 
     Ok((types, ind))
 }
@@ -199,12 +200,12 @@ pub struct RowCell{
 }
 
 impl RowCell {
-    fn new(bytes: &[u8]) -> Result<Self> {
-        let (types, mut ind) = parse_cell_header(bytes)?;
+    fn new(payload: &PageBuf) -> Result<Self> {
+        let (types, mut ind) = parse_cell_header(payload)?;
         let mut row = Vec::new();
 
         for serial_type in types {
-            let r = Record::new(&bytes[ind..], &serial_type)?;
+            let r = Record::new(payload, ind, &serial_type)?;
             ind += r.mem_size();
             // println!("r: {:?}", r);
             row.push(r);
@@ -214,7 +215,7 @@ impl RowCell {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 #[allow(dead_code)]
 pub enum ContentVariant {
     TableCell,
@@ -224,8 +225,10 @@ pub enum ContentVariant {
 #[derive(Debug)]
 #[allow(dead_code)]
 pub enum Content {
-    TableCell(TableCell),
-    RowCell(RowCell)
+    TableCell(Box<TableCell>),
+    RowCell(RowCell),
+    /// Interior table cells only carry a child pointer and a key.
+    Empty,
 }
 
 #[derive(Debug)]
@@ -248,33 +251,32 @@ impl TableCell {
         &self.schema_rootpage
     }
 
-    pub fn is_table(&self) -> bool {
-        if let Record::String(s) = &self.schema_type {
-            if s == "table" {
-                return true;
-            }
-        }
-        false
+    pub fn get_type(&self) -> &Record {
+        &self.schema_type
     }
 
-    pub fn get_table_name(&self) -> Result<&str> {
-        if self.is_table() {
-            if let Record::String(s) = &self.schema_tbl_name {
-                return Ok(s);
-            }
-        }
-        bail!("not a table or not a string type")
+    pub fn get_name(&self) -> &Record {
+        &self.schema_name
+    }
+
+    pub fn get_tbl_name(&self) -> &Record {
+        &self.schema_tbl_name
     }
 
-    fn new(bytes: &[u8]) -> Result<Self> {
-        let header_size = VarInt::from_mem(&bytes[..10])?;
-        let mut c = RowCell::new(bytes)?;
+    fn new(payload: &PageBuf) -> Result<Self> {
+        let header_size = VarInt::from_mem(payload)?;
+        let mut c = RowCell::new(payload)?;
+        let mut field = |name: &str| {
+            c.row
+                .pop()
+                .ok_or_else(|| DbError::Corrupt(format!("TableRow doesnt have schema {}", name)))
+        };
 
-        let schema_sql = c.row.pop().expect("TableRow doesnt have schema sql");
-        let schema_rootpage = c.row.pop().expect("TableRow doesnt have schema rootpage");
-        let schema_tbl_name = c.row.pop().expect("TableRow doesnt have schema table name");
-        let schema_name = c.row.pop().expect("TableRow doesnt have schema name");
-        let schema_type = c.row.pop().expect("TableRow doesnt have schema type");
+        let schema_sql = field("sql")?;
+        let schema_rootpage = field("rootpage")?;
+        let schema_tbl_name = field("table name")?;
+        let schema_name = field("name")?;
+        let schema_type = field("type")?;
         // let mut ind: usize = 0;
         // let schema_type_size = VarInt::from_mem(&bytes[ind..ind + 10])?;
         // ind += schema_type_size.len;
@@ -309,7 +311,10 @@ impl TableCell {
 
 pub struct Cell {
     size_record: VarInt,
-    pub rowid: VarInt,
+    pub rowid: Option<VarInt>,
+    pub left_child: Option<u32>,
+    #[allow(dead_code)]
+    pub overflow_page: Option<u32>,
     pub content: Content,
 }
 
@@ -320,34 +325,240 @@ impl Cell {
     }
 
     pub fn cell_size(&self) -> usize {
-        (self.size_record.val as usize) + self.rowid.len + self.size_record.len
+        (self.size_record.val as usize)
+            + self.rowid.as_ref().map_or(0, |r| r.len)
+            + self.size_record.len
     }
 
-    pub fn new(bytes: &[u8], page_type: &PageType, content_type: ContentVariant) -> Result<Self> {
-        match page_type {
-            PageType::LeafTable => {
-                // there can be overflow for cell spillage
-                let size_record = VarInt::from_mem(&bytes[..9])?;
-                let rowid = VarInt::from_mem(&bytes[size_record.len..size_record.len + 9])?;
-
-                let content = match content_type {
-                    ContentVariant::TableCell => Content::TableCell(TableCell::new(&bytes[&size_record.len + &rowid.len..])?),
-                    ContentVariant::RowCell => {
-                        Content::RowCell(RowCell::new(&bytes[&size_record.len + &rowid.len..])?)
-                        // println!("page: {}",)
-                    },
-                };
+    /// Parses the cell starting at `cell_start` in `page`, following the
+    /// overflow chain through `db` when the payload doesn't fit on the page.
+    pub fn new(db: &mut DB, page: &Page, cell_start: usize, content_type: ContentVariant) -> Result<Self> {
+        Self::parse(db, page, cell_start, content_type).map_err(|e| e.at(page.number, cell_start))
+    }
 
-                return Ok(Self {
-                    size_record,
-                    rowid,
-                    content,
-                });
+    fn parse(db: &mut DB, page: &Page, cell_start: usize, content_type: ContentVariant) -> Result<Self> {
+        // the reserved bytes at the end of the page never hold payload
+        let usable = (page.usable_size as usize).min(page.raw.len());
+        let bytes = page.raw.get(cell_start..usable).unwrap_or_default();
+        let mut ind = 0;
+
+        let left_child = match page.page_type {
+            PageType::InteriorIndex | PageType::InteriorTable => {
+                if bytes.len() < 4 {
+                    return Err(DbError::Corrupt("cell is cut off by the end of the page".to_string()));
+                }
+                ind += 4;
+                Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
             }
-            _ => todo!(),
-            // PageType::InteriorTable => todo!(),
-            // PageType::LeafIndex => todo!(),
-            // PageType::InteriorIndex => todo!(),
+            _ => None,
+        };
+
+        if let PageType::InteriorTable = page.page_type {
+            let rowid = VarInt::from_mem(&bytes[ind..])?;
+            return Ok(Self {
+                size_record: VarInt { val: 0, len: 0 },
+                rowid: Some(rowid),
+                left_child,
+                overflow_page: None,
+                content: Content::Empty,
+            });
+        }
+
+        let size_record = VarInt::from_mem(&bytes[ind..])?;
+        ind += size_record.len;
+        let rowid = match page.page_type {
+            PageType::LeafTable => {
+                let rowid = VarInt::from_mem(bytes.get(ind..).unwrap_or_default())?;
+                ind += rowid.len;
+                Some(rowid)
+            }
+            _ => None,
+        };
+
+        let payload_size = size_record.val as usize;
+        let local = local_payload_size(page.usable_size as usize, payload_size, &page.page_type);
+        let Some(local_bytes) = bytes.get(ind..ind + local) else {
+            return Err(DbError::Corrupt("cell is cut off by the end of the page".to_string()));
+        };
+        let mut overflow_page = None;
+        let payload = if local == payload_size {
+            page.raw.slice(cell_start + ind..cell_start + ind + local)
+        } else {
+            let Some(ptr) = bytes.get(ind + local..ind + local + 4) else {
+                return Err(DbError::Corrupt("cell is cut off by the end of the page".to_string()));
+            };
+            let first = u32::from_be_bytes([ptr[0], ptr[1], ptr[2], ptr[3]]);
+            overflow_page = Some(first);
+            let mut payload = local_bytes.to_vec();
+            read_overflow(db, first, payload_size, &mut payload)?;
+            PageBuf::from(payload)
         };
+
+        let content = match content_type {
+            ContentVariant::TableCell => Content::TableCell(Box::new(TableCell::new(&payload)?)),
+            ContentVariant::RowCell => Content::RowCell(RowCell::new(&payload)?),
+        };
+
+        Ok(Self {
+            size_record,
+            rowid,
+            left_child,
+            overflow_page,
+            content,
+        })
+    }
+}
+
+/// Where a cell lies within its page, read from its header without decoding the payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellInfo {
+    pub offset: usize,
+    /// Bytes the cell takes up on the page, at least 4.
+    pub size: usize,
+    pub left_child: Option<u32>,
+    pub rowid: Option<i64>,
+    /// Size of the whole payload, none for interior table cells.
+    pub payload_size: Option<usize>,
+    /// Offset of the payload within the page.
+    pub payload_start: usize,
+    /// Payload bytes stored on overflow pages and the first of them.
+    pub overflow: Option<(usize, u32)>,
+}
+
+impl CellInfo {
+    pub fn new(page: &Page, offset: usize) -> Result<Self> {
+        let usable = (page.usable_size as usize).min(page.raw.len());
+        let bytes = page.raw.get(offset..usable).unwrap_or_default();
+        let cut_off = || DbError::Corrupt("cell extends off the end of the page".to_string());
+        let read_u32 = |at: usize| -> Result<u32> {
+            let b = bytes.get(at..at + 4).ok_or_else(cut_off)?;
+            Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        };
+
+        let mut ind = 0;
+        let mut info = Self {
+            offset,
+            size: 0,
+            left_child: None,
+            rowid: None,
+            payload_size: None,
+            payload_start: offset,
+            overflow: None,
+        };
+        if let PageType::InteriorIndex | PageType::InteriorTable = page.page_type {
+            info.left_child = Some(read_u32(0)?);
+            ind += 4;
+        }
+        if let PageType::InteriorTable = page.page_type {
+            let rowid = VarInt::from_mem(bytes.get(ind..).unwrap_or_default())?;
+            info.rowid = Some(rowid.val as i64);
+            info.size = ind + rowid.len;
+            info.payload_start = offset + info.size;
+            return Ok(info);
+        }
+
+        let payload = VarInt::from_mem(bytes.get(ind..).unwrap_or_default())?;
+        ind += payload.len;
+        if let PageType::LeafTable = page.page_type {
+            let rowid = VarInt::from_mem(bytes.get(ind..).unwrap_or_default())?;
+            info.rowid = Some(rowid.val as i64);
+            ind += rowid.len;
+        }
+        let payload_size = payload.val as usize;
+        info.payload_size = Some(payload_size);
+        info.payload_start = offset + ind;
+        let local = local_payload_size(page.usable_size as usize, payload_size, &page.page_type);
+        ind += local;
+        if local < payload_size {
+            info.overflow = Some((payload_size - local, read_u32(ind)?));
+            ind += 4;
+        }
+        if ind > bytes.len() {
+            return Err(cut_off());
+        }
+        // cells are never smaller than 4 bytes so they can become freeblocks
+        info.size = ind.max(4);
+        Ok(info)
+    }
+}
+
+/// How many payload bytes are stored on the b-tree page itself, the rest
+/// spills onto overflow pages.
+pub fn local_payload_size(usable_size: usize, payload_size: usize, page_type: &PageType) -> usize {
+    let max_local = match page_type {
+        PageType::LeafTable | PageType::InteriorTable => usable_size - 35,
+        _ => (usable_size - 12) * 64 / 255 - 23,
+    };
+    if payload_size <= max_local {
+        return payload_size;
+    }
+    let min_local = (usable_size - 12) * 32 / 255 - 23;
+    let k = min_local + (payload_size - min_local) % (usable_size - 4);
+    if k <= max_local {
+        k
+    } else {
+        min_local
+    }
+}
+
+pub(crate) fn read_overflow(db: &mut DB, first: u32, payload_size: usize, payload: &mut Vec<u8>) -> Result<()> {
+    let mut next = first;
+    let mut visited = 0;
+    while payload.len() < payload_size {
+        if next == 0 {
+            return Err(DbError::Corrupt(format!(
+                "overflow chain ends before the payload of {} bytes is complete",
+                payload_size
+            )));
+        }
+        visited += 1;
+        if visited > db.page_count {
+            return Err(DbError::Corrupt(format!("overflow chain starting at page {} loops", first)));
+        }
+        let raw = db.read_page(next as u64)?;
+        let remaining = payload_size - payload.len();
+        let content = &raw[4..db.header.usable_size() as usize];
+        payload.extend_from_slice(&content[..remaining.min(content.len())]);
+        next = u32::from_be_bytes([raw[0], raw[1], raw[2], raw[3]]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use bytes::Bytes;
+
+    #[test]
+    fn payload_stops_at_the_reserved_bytes() {
+        let mut raw = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let mut db = DB::from_storage(Box::new(MemoryStorage::from(raw.clone()))).unwrap();
+        let page = Page::new(&mut db, 2).unwrap();
+        // the first apple is the last 29 bytes of the page
+        assert_eq!(page.cell_ptrs[0], 4067);
+        assert!(Cell::new(&mut db, &page, 4067, ContentVariant::RowCell).is_ok());
+
+        raw[20] = 20;
+        let mut db = DB::from_storage(Box::new(MemoryStorage::from(raw))).unwrap();
+        let page = Page::new(&mut db, 2).unwrap();
+        assert!(matches!(
+            Cell::new(&mut db, &page, 4067, ContentVariant::RowCell),
+            Err(DbError::CorruptPage { page: 2, offset: 4067, .. })
+        ));
+    }
+
+    #[test]
+    fn text_borrows_from_the_page() {
+        let raw = Bytes::from(std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap());
+        let file = raw.as_ptr_range();
+        let mut db = DB::from_storage(Box::new(MemoryStorage::from(raw.clone()))).unwrap();
+        let page = Page::new(&mut db, 2).unwrap();
+        let cell = Cell::new(&mut db, &page, 4067, ContentVariant::RowCell).unwrap();
+        let Content::RowCell(RowCell { row }) = cell.content else { panic!("expected a row") };
+        let Record::String(name) = &row[1] else { panic!("expected text, got {:?}", row[1]) };
+        assert_eq!(&**name, b"Granny Smith");
+        assert!(name.is_shared());
+        assert!(file.contains(&name.as_ptr()));
     }
 }
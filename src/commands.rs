@@ -1,248 +1,184 @@
-use std::vec;
+use std::collections::BTreeMap;
+use std::io;
 
-use crate::cell::*;
-use crate::db::DB;
-use crate::page::*;
-
-use anyhow::bail;
-use regex::Regex;
+use codecrafters_sqlite::{sql_literal, Database, OutputSettings, Value};
 
 use anyhow::Ok;
-use anyhow::Result;
-
-pub fn tables(db: &mut DB) -> Result<()> {
-    let page = Page::new(db, 1)?;
+use anyhow::{bail, Result};
 
-    let tables = tables_from_page(&page)?;
-
-    let mut tables_names: Vec<&str> = tables
-        .iter()
-        .filter_map(|table| {
-            if let Content::TableCell(content) = &table.content {
-                content.get_table_name().ok()
-            } else {
-                None
-            }
-        })
-        .collect();
+pub fn tables(db: &mut Database) -> Result<()> {
+    let mut tables_names = db.tables()?;
 
     tables_names.sort();
 
     for table_name in tables_names {
         print!("{} ", table_name);
     }
-    println!("");
+    println!();
 
     Ok(())
 }
 
-pub fn db_info(db: &mut DB) -> Result<()> {
-    let page = Page::new(db, 1)?;
+pub fn db_info(db: &mut Database) -> Result<()> {
+    let schema = db.schema()?;
 
-    println!("database page size: {}", db.header.page_size);
-    println!("number of tables: {}", page.cell_count);
+    println!("database page size: {}", db.header().page_size);
+    println!("number of tables: {}", schema.len());
 
     Ok(())
 }
 
-fn count_rows(db: &mut DB, table: &str) -> Result<()> {
-    let page = Page::new(db, 1)?;
-
-    let tables = tables_from_page(&page)?;
+pub fn freelist(db: &mut Database) -> Result<()> {
+    let freelist = db.freelist()?;
+    let expected = db.header().freelist_count as usize;
 
-    if let Some(table) = find_table_by_name(&tables, table) {
-        // println!("{}", table);
-        if let Content::TableCell(content) = &table.content {
-            let table_page = Page::new(db, content.get_rootpage().get_numeric_val())?;
-
-            if let PageType::LeafTable = table_page.page_type {
-                println!("{}", table_page.cell_count);
-            } else {
-                println!("table is multipage table ... cant parse that yet");
-            }
-        }
-    } else {
-        println!("no such table: {}", table);
+    for trunk in &freelist.trunks {
+        let leaves: Vec<String> = trunk.leaves.iter().map(|l| l.to_string()).collect();
+        println!("trunk {}: {} leaves: {}", trunk.page, trunk.leaves.len(), leaves.join(" "));
     }
+    println!("free pages: {}", freelist.len());
+    if freelist.len() != expected {
+        bail!("freelist holds {} pages but the header says {}", freelist.len(), expected);
+    }
+    println!("freelist count matches the header");
 
     Ok(())
 }
 
-fn values_from_rows(
-    db: &mut DB,
-    page_ind: u64,
-    ind: &Vec<usize>,
-    cond_ind: Option<usize>,
-    where_cond: &Option<(&&str, ConditionOperators, String)>,
-) -> Result<Vec<Vec<String>>> {
-    if ind.len() == 0 {
-        bail!("0 keys");
+pub fn check(db: &mut Database) -> Result<()> {
+    let problems = db.integrity_check()?;
+    if problems.is_empty() {
+        println!("ok");
     }
-    db.root_page(page_ind)?;
-    let table_page = Page::new(db, page_ind)?;
-    let contents = rows_from_page(&table_page)?;
-
-    let mut ret = vec![];
-
-    for content in contents {
-        if let Content::RowCell(row) = content {
-            if let (Some(cond_ind), Some(where_cond)) = (cond_ind, where_cond) {
-                if let Some(Record::String(s)) = row.row.get(cond_ind) {
-                    if !check_condition(&s, where_cond) {
-                        continue;
-                    }
-                }
-            }
-            let mut inner_ret = vec![];
-            for c_ind in ind {
-                if let Some(Record::String(s)) = row.row.get(c_ind.clone()) {
-                    inner_ret.push(s.clone());
-                }
-            }
-            ret.push(inner_ret);
-        }
+    for problem in problems {
+        println!("{}", problem);
     }
 
-    Ok(ret)
+    Ok(())
 }
 
-fn check_condition(col_val: &str, where_cond: &(&&str, ConditionOperators, String)) -> bool {
-    match where_cond.1 {
-        ConditionOperators::EQUAL => where_cond.2.eq(col_val),
-        ConditionOperators::UNEQUAL => !where_cond.2.eq(col_val),
-        ConditionOperators::GREATERTHAN => where_cond.2.gt(&col_val.to_string()),
-        ConditionOperators::GREATEREQUALTHAN => where_cond.2.ge(&col_val.to_string()),
-        ConditionOperators::LESSTHAN => where_cond.2.lt(&col_val.to_string()),
-        ConditionOperators::LESSEQUALTHAN => where_cond.2.le(&col_val.to_string()),
-    }
+/// Prints a dotted `label..... value` line like sqlite3_analyzer.
+fn stat(label: &str, value: impl std::fmt::Display) {
+    println!("{:.<50} {}", format!("{} ", label), value);
 }
 
-fn select_with_parsed_params(
-    db: &mut DB,
-    columns: &[&str],
-    table: &str,
-    where_cond: Option<(&&str, ConditionOperators, String)>,
-) -> Result<()> {
-    let mut keys = vec![];
-    for key in columns {
-        keys.push(key.trim_matches(','));
-    }
-
-    let page = Page::new(db, 1)?;
-
-    let tables = tables_from_page(&page)?;
-
-    let mut column_ind = vec![];
-    let mut rootpage_ind = None;
-    let mut cond_ind = None;
-
-    if let Some(table) = find_table_by_name(&tables, table) {
-        if let Content::TableCell(content) = &table.content {
-            rootpage_ind = Some(content.get_rootpage().get_numeric_val());
-            let table_regex =
-                Regex::new(r#"(?i)CREATE\s+TABLE\s+"?(\w+)"?\s*\(([^;]+)\)"#).unwrap();
-            let field_regex = Regex::new(r"(?m)^\s*(\w+)\s+[\w()]+").unwrap();
-
-            if let Some(caps) = table_regex.captures(content.get_sql().get_string_val()) {
-                let fields_part = &caps[2];
-                for (i, field_cap) in field_regex.captures_iter(fields_part).enumerate() {
-                    if keys.contains(&&field_cap[1]) {
-                        column_ind.push(i);
-                    }
-                    if let Some(t) = &where_cond {
-                        if &field_cap[1] == *t.0 {
-                            cond_ind = Some(i);
-                        }
-                    }
-                }
-            }
-        }
-    };
-
-    if let Some(r_ind) = rootpage_ind {
-        let vals = values_from_rows(db, r_ind, &column_ind, cond_ind, &where_cond)?;
-        for row_val in vals {
-            let mut s = String::new();
-            for (i, col_val) in row_val.iter().enumerate() {
-                if i > 0 {
-                    s.push_str("|");
-                    print!("|");
-                }
-                s.push_str(&format!("{}", col_val));
-            }
-            println!("{}", s);
-        }
+pub fn analyze(db: &mut Database) -> Result<()> {
+    let analysis = db.analyze()?;
+    let percent = |part: usize, whole: usize| if whole == 0 { 0.0 } else { 100.0 * part as f64 / whole as f64 };
+    let total_bytes = analysis.page_count * analysis.page_size as usize;
+    let overflow: usize = analysis.trees.iter().map(|t| t.overflow_pages).sum();
+
+    println!("/** Disk-Space Utilization Report");
+    stat("Page size in bytes", analysis.page_size);
+    stat("Pages in the whole file", analysis.page_count);
+    stat("Pages of b-trees", analysis.btree_pages() - overflow);
+    stat("Pages of overflow chains", overflow);
+    stat("Pages on the freelist", analysis.freelist_pages);
+    stat("Pages of other use", analysis.other_pages());
+    stat("Number of tables and indexes", analysis.trees.len());
+    let unused = analysis.unused_bytes() + analysis.freelist_pages * analysis.page_size as usize;
+    stat("Unused bytes on all pages", format!("{} {:.1}%", unused, percent(unused, total_bytes)));
+
+    for tree in &analysis.trees {
+        println!();
+        println!("*** {} {} (root page {}) ***", tree.kind, tree.name, tree.root);
+        stat("Number of entries", tree.entries);
+        stat("Total pages used", tree.pages());
+        stat("Interior pages", tree.interior_pages);
+        stat("Leaf pages", tree.leaf_pages);
+        stat("Overflow pages", tree.overflow_pages);
+        stat("Bytes of payload", tree.payload_bytes);
+        stat("Average payload per entry", format!("{:.2}", tree.average_payload()));
+        stat("Maximum payload per entry", tree.max_payload);
+        stat("Entries that use overflow", tree.overflow_cells);
+        let bytes = tree.pages() * analysis.page_size as usize;
+        stat("Unused bytes", format!("{} {:.1}%", tree.unused_bytes, percent(tree.unused_bytes, bytes)));
+        stat("Fragmented bytes", tree.fragmented_bytes);
+        stat("Average fanout", format!("{:.2}", tree.fanout));
+        stat("Depth of the b-tree", tree.depth);
+        stat("Fragmentation", format!("{:.1}%", 100.0 * tree.fragmentation));
     }
 
     Ok(())
 }
 
-#[derive(Debug)]
-enum ConditionOperators {
-    EQUAL,
-    UNEQUAL,
-    GREATERTHAN,
-    GREATEREQUALTHAN,
-    LESSTHAN,
-    LESSEQUALTHAN,
+pub fn page(db: &mut Database, page_no: u64) -> Result<()> {
+    print!("{}", db.describe_page(page_no)?);
+
+    Ok(())
 }
 
-pub fn select(db: &mut DB, query: &[&str]) -> Result<()> {
-    let mut ind = 0;
-    let mut cond = None;
-    for (i, token) in query.iter().enumerate() {
-        if *token == "FROM" {
-            ind = i;
-        }
-        if *token == "WHERE" {
-            if let (Some(table), Some(cond_operator_str), Some(value)) =
-                (query.get(i + 1), query.get(i + 2), query.get(i + 3))
-            {
-                let cond_operator = match *cond_operator_str {
-                    "!=" | "<>" => ConditionOperators::UNEQUAL,
-                    "=" => ConditionOperators::EQUAL,
-                    ">" => ConditionOperators::GREATERTHAN,
-                    ">=" => ConditionOperators::GREATEREQUALTHAN,
-                    "<" => ConditionOperators::LESSTHAN,
-                    "<=" => ConditionOperators::LESSEQUALTHAN,
-                    _ => bail!("unknown Conditional Operator in WHERE"),
-                };
-                // let mut trimmed = String::new();
-                let trimmed = if value.starts_with('\'') {
-                    query[i + 3..]
-                        .join(" ")
-                        .trim_matches('\'')
-                        .trim()
-                        .to_string()
-                    // trimmed = joined.trim_matches('\'').trim().to_string();
-                    // trimmed
-                } else {
-                    value.to_string()
-                };
-                cond = Some((table, cond_operator, trimmed));
-            } else {
-                bail!("Found WHERE but not enough arguments")
-            }
-            break;
-        }
+pub fn recover(db: &mut Database, min_confidence: f64) -> Result<()> {
+    let quote = |name: &str| format!("\"{}\"", name.replace('"', "\"\""));
+    for record in db.recover(min_confidence)? {
+        let columns: Vec<String> = record.columns.iter().map(|c| quote(c)).collect();
+        let values: Vec<String> = record.values.iter().map(sql_literal).collect();
+        println!(
+            "INSERT INTO {}({}) VALUES({}); -- {} page {} offset {} confidence {:.2}",
+            quote(&record.table),
+            columns.join(","),
+            values.join(","),
+            record.source,
+            record.page,
+            record.offset,
+            record.confidence
+        );
     }
 
-    select_with_parsed_params(db, &query[0..ind], &query[ind + 1], cond)?;
     Ok(())
 }
 
-pub fn sql_query(db: &mut DB, query: &str) -> Result<()> {
-    let tokens: Vec<&str> = query.split(" ").collect();
-
-    if tokens.len() < 4 {
-        println!("syntax error, need more arguments");
+/// Runs `query` with its parameters bound from `.parameter set`, a plain `?`
+/// by its index as `?N` like in sqlite3.
+pub fn sql_query(
+    db: &mut Database,
+    settings: &OutputSettings,
+    parameters: &BTreeMap<String, Value>,
+    query: &str,
+) -> Result<()> {
+    let mut statement = db.prepare(query)?;
+    for index in 1..=statement.parameter_count() {
+        let name = statement.parameter_name(index).map_or_else(|| format!("?{}", index), str::to_string);
+        if let Some(value) = parameters.get(&name).cloned() {
+            statement.bind(index, value)?;
+        }
     }
+    let rows = statement.query()?;
+    let columns = rows.columns().to_vec();
+    let rows: Vec<_> = rows.map(|row| row.into_values()).collect();
 
-    if *tokens.get(1).unwrap() == "COUNT(*)" {
-        return count_rows(db, tokens.last().unwrap());
-    }
+    let mut out = io::stdout().lock();
+    settings.formatter().write(&mut out, &columns, &rows)?;
 
-    select(db, &tokens[1..])?;
+    Ok(())
+}
+
+/// `.parameter clear|init|list|set NAME VALUE|unset NAME`. Like sqlite3, the
+/// value is an SQL expression, or text when it doesn't evaluate as one.
+pub fn parameter(db: &mut Database, parameters: &mut BTreeMap<String, Value>, args: &[String]) -> Result<()> {
+    match args {
+        [command] if command == "clear" => parameters.clear(),
+        [command] if command == "init" => (),
+        [command] if command == "list" => {
+            let width = parameters.keys().map(|name| name.chars().count()).max().unwrap_or(0);
+            for (name, value) in parameters.iter() {
+                println!("{:<width$} {}", name, sql_literal(value));
+            }
+        }
+        [command, name, value] if command == "set" => {
+            let evaluated = db
+                .query(&format!("SELECT {}", value))
+                .ok()
+                .filter(|rows| rows.columns().len() == 1)
+                .and_then(|mut rows| rows.next())
+                .map(|row| row.into_values().swap_remove(0));
+            parameters.insert(name.clone(), evaluated.unwrap_or_else(|| Value::Text(value.clone())));
+        }
+        [command, name] if command == "unset" => {
+            parameters.remove(name);
+        }
+        _ => bail!("Usage: .parameter clear|init|list|set PARAMETER VALUE|unset PARAMETER"),
+    }
 
     Ok(())
 }
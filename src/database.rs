@@ -0,0 +1,624 @@
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use crate::error::{DbError, Result};
+
+use crate::analyze::{self, Analysis};
+use crate::db::{DataBaseHeader, DB};
+use crate::freelist::{read_freelist, Freelist};
+use crate::{inspect, integrity};
+use crate::pager::CacheStats;
+use crate::query::Executor;
+use crate::recover::{self, RecoveredRecord};
+use crate::schema::{read_schema, SchemaEntry};
+use crate::sql::{ast, Parser};
+use crate::storage::{MemoryStorage, ReaderStorage, Storage};
+use crate::value::{FromValue, Value};
+
+/// A read-only handle on an SQLite database file.
+pub struct Database {
+    db: DB,
+}
+
+impl Database {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            db: DB::new(path)?,
+        })
+    }
+
+    /// Opens the file through a read-only memory mapping, pages are then
+    /// borrowed from the mapping instead of read into the page cache.
+    #[cfg(feature = "mmap")]
+    pub fn open_mmap(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            db: DB::new_mmap(path)?,
+        })
+    }
+
+    /// Opens a database file held in memory, e.g. a `Vec<u8>`, `bytes::Bytes`
+    /// or `&'static [u8]`. Use `MemoryStorage::copy_from_slice` with
+    /// `from_storage` for a shorter lived slice.
+    pub fn from_bytes(data: impl Into<MemoryStorage>) -> Result<Self> {
+        Self::from_storage(data.into())
+    }
+
+    /// Opens a database from a seekable reader, reading pages on demand.
+    pub fn from_reader(reader: impl Read + Seek + 'static) -> Result<Self> {
+        Self::from_storage(ReaderStorage::new(reader))
+    }
+
+    pub fn from_storage(storage: impl Storage + 'static) -> Result<Self> {
+        Ok(Self {
+            db: DB::from_storage(Box::new(storage))?,
+        })
+    }
+
+    /// Reads committed pages from a write-ahead log on top of the database.
+    /// `open` does this on its own for a `-wal` file next to a WAL-mode database.
+    pub fn attach_wal(&mut self, wal: impl Storage + 'static) -> Result<()> {
+        self.db.attach_wal(Box::new(wal))
+    }
+
+    /// Presents the database as it was before the transaction recorded in a
+    /// hot rollback journal. `open` does this on its own for a `-journal` file.
+    pub fn attach_journal(&mut self, journal: impl Storage + 'static) -> Result<()> {
+        self.db.attach_journal(Box::new(journal))
+    }
+
+    pub fn header(&self) -> &DataBaseHeader {
+        &self.db.header
+    }
+
+    /// Size of the database in pages, including pages added by an attached
+    /// WAL and excluding pages a hot journal rolls back.
+    pub fn page_count(&self) -> u32 {
+        self.db.page_count
+    }
+
+    /// Sets the page cache size like `PRAGMA cache_size`: pages when
+    /// positive, KiB when negative.
+    pub fn set_cache_size(&mut self, cache_size: i32) {
+        self.db.set_cache_size(cache_size)
+    }
+
+    /// Page cache hits and misses since the database was opened.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.db.cache_stats()
+    }
+
+    /// Every entry of `sqlite_schema`: tables, indexes, views and triggers.
+    pub fn schema(&mut self) -> Result<Vec<SchemaEntry>> {
+        read_schema(&mut self.db)
+    }
+
+    /// The trunk and leaf pages of the freelist.
+    pub fn freelist(&mut self) -> Result<Freelist> {
+        read_freelist(&mut self.db)
+    }
+
+    /// Problems found by `PRAGMA integrity_check`, empty if the file is intact.
+    pub fn integrity_check(&mut self) -> Result<Vec<String>> {
+        integrity::integrity_check(&mut self.db, 100)
+    }
+
+    /// Page and byte counts of every table and index, see `analyze::analyze`.
+    pub fn analyze(&mut self) -> Result<Analysis> {
+        analyze::analyze(&mut self.db)
+    }
+
+    /// A readable dump of page `page_no` for debugging its layout, see
+    /// `inspect::describe_page`.
+    pub fn describe_page(&mut self, page_no: u64) -> Result<String> {
+        inspect::describe_page(&mut self.db, page_no)
+    }
+
+    /// Deleted rows carved out of unused space that match a table with at
+    /// least `min_confidence`, see `recover::recover`.
+    pub fn recover(&mut self, min_confidence: f64) -> Result<Vec<RecoveredRecord>> {
+        recover::recover(&mut self.db, min_confidence)
+    }
+
+    /// Names of all tables and views in schema order.
+    pub fn tables(&mut self) -> Result<Vec<String>> {
+        Ok(self
+            .schema()?
+            .into_iter()
+            .filter(|e| e.kind == "table" || e.kind == "view")
+            .map(|e| e.name)
+            .collect())
+    }
+
+    /// Runs a single SQL statement and returns all of its rows.
+    pub fn query(&mut self, sql: &str) -> Result<Rows> {
+        self.prepare(sql)?.query()
+    }
+
+    /// Parses a single SQL statement whose `?`, `?NNN`, `:name`, `@name` and
+    /// `$name` parameters are given values with `Statement::bind` before it runs.
+    pub fn prepare(&mut self, sql: &str) -> Result<Statement<'_>> {
+        let (statement, parameters) = Parser::parse_statement_with_parameters(sql)?;
+        Ok(Statement {
+            db: &mut self.db,
+            statement,
+            values: vec![Value::Null; parameters.len()],
+            parameters,
+        })
+    }
+}
+
+/// A parsed statement and the values bound to its parameters, see `Database::prepare`.
+///
+/// ```no_run
+/// let mut db = codecrafters_sqlite::Database::open("sample.db")?;
+/// let mut statement = db.prepare("SELECT name FROM apples WHERE color = :color AND id > ?")?;
+/// statement.bind_named(":color", "Red")?;
+/// statement.bind(2, 1)?;
+/// for row in statement.query()? {
+///     println!("{}", row.get_as::<String>(0)?);
+/// }
+/// # Ok::<(), codecrafters_sqlite::DbError>(())
+/// ```
+pub struct Statement<'db> {
+    db: &'db mut DB,
+    statement: ast::Statement,
+    /// Name of each parameter by index, `None` for a plain `?`.
+    parameters: Vec<Option<String>>,
+    values: Vec<Value>,
+}
+
+impl Statement<'_> {
+    /// Number of parameters, which is the largest parameter index.
+    pub fn parameter_count(&self) -> usize {
+        self.parameters.len()
+    }
+
+    /// Name of the parameter with the 1-based `index`, prefix included as in
+    /// `:id` or `?2`. `None` for a plain `?`.
+    pub fn parameter_name(&self, index: usize) -> Option<&str> {
+        self.parameters.get(index.checked_sub(1)?)?.as_deref()
+    }
+
+    /// Index of the parameter named `name`, prefix included.
+    pub fn parameter_index(&self, name: &str) -> Option<usize> {
+        self.parameters.iter().position(|p| p.as_deref() == Some(name)).map(|i| i + 1)
+    }
+
+    /// Binds a value to the parameter with the 1-based `index`, e.g.
+    /// `statement.bind(1, 42)`. Unbound parameters are NULL.
+    pub fn bind(&mut self, index: usize, value: impl Into<Value>) -> Result<()> {
+        match index.checked_sub(1).and_then(|i| self.values.get_mut(i)) {
+            Some(slot) => {
+                *slot = value.into();
+                Ok(())
+            }
+            None => Err(DbError::Query(format!("parameter index {} out of range", index))),
+        }
+    }
+
+    /// Binds a value to the parameter named `name`, e.g. `:id`.
+    pub fn bind_named(&mut self, name: &str, value: impl Into<Value>) -> Result<()> {
+        match self.parameter_index(name) {
+            Some(index) => self.bind(index, value),
+            None => Err(DbError::Query(format!("no such parameter: {}", name))),
+        }
+    }
+
+    /// Sets every parameter back to NULL.
+    pub fn clear_bindings(&mut self) {
+        self.values.fill(Value::Null);
+    }
+
+    /// Runs the statement with the current bindings, which are kept for the next run.
+    pub fn query(&mut self) -> Result<Rows> {
+        let result = Executor::new(self.db)?
+            .with_parameters(self.values.clone())
+            .execute(&self.statement)?;
+        Ok(Rows {
+            columns: result.columns,
+            rows: result.rows.into_iter(),
+        })
+    }
+}
+
+/// The result of a query, iterating over its rows.
+pub struct Rows {
+    columns: Vec<String>,
+    rows: std::vec::IntoIter<Vec<Value>>,
+}
+
+impl Rows {
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+}
+
+impl Iterator for Rows {
+    type Item = Row;
+
+    fn next(&mut self) -> Option<Row> {
+        self.rows.next().map(|values| Row { values })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    values: Vec<Value>,
+}
+
+impl Row {
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn get(&self, ind: usize) -> Option<&Value> {
+        self.values.get(ind)
+    }
+
+    /// Converts column `ind` into a Rust type, e.g. `row.get_as::<i64>(0)`.
+    pub fn get_as<T: FromValue>(&self, ind: usize) -> Result<T> {
+        match self.values.get(ind) {
+            Some(v) => T::from_value(v),
+            None => Err(DbError::Query(format!("column index {} out of range", ind))),
+        }
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn into_values(self) -> Vec<Value> {
+        self.values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, schema_page};
+    use crate::recover::Source;
+
+    fn sample() -> Database {
+        Database::open(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap()
+    }
+
+    #[test]
+    fn lists_tables() {
+        assert_eq!(sample().tables().unwrap(), ["apples", "sqlite_sequence", "oranges"]);
+        let mut db = one_table_db("CREATE TABLE t(a)", &[], &[("v", "CREATE VIEW v AS SELECT a FROM t")]);
+        assert_eq!(db.tables().unwrap(), ["t", "v"]);
+    }
+
+    #[test]
+    fn queries_typed_rows() {
+        let mut db = sample();
+        let rows = db
+            .query("SELECT id, name FROM apples WHERE color = 'Red' OR id > 3 ORDER BY id DESC")
+            .unwrap();
+        assert_eq!(rows.columns(), ["id", "name"]);
+        let rows: Vec<(i64, String)> = rows
+            .map(|r| (r.get_as(0).unwrap(), r.get_as(1).unwrap()))
+            .collect();
+        assert_eq!(rows, [(4, "Golden Delicious".to_string()), (2, "Fuji".to_string())]);
+
+        let count = db.query("SELECT COUNT(*) FROM oranges").unwrap().next().unwrap();
+        assert_eq!(count.get(0), Some(&Value::Integer(6)));
+    }
+
+    #[test]
+    fn reports_unknown_names() {
+        let mut db = sample();
+        assert!(db.query("SELECT * FROM pears").is_err());
+        assert!(db.query("SELECT weight FROM apples").is_err());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn queries_through_mmap() {
+        let mut db = Database::open_mmap(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let count = db.query("SELECT COUNT(*) FROM oranges").unwrap().next().unwrap();
+        assert_eq!(count.get(0), Some(&Value::Integer(6)));
+        assert!(db.db.read_page(2).unwrap().is_shared());
+        assert_eq!(db.cache_stats().misses, 0);
+    }
+
+    #[test]
+    fn opens_from_memory_and_readers() {
+        let raw = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let count = |mut db: Database| {
+            let row = db.query("SELECT COUNT(*) FROM apples").unwrap().next().unwrap();
+            row.get_as::<i64>(0).unwrap()
+        };
+        assert_eq!(count(Database::from_bytes(raw.clone()).unwrap()), 4);
+        assert_eq!(count(Database::from_bytes(bytes::Bytes::from(raw.clone())).unwrap()), 4);
+        assert_eq!(count(Database::from_storage(MemoryStorage::copy_from_slice(&raw)).unwrap()), 4);
+        assert_eq!(count(Database::from_reader(std::io::Cursor::new(raw.clone())).unwrap()), 4);
+
+        assert!(matches!(
+            Database::from_bytes(raw[..50].to_vec()),
+            Err(DbError::NotADatabase(_))
+        ));
+    }
+
+    #[test]
+    fn reads_pages_from_wal() {
+        let mut db = sample();
+        let root = |db: &mut Database, name: &str| {
+            db.schema().unwrap().into_iter().find(|e| e.name == name).unwrap().rootpage
+        };
+        let (apples, oranges) = (root(&mut db, "apples"), root(&mut db, "oranges"));
+        let page_size = db.header().page_size;
+        let apples_page = db.db.read_page(apples as u64).unwrap().to_vec();
+
+        // a committed frame replaces the oranges root page with the apples one
+        let wal = crate::wal::tests::build_wal(page_size, &[(oranges, 0, &apples_page), (oranges, 4, &apples_page)]);
+        db.attach_wal(MemoryStorage::from(wal)).unwrap();
+        let count = db.query("SELECT COUNT(*) FROM oranges").unwrap().next().unwrap();
+        assert_eq!(count.get(0), Some(&Value::Integer(4)));
+        assert_eq!(db.page_count(), 4);
+    }
+
+    #[test]
+    fn validates_database_size() {
+        let sample = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let pages = Database::from_bytes(sample.clone()).unwrap().page_count();
+        assert_eq!(pages as usize * 4096, sample.len());
+
+        // a size written by a version that does not keep it up to date is ignored
+        let mut raw = sample.clone();
+        raw[28..32].copy_from_slice(&1u32.to_be_bytes());
+        raw[92..96].copy_from_slice(&0u32.to_be_bytes());
+        let mut db = Database::from_bytes(raw.clone()).unwrap();
+        assert_eq!(db.page_count(), pages);
+        assert!(db.integrity_check().unwrap().is_empty());
+
+        // otherwise it must fit in the file
+        raw.copy_within(24..28, 92);
+        assert_eq!(Database::from_bytes(raw.clone()).unwrap().page_count(), 1);
+        raw[28..32].copy_from_slice(&(pages + 1).to_be_bytes());
+        assert!(matches!(Database::from_bytes(raw), Err(DbError::CorruptPage { page: 1, offset: 28, .. })));
+    }
+
+    #[test]
+    fn reads_freelist() {
+        assert!(sample().freelist().unwrap().is_empty());
+
+        // append a trunk page listing one leaf page after it
+        let mut raw = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let page_size = u16::from_be_bytes([raw[16], raw[17]]) as usize;
+        let trunk = (raw.len() / page_size + 1) as u32;
+        let mut page = vec![0u8; page_size];
+        page[4..8].copy_from_slice(&1u32.to_be_bytes());
+        page[8..12].copy_from_slice(&(trunk + 1).to_be_bytes());
+        raw.extend(page);
+        raw.extend(vec![0u8; page_size]);
+        raw[28..32].copy_from_slice(&(trunk + 1).to_be_bytes());
+        raw[32..36].copy_from_slice(&trunk.to_be_bytes());
+        raw[36..40].copy_from_slice(&2u32.to_be_bytes());
+
+        let freelist = Database::from_bytes(raw.clone()).unwrap().freelist().unwrap();
+        assert_eq!(freelist.pages().collect::<Vec<_>>(), [trunk, trunk + 1]);
+
+        // the leaf points back at its trunk
+        raw[page_size * (trunk as usize - 1) + 8..][..4].copy_from_slice(&trunk.to_be_bytes());
+        assert!(Database::from_bytes(raw).unwrap().freelist().is_err());
+    }
+
+    #[test]
+    fn checks_integrity() {
+        let mut db = sample();
+        assert!(db.integrity_check().unwrap().is_empty());
+        let rows = db.query("PRAGMA integrity_check").unwrap();
+        assert_eq!(rows.map(|r| r.into_values()).collect::<Vec<_>>(), [[Value::Text("ok".to_string())]]);
+
+        let raw = [schema_page(&[], 3), vec![0; 2 * 4096]].concat();
+        let rows = Database::from_bytes(raw).unwrap().query("PRAGMA integrity_check(1)").unwrap();
+        let problems: Vec<_> = rows.map(|r| r.into_values()).collect();
+        assert_eq!(problems, [[Value::Text("*** in database main ***\nPage 2: never used".to_string())]]);
+    }
+
+    /// The database `fixtures::one_table` builds.
+    fn one_table_db(sql: &str, rows: &[Vec<Value>], views: &[(&str, &str)]) -> Database {
+        Database::from_bytes(fixtures::one_table(sql, rows, views)).unwrap()
+    }
+
+    /// The first column of every row `sql` returns.
+    fn column(db: &mut Database, sql: &str) -> Vec<Value> {
+        db.query(sql).unwrap().map(|r| r.into_values()[0].clone()).collect()
+    }
+
+    fn ints(v: &[i64]) -> Vec<Value> {
+        v.iter().map(|&i| Value::Integer(i)).collect()
+    }
+
+    fn text(v: &[&str]) -> Vec<Value> {
+        v.iter().map(|s| Value::Text(s.to_string())).collect()
+    }
+
+    /// The message of the error `sql` fails with.
+    fn expect_err(db: &mut Database, sql: &str) -> String {
+        match db.query(sql) {
+            Ok(_) => panic!("expected an error from {}", sql),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn queries_without_rowid_tables() {
+        let string = |s: &str| Value::Text(s.to_string());
+        // records hold the key (c, a) first, then b
+        let rows = [
+            vec![string("x"), Value::Integer(2), string("b1")],
+            vec![string("y"), Value::Integer(1), string("b2")],
+            vec![string("y"), Value::Integer(3), string("b3")],
+        ];
+        let mut db = one_table_db("CREATE TABLE t(a INT, b TEXT, c TEXT, PRIMARY KEY(c, a)) WITHOUT ROWID", &rows, &[]);
+        let query = |db: &mut Database, sql: &str| db.query(sql).unwrap().map(|r| r.into_values()).collect::<Vec<_>>();
+        assert_eq!(query(&mut db, "SELECT * FROM t")[0], [Value::Integer(2), string("b1"), string("x")]);
+        assert_eq!(query(&mut db, "SELECT b FROM t WHERE c = 'y'"), [[string("b2")], [string("b3")]]);
+        assert_eq!(query(&mut db, "SELECT b FROM t WHERE a = 3 AND 'y' = c"), [[string("b3")]]);
+        assert!(query(&mut db, "SELECT b FROM t WHERE c = 'z'").is_empty());
+        assert!(db.query("SELECT rowid FROM t").is_err());
+    }
+
+    #[test]
+    fn fills_in_defaults_of_added_columns() {
+        let sql = "CREATE TABLE t(a, b INTEGER DEFAULT '5', c TEXT DEFAULT 7, d DEFAULT (-3), e)";
+        let mut db = one_table_db(sql, &[vec![Value::Integer(1)], vec![Value::Integer(2), Value::Integer(9)]], &[]);
+        let rows: Vec<_> = db.query("SELECT * FROM t").unwrap().map(|r| r.into_values()).collect();
+        let text = Value::Text("7".to_string());
+        assert_eq!(rows[0], [Value::Integer(1), Value::Integer(5), text.clone(), Value::Integer(-3), Value::Null]);
+        assert_eq!(rows[1], [Value::Integer(2), Value::Integer(9), text, Value::Integer(-3), Value::Null]);
+    }
+
+    #[test]
+    fn runs_subqueries() {
+        let rows: Vec<_> = (1..=4).map(|i| vec![Value::Integer(i), Value::Integer(i % 2)]).collect();
+        let mut db = one_table_db("CREATE TABLE t(a, b)", &rows, &[]);
+        assert_eq!(column(&mut db, "SELECT a FROM t WHERE a IN (SELECT b FROM t)"), ints(&[1]));
+        assert_eq!(column(&mut db, "SELECT (SELECT count(*) FROM t AS x WHERE x.a < t.a) FROM t"), ints(&[0, 1, 2, 3]));
+        assert_eq!(column(&mut db, "SELECT a FROM t WHERE NOT EXISTS (SELECT 1 FROM t AS x WHERE x.a > t.a)"), ints(&[4]));
+        assert_eq!(column(&mut db, "SELECT s.d FROM (SELECT a * 2 AS d FROM t WHERE b = 0) AS s"), ints(&[4, 8]));
+        assert_eq!(column(&mut db, "SELECT 1 IN (SELECT NULL)"), [Value::Null]);
+        assert_eq!(expect_err(&mut db, "SELECT (SELECT a, b FROM t)"), "sub-select returns 2 columns - expected 1");
+    }
+
+    #[test]
+    fn runs_compound_selects() {
+        let rows: Vec<_> = (1..=4).map(|i| vec![Value::Integer(i), Value::Integer(i % 2)]).collect();
+        let mut db = one_table_db("CREATE TABLE t(a, b)", &rows, &[]);
+        assert_eq!(column(&mut db, "SELECT b FROM t UNION SELECT a FROM t WHERE a > 2"), ints(&[0, 1, 3, 4]));
+        assert_eq!(column(&mut db, "SELECT b FROM t UNION ALL SELECT 7 ORDER BY 1 DESC LIMIT 3"), ints(&[7, 1, 1]));
+        assert_eq!(column(&mut db, "SELECT a FROM t INTERSECT SELECT b FROM t"), ints(&[1]));
+        assert_eq!(column(&mut db, "SELECT a AS x FROM t EXCEPT SELECT b FROM t ORDER BY x DESC"), ints(&[4, 3, 2]));
+        assert_eq!(
+            expect_err(&mut db, "SELECT a FROM t UNION SELECT a, b FROM t"),
+            "SELECTs to the left and right of UNION do not have the same number of result columns"
+        );
+    }
+
+    #[test]
+    fn runs_predicates() {
+        let rows: Vec<_> = [(1, "Apple"), (2, "a_b"), (3, "banana")]
+            .into_iter()
+            .map(|(a, b)| vec![Value::Integer(a), Value::Text(b.to_string())])
+            .collect();
+        let mut db = one_table_db("CREATE TABLE t(a, b)", &rows, &[]);
+        // pattern matching itself is tested with the functions
+        assert_eq!(column(&mut db, "SELECT a FROM t WHERE b LIKE 'a\\_%' ESCAPE '\\'"), ints(&[2]));
+        assert_eq!(column(&mut db, "SELECT a FROM t WHERE b NOT GLOB '[a-b]*'"), ints(&[1]));
+        assert_eq!(column(&mut db, "SELECT a FROM t WHERE a NOT BETWEEN 2 AND 3"), ints(&[1]));
+        assert_eq!(column(&mut db, "SELECT a FROM t WHERE a IN (3, 1) OR a IN ()"), ints(&[1, 3]));
+        assert_eq!(column(&mut db, "SELECT 2 NOT IN (1, NULL) IS NULL, NULL IS NOT 1, a NOTNULL FROM t LIMIT 1"), ints(&[1]));
+
+        // text compared with an INTEGER column is read as a number, a number
+        // compared with a TEXT column as text
+        let rows: Vec<_> = (1..=5).map(|i| vec![Value::Integer(i), Value::Text(i.to_string())]).collect();
+        let mut db = one_table_db("CREATE TABLE t(a INTEGER, b TEXT)", &rows, &[]);
+        assert_eq!(column(&mut db, "SELECT a FROM t WHERE a = '3'"), ints(&[3]));
+        assert_eq!(column(&mut db, "SELECT a FROM t WHERE a IN ('3', '5')"), ints(&[3, 5]));
+        assert_eq!(column(&mut db, "SELECT a FROM t WHERE a BETWEEN '2' AND '4'"), ints(&[2, 3, 4]));
+        assert_eq!(column(&mut db, "SELECT a FROM t WHERE a > '2'"), ints(&[3, 4, 5]));
+        assert_eq!(column(&mut db, "SELECT a FROM t WHERE b = 4 OR b IN (SELECT a FROM t WHERE a = 1)"), ints(&[1, 4]));
+        assert_eq!(column(&mut db, "SELECT a FROM (SELECT a FROM t) WHERE a = '5'"), ints(&[5]));
+        assert_eq!(column(&mut db, "SELECT a FROM t WHERE a + 0 = '3'"), ints(&[]));
+    }
+
+    #[test]
+    fn runs_case_and_cast() {
+        let rows: Vec<_> = (1..=3).map(|i| vec![Value::Integer(i), Value::Text(format!("{}.5e1x", i))]).collect();
+        let mut db = one_table_db("CREATE TABLE t(a, b)", &rows, &[]);
+        let simple = column(&mut db, "SELECT CASE a WHEN 1 THEN 'one' WHEN 2 THEN 'two' END FROM t");
+        assert_eq!(simple, [text(&["one", "two"]), vec![Value::Null]].concat());
+        let searched = column(&mut db, "SELECT CASE WHEN a > 2 THEN 'big' ELSE 'small' END FROM t");
+        assert_eq!(searched, text(&["small", "small", "big"]));
+        assert_eq!(column(&mut db, "SELECT CAST(b AS INTEGER) FROM t"), (1..=3).map(Value::Integer).collect::<Vec<_>>());
+        assert_eq!(column(&mut db, "SELECT CAST(b AS NUMERIC) FROM t WHERE a = 1"), [Value::Integer(15)]);
+        assert_eq!(column(&mut db, "SELECT CAST(b AS REAL) FROM t WHERE a = 2"), [Value::Real(25.0)]);
+        assert_eq!(column(&mut db, "SELECT CAST(a AS VARCHAR(10)) FROM t WHERE a = 3"), text(&["3"]));
+        assert_eq!(column(&mut db, "SELECT CAST(a AS BLOB) FROM t WHERE a = 3"), [Value::Blob(b"3".to_vec())]);
+    }
+
+    #[test]
+    fn binds_parameters() {
+        let mut db = sample();
+        let mut statement = db.prepare("SELECT id, ?3 FROM apples WHERE color = :color AND id > ? AND :color <> $x").unwrap();
+        assert_eq!(statement.parameter_count(), 6);
+        assert_eq!(statement.parameter_name(3), Some("?3"));
+        assert_eq!(statement.parameter_index(":color"), Some(4));
+        assert_eq!(statement.parameter_name(5), None);
+        statement.bind_named(":color", "Red").unwrap();
+        statement.bind(5, 1).unwrap();
+        statement.bind(3, Some(1.5)).unwrap();
+        assert_eq!(statement.query().unwrap().count(), 0);
+        statement.bind_named("$x", "Green").unwrap();
+        let rows: Vec<Vec<Value>> = statement.query().unwrap().map(Row::into_values).collect();
+        assert_eq!(rows, [vec![Value::Integer(2), Value::Real(1.5)]]);
+        assert!(statement.bind(7, 1).is_err());
+        assert!(statement.bind_named(":colour", "Red").is_err());
+
+        assert_eq!(expect_err(&mut db, "SELECT ?0"), "variable number must be between ?1 and ?250000");
+    }
+
+    #[test]
+    fn analyzes_space() {
+        let analysis = sample().analyze().unwrap();
+        assert_eq!(analysis.trees[1].name, "apples");
+        assert_eq!(analysis.trees[1].entries, 4);
+    }
+
+    #[test]
+    fn describes_pages() {
+        let page = sample().describe_page(2).unwrap();
+        assert!(page.contains("values: NULL, 'Fuji', 'Red'"), "{}", page);
+    }
+
+    #[test]
+    fn recovers_deleted_rows() {
+        assert!(sample().recover(0.5).unwrap().is_empty());
+
+        // delete the first apple like sqlite does: drop its cell pointer and
+        // write a freeblock header over the start of the cell
+        let mut raw = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let page_size = u16::from_be_bytes([raw[16], raw[17]]) as usize;
+        let page = &mut raw[page_size..2 * page_size];
+        let cell = u16::from_be_bytes([page[8], page[9]]) as usize;
+        let size = 2 + page[cell] as u16;
+        page.copy_within(10..16, 8);
+        page[4] -= 1;
+        page[1..3].copy_from_slice(&(cell as u16).to_be_bytes());
+        page[cell..cell + 4].copy_from_slice(&[0, 0, 0, size as u8]);
+
+        let mut db = Database::from_bytes(raw).unwrap();
+        assert_eq!(db.query("SELECT count(*) FROM apples").unwrap().next().unwrap().get(0), Some(&Value::Integer(3)));
+        let recovered = db.recover(0.5).unwrap();
+        assert_eq!(recovered.len(), 1);
+        let record = &recovered[0];
+        assert_eq!((record.table.as_str(), record.page, record.source), ("apples", 2, Source::FreeBlock));
+        assert_eq!(record.columns, ["id", "name", "color"]);
+        assert_eq!(
+            record.values,
+            [Value::Null, Value::Text("Granny Smith".to_string()), Value::Text("Light Green".to_string())]
+        );
+    }
+
+    #[test]
+    fn caches_pages_between_queries() {
+        let mut db = sample();
+        db.query("SELECT * FROM apples").unwrap();
+        let first = db.cache_stats();
+        assert!(first.misses > 0);
+
+        db.query("SELECT * FROM apples").unwrap();
+        let second = db.cache_stats();
+        assert_eq!(second.misses, first.misses);
+        assert!(second.hits > first.hits);
+
+        db.set_cache_size(1);
+        assert_eq!(db.cache_stats().len, 1);
+    }
+}
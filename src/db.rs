@@ -1,21 +1,39 @@
-use std::{fs::File, io::Read};
-use anyhow::{Ok, Result};
-use std::io::SeekFrom;
-use std::io::prelude::*;
+use crate::error::{DbError, Result};
+use crate::pager::{cache_pages, CacheStats, PageBuf, Pager};
+use crate::storage::{FileStorage, Storage};
+use crate::journal::Journal;
+use crate::wal::Wal;
+use std::path::Path;
 
 #[derive(Debug)]
 #[allow(dead_code)]
 pub enum StringEncoding {
     Utf8,
-    Utf16le,
-    Utf16be,
 }
 
 #[allow(dead_code)]
 pub struct DataBaseHeader {
-    pub page_size: u16,
+    /// Page size in bytes, the stored value 1 already translated to 65536.
+    pub page_size: u32,
+    pub write_version: u8,
+    pub read_version: u8,
+    pub reserved_space: u8,
     pub string_encoding: StringEncoding,
+    /// Incremented by every transaction that changes the file.
+    pub change_counter: u32,
+    /// The in-header database size, only valid when the header was last
+    /// written with `version_valid_for` equal to `change_counter`.
     pub database_size: u32,
+    pub first_freelist_trunk: u32,
+    /// Total number of freelist pages, trunks included.
+    pub freelist_count: u32,
+    pub schema_format: u32,
+    /// Suggested cache size, pages when positive and KiB when negative.
+    pub default_cache_size: i32,
+    /// Non-zero in auto-vacuum mode, which adds pointer map pages.
+    pub largest_root_page: u32,
+    /// Value of `change_counter` when `database_size` was last written.
+    pub version_valid_for: u32,
     // Offset	Size	Description
     // 0	16	The header string: "SQLite format 3\000"
     // 16	2	The database page size in bytes. Must be a power of two between 512 and 32768 inclusive, or the value 1 representing a page size of 65536.
@@ -42,22 +60,111 @@ pub struct DataBaseHeader {
     // 96	4	SQLITE_VERSION_NUMBER
 }
 
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+const MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
 impl DataBaseHeader {
-    pub fn new(file: &mut File) -> Result<Self> {
-        let mut file_header = [0; 100];
-        file.read_exact(&mut file_header)?;
-        let page_size = u16::from_be_bytes([file_header[16], file_header[17]]);
-        let string_encoding = match u32::from_be_bytes(file_header[56..60].try_into()?) {
-            1 => StringEncoding::Utf8,
-            2 => StringEncoding::Utf16le,
-            3 => StringEncoding::Utf16be,
-            _ => panic!("Unknown String Encoding"),
+    pub fn new(storage: &mut dyn Storage) -> Result<Self> {
+        let file_header = match storage.read_at(0, 100) {
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Err(DbError::NotADatabase("file is shorter than the 100 byte header".to_string()))
+            }
+            r => r?,
+        };
+        let mut bytes = [0; 100];
+        bytes.copy_from_slice(&file_header);
+        Self::from_bytes(&bytes)
+    }
+
+    /// Bytes of each page available to the b-tree layer, i.e. the page size
+    /// minus the reserved space at the end of every page.
+    pub fn usable_size(&self) -> u32 {
+        self.page_size - self.reserved_space as u32
+    }
+
+    /// Parses and validates the 100 byte header at the start of the file.
+    pub fn from_bytes(file_header: &[u8; 100]) -> Result<Self> {
+        if &file_header[..16] != MAGIC {
+            return Err(DbError::NotADatabase("missing \"SQLite format 3\" header string".to_string()));
+        }
+
+        let page_size = match u16::from_be_bytes([file_header[16], file_header[17]]) {
+            1 => 65536,
+            size if size >= 512 && size.is_power_of_two() => size as u32,
+            size => {
+                return Err(DbError::NotADatabase(format!(
+                    "page size {} is not a power of two between 512 and 65536",
+                    size
+                )))
+            }
+        };
+
+        // a newer write version only makes sqlite open the file read-only, which this reader always is
+        let (write_version, read_version) = (file_header[18], file_header[19]);
+        if !(1..=2).contains(&read_version) {
+            return Err(DbError::UnsupportedFeature(format!("file format read version {}", read_version)));
+        }
+
+        let reserved_space = file_header[20];
+        if page_size - (reserved_space as u32) < 480 {
+            return Err(DbError::NotADatabase(format!(
+                "{} reserved bytes leave less than 480 usable bytes per page",
+                reserved_space
+            )));
+        }
+
+        let fractions = (file_header[21], file_header[22], file_header[23]);
+        if fractions != (64, 32, 32) {
+            return Err(DbError::NotADatabase(format!(
+                "payload fractions are {}/{}/{} instead of 64/32/32",
+                fractions.0, fractions.1, fractions.2
+            )));
+        }
+
+        let schema_format = read_u32(file_header, 44);
+        if schema_format > 4 {
+            return Err(DbError::UnsupportedFeature(format!(
+                "schema format {}",
+                schema_format
+            )));
+        }
+
+        let string_encoding = match read_u32(file_header, 56) {
+            // 0 is left by sqlite until the first table is created
+            0 | 1 => StringEncoding::Utf8,
+            2 | 3 => return Err(DbError::UnsupportedFeature("UTF-16 text encoding".to_string())),
+            encoding => {
+                return Err(DbError::corrupt_page(
+                    1,
+                    56,
+                    format!("unknown text encoding {}", encoding),
+                ))
+            }
         };
-        let database_size = u32::from_be_bytes(file_header[28..32].try_into()?);
+        let change_counter = read_u32(file_header, 24);
+        let database_size = read_u32(file_header, 28);
+        let version_valid_for = read_u32(file_header, 92);
+        let default_cache_size = read_u32(file_header, 48) as i32;
+        let largest_root_page = read_u32(file_header, 52);
+        let first_freelist_trunk = read_u32(file_header, 32);
+        let freelist_count = read_u32(file_header, 36);
         Ok(Self {
             page_size,
+            write_version,
+            read_version,
+            reserved_space,
             string_encoding,
+            change_counter,
             database_size,
+            first_freelist_trunk,
+            freelist_count,
+            schema_format,
+            default_cache_size,
+            largest_root_page,
+            version_valid_for,
         })
     }
 }
@@ -66,24 +173,187 @@ impl DataBaseHeader {
 
 pub struct DB {
     pub header: DataBaseHeader,
-    pub file: File
+    pub pager: Pager,
+    /// Size of the database in pages, from the header if it is valid and
+    /// from the file size otherwise, or from an attached WAL or journal.
+    pub page_count: u32,
 }
 
 impl DB {
-    pub fn new(p: &str) -> Result<Self> {
-        let mut file = File::open(p)?;
-        let header = DataBaseHeader::new(&mut file)?;
+    pub fn new(p: impl AsRef<Path>) -> Result<Self> {
+        let mut db = Self::from_storage(Box::new(FileStorage::open(&p)?))?;
+        db.open_sidecars(p.as_ref())?;
+        Ok(db)
+    }
+
+    /// Opens the file through a read-only memory mapping instead of reading
+    /// each page into a buffer.
+    #[cfg(feature = "mmap")]
+    pub fn new_mmap(p: impl AsRef<Path>) -> Result<Self> {
+        let mut db = Self::from_storage(Box::new(crate::storage::MmapStorage::open(&p)?))?;
+        db.open_sidecars(p.as_ref())?;
+        Ok(db)
+    }
+
+    /// Attaches `<path>-wal` when the header says the database is in WAL
+    /// mode, `<path>-journal` otherwise, if the file exists.
+    fn open_sidecars(&mut self, p: &Path) -> Result<()> {
+        let wal_mode = self.header.read_version == 2;
+        let mut sidecar = p.as_os_str().to_owned();
+        sidecar.push(if wal_mode { "-wal" } else { "-journal" });
+        let storage = match FileStorage::open(&sidecar) {
+            Ok(storage) => Box::new(storage),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        if wal_mode {
+            self.attach_wal(storage)
+        } else {
+            self.attach_journal(storage)
+        }
+    }
+
+    /// Presents the database as it was before the transaction recorded in a
+    /// hot rollback journal, leaving both files untouched. A journal that is
+    /// not hot is ignored.
+    pub fn attach_journal(&mut self, storage: Box<dyn Storage>) -> Result<()> {
+        let Some(journal) = Journal::open(storage, self.header.page_size)? else {
+            return Ok(());
+        };
+        let database_size = journal.database_size;
+        let restores_header = journal.contains(1);
+        self.pager.set_journal(journal);
+        if restores_header {
+            self.reread_header()?;
+        }
+        self.page_count = database_size;
+        Ok(())
+    }
+
+    fn reread_header(&mut self) -> Result<()> {
+        let page = self.pager.get(1)?;
+        let mut bytes = [0; 100];
+        bytes.copy_from_slice(&page[..100]);
+        self.header = DataBaseHeader::from_bytes(&bytes)?;
+        Ok(())
+    }
+
+    /// Reads committed pages from a write-ahead log on top of the main file.
+    /// The header is reparsed when the log holds a newer page 1.
+    pub fn attach_wal(&mut self, storage: Box<dyn Storage>) -> Result<()> {
+        let wal = Wal::open(storage, self.header.page_size)?;
+        let database_size = wal.database_size;
+        self.pager.set_wal(wal);
+        if self.pager.wal().is_some_and(|wal| wal.contains(1)) {
+            self.reread_header()?;
+        }
+        if let Some(size) = database_size {
+            self.page_count = size;
+        }
+        Ok(())
+    }
+
+    /// Opens a database read from any storage: a file, an in-memory buffer
+    /// or a seekable reader.
+    pub fn from_storage(mut storage: Box<dyn Storage>) -> Result<Self> {
+        let header = DataBaseHeader::new(storage.as_mut())?;
+        let file_pages = storage.size()?.div_ceil(header.page_size as u64).min(u32::MAX as u64) as u32;
+        // like sqlite, trust the in-header size only if it was written by a
+        // version that keeps it up to date
+        let page_count = if header.database_size != 0 && header.change_counter == header.version_valid_for {
+            header.database_size
+        } else {
+            file_pages
+        };
+        if page_count > file_pages {
+            return Err(DbError::corrupt_page(
+                1,
+                28,
+                format!("database size of {} pages is larger than the file of {} pages", page_count, file_pages),
+            ));
+        }
+        let pager = Pager::new(storage, header.page_size, cache_pages(header.default_cache_size, header.page_size));
 
         Ok(Self{
             header,
-            file
+            pager,
+            page_count,
         })
     }
 
-    pub fn root_page(&mut self, rp: u64) -> Result<()> {
-        let offset = (rp - 1) * self.header.page_size as u64;
-        self.file.seek(SeekFrom::Start(offset))?;
-        Ok(())
+    /// Reads the raw bytes of page `page_ind`, counting from 1.
+    pub fn read_page(&mut self, page_ind: u64) -> Result<PageBuf> {
+        self.pager.get(page_ind)
+    }
+
+    /// Sets the page cache size like `PRAGMA cache_size`: pages when
+    /// positive, KiB when negative.
+    pub fn set_cache_size(&mut self, cache_size: i32) {
+        self.pager.set_cache_pages(cache_pages(cache_size, self.header.page_size));
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.pager.stats()
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(page_size: u16) -> [u8; 100] {
+        let mut bytes = [0u8; 100];
+        bytes[..16].copy_from_slice(MAGIC);
+        bytes[16..18].copy_from_slice(&page_size.to_be_bytes());
+        bytes[18] = 1;
+        bytes[19] = 1;
+        bytes[21] = 64;
+        bytes[22] = 32;
+        bytes[23] = 32;
+        bytes[47] = 4;
+        bytes[59] = 1;
+        bytes
+    }
+
+    #[test]
+    fn validates_header() {
+        assert_eq!(DataBaseHeader::from_bytes(&header(4096)).unwrap().page_size, 4096);
+        assert_eq!(DataBaseHeader::from_bytes(&header(1)).unwrap().page_size, 65536);
+
+        let mut bytes = header(512);
+        bytes[20] = 32;
+        assert_eq!(DataBaseHeader::from_bytes(&bytes).unwrap().usable_size(), 480);
+        bytes[20] = 33;
+        assert!(matches!(DataBaseHeader::from_bytes(&bytes), Err(DbError::NotADatabase(_))));
+
+        for page_size in [0, 256, 1000, 3] {
+            assert!(matches!(
+                DataBaseHeader::from_bytes(&header(page_size)),
+                Err(DbError::NotADatabase(_))
+            ));
+        }
+
+        let mut bytes = header(4096);
+        bytes[0] = b's';
+        assert!(matches!(DataBaseHeader::from_bytes(&bytes), Err(DbError::NotADatabase(_))));
+
+        let mut bytes = header(4096);
+        bytes[21] = 50;
+        assert!(matches!(DataBaseHeader::from_bytes(&bytes), Err(DbError::NotADatabase(_))));
+
+        let mut bytes = header(4096);
+        bytes[47] = 5;
+        assert!(matches!(DataBaseHeader::from_bytes(&bytes), Err(DbError::UnsupportedFeature(_))));
+
+        let mut bytes = header(4096);
+        bytes[18] = 3;
+        assert_eq!(DataBaseHeader::from_bytes(&bytes).unwrap().write_version, 3);
+        bytes[19] = 3;
+        assert!(matches!(DataBaseHeader::from_bytes(&bytes), Err(DbError::UnsupportedFeature(_))));
+
+        let mut bytes = header(4096);
+        bytes[59] = 2;
+        assert!(matches!(DataBaseHeader::from_bytes(&bytes), Err(DbError::UnsupportedFeature(_))));
+    }
+}
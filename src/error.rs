@@ -0,0 +1,53 @@
+use thiserror::Error;
+
+use crate::sql::tokenizer::Span;
+
+/// Everything that can go wrong while reading a database or running a query.
+#[derive(Debug, Error)]
+pub enum DbError {
+    #[error("database disk image is malformed: page {page}, offset {offset}: {reason}")]
+    CorruptPage {
+        page: u64,
+        offset: usize,
+        reason: String,
+    },
+    /// Malformed bytes found while decoding a record, before it's known which
+    /// page they came from. `DbError::at` attaches the location.
+    #[error("database disk image is malformed: {0}")]
+    Corrupt(String),
+    #[error("file is not a database: {0}")]
+    NotADatabase(String),
+    #[error("unsupported feature: {0}")]
+    UnsupportedFeature(String),
+    #[error("{message}")]
+    SyntaxError { message: String, span: Span },
+    #[error("no such table: {0}")]
+    NoSuchTable(String),
+    #[error("no such column: {0}")]
+    NoSuchColumn(String),
+    /// Errors raised while evaluating a well-formed query, e.g. integer overflow.
+    #[error("{0}")]
+    Query(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, DbError>;
+
+impl DbError {
+    pub fn corrupt_page(page: u64, offset: usize, reason: impl Into<String>) -> Self {
+        DbError::CorruptPage {
+            page,
+            offset,
+            reason: reason.into(),
+        }
+    }
+
+    /// Pins a location-less `Corrupt` error to the page and offset it was found at.
+    pub fn at(self, page: u64, offset: usize) -> Self {
+        match self {
+            DbError::Corrupt(reason) => DbError::corrupt_page(page, offset, reason),
+            e => e,
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Databases built byte by byte for tests.
+
+use crate::db::DB;
+use crate::storage::MemoryStorage;
+use crate::value::Value;
+
+pub(crate) fn varint(v: usize) -> Vec<u8> {
+    if v < 128 {
+        vec![v as u8]
+    } else {
+        vec![0x80 | (v >> 7) as u8, (v & 0x7f) as u8]
+    }
+}
+
+/// A record of small integers, text and NULLs.
+pub(crate) fn record(values: &[Value]) -> Vec<u8> {
+    let (mut types, mut body) = (vec![], vec![]);
+    for v in values {
+        match v {
+            Value::Integer(i) => {
+                types.push(1);
+                body.push(*i as u8);
+            }
+            Value::Text(t) => {
+                types.extend(varint(13 + 2 * t.len()));
+                body.extend(t.bytes());
+            }
+            _ => types.push(0),
+        }
+    }
+    [varint(types.len() + 1), types, body].concat()
+}
+
+/// A 4096 byte leaf page with its header at `start` holding `cells`.
+pub(crate) fn leaf_page(page_type: u8, start: usize, cells: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut raw = vec![0; 4096];
+    let mut end = 4096;
+    raw[start] = page_type;
+    raw[start + 3..start + 5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+    for (i, cell) in cells.iter().enumerate() {
+        end -= cell.len();
+        raw[end..end + cell.len()].copy_from_slice(cell);
+        raw[start + 8 + 2 * i..start + 10 + 2 * i].copy_from_slice(&(end as u16).to_be_bytes());
+    }
+    raw[start + 5..start + 7].copy_from_slice(&(end as u16).to_be_bytes());
+    raw
+}
+
+/// Page 1 of a database of `page_count` pages with `schema` entries
+/// (type, name, rootpage, sql), all on table `t`.
+pub(crate) fn schema_page(schema: &[(&str, &str, i64, &str)], page_count: u32) -> Vec<u8> {
+    let string = |s: &str| Value::Text(s.to_string());
+    let cells = schema
+        .iter()
+        .enumerate()
+        .map(|(i, (kind, name, root, sql))| {
+            let r = record(&[string(kind), string(name), string("t"), Value::Integer(*root), string(sql)]);
+            [varint(r.len()), varint(i + 1), r].concat()
+        })
+        .collect();
+    let mut page1 = leaf_page(0x0d, 100, cells);
+    let sample = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+    page1[..100].copy_from_slice(&sample[..100]);
+    page1[28..32].copy_from_slice(&page_count.to_be_bytes());
+    page1
+}
+
+/// A two page database with the schema on page 1 and the rows of its only
+/// table `t` on page 2, a leaf index page if it is a WITHOUT ROWID table.
+/// `views` are added to the schema as (name, sql).
+pub(crate) fn one_table(sql: &str, rows: &[Vec<Value>], views: &[(&str, &str)]) -> Vec<u8> {
+    let mut schema = vec![("table", "t", 2, sql)];
+    schema.extend(views.iter().map(|&(name, sql)| ("view", name, 0, sql)));
+    let page1 = schema_page(&schema, 2);
+    let without_rowid = sql.ends_with("WITHOUT ROWID");
+    let cells = rows
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let r = record(r);
+            let rowid = if without_rowid { vec![] } else { varint(i + 1) };
+            [varint(r.len()), rowid, r].concat()
+        })
+        .collect();
+    let page_type = if without_rowid { 0x0a } else { 0x0d };
+    [page1, leaf_page(page_type, 0, cells)].concat()
+}
+
+/// Opens a database held in `raw`.
+pub(crate) fn open(raw: Vec<u8>) -> DB {
+    DB::from_storage(Box::new(MemoryStorage::from(raw))).unwrap()
+}
@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+
+use crate::error::{DbError, Result};
+
+use crate::db::DB;
+
+/// A freelist trunk page and the leaf pages it lists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrunkPage {
+    pub page: u32,
+    pub leaves: Vec<u32>,
+}
+
+/// Unused pages of the database, kept for reuse instead of shrinking the file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Freelist {
+    pub trunks: Vec<TrunkPage>,
+}
+
+impl Freelist {
+    /// Every free page, trunks and leaves, in chain order.
+    pub fn pages(&self) -> impl Iterator<Item = u32> + '_ {
+        self.trunks
+            .iter()
+            .flat_map(|trunk| std::iter::once(trunk.page).chain(trunk.leaves.iter().copied()))
+    }
+
+    /// Number of free pages, which the header stores at offset 36.
+    pub fn len(&self) -> usize {
+        self.trunks.iter().map(|trunk| 1 + trunk.leaves.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trunks.is_empty()
+    }
+}
+
+/// Follows the chain of trunk pages starting at header offset 32. Each trunk
+/// holds the next trunk page, the number of leaf pages and their numbers.
+pub fn read_freelist(db: &mut DB) -> Result<Freelist> {
+    let mut freelist = Freelist::default();
+    let max_leaves = db.header.usable_size() as usize / 4 - 2;
+    let page_count = db.page_count;
+    let mut seen = HashSet::new();
+
+    let mut next = db.header.first_freelist_trunk;
+    while next != 0 {
+        if !seen.insert(next) {
+            return Err(DbError::corrupt_page(next as u64, 0, "freelist trunk chain loops"));
+        }
+        let raw = db.read_page(next as u64)?;
+        let read_u32 = |offset: usize| u32::from_be_bytes([raw[offset], raw[offset + 1], raw[offset + 2], raw[offset + 3]]);
+        let count = read_u32(4) as usize;
+        if count > max_leaves {
+            return Err(DbError::corrupt_page(
+                next as u64,
+                4,
+                format!("freelist trunk lists {} leaves, at most {} fit", count, max_leaves),
+            ));
+        }
+        let mut leaves = Vec::with_capacity(count);
+        for i in 0..count {
+            let leaf = read_u32(8 + 4 * i);
+            if leaf == 0 || leaf > page_count || !seen.insert(leaf) {
+                return Err(DbError::corrupt_page(
+                    next as u64,
+                    8 + 4 * i,
+                    format!("invalid freelist leaf page {}", leaf),
+                ));
+            }
+            leaves.push(leaf);
+        }
+        freelist.trunks.push(TrunkPage { page: next, leaves });
+        next = read_u32(0);
+    }
+
+    Ok(freelist)
+}
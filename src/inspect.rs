@@ -0,0 +1,234 @@
+use std::fmt::Write;
+
+use crate::cell::{parse_cell_header, Cell, CellInfo, Content, ContentVariant};
+use crate::db::DB;
+use crate::error::{DbError, Result};
+use crate::freelist::read_freelist;
+use crate::output::sql_literal;
+use crate::page::{Page, PageType};
+use crate::value::Value;
+
+const LINE: usize = 16;
+
+fn page_type_name(page_type: &PageType) -> &'static str {
+    match page_type {
+        PageType::InteriorIndex => "interior index",
+        PageType::InteriorTable => "interior table",
+        PageType::LeafIndex => "leaf index",
+        PageType::LeafTable => "leaf table",
+    }
+}
+
+/// Describes page `page_no` for the `.page` command: its header fields, cell
+/// pointer array, cells with their decoded records, free space and a hexdump
+/// annotated with the region every line belongs to.
+pub fn describe_page(db: &mut DB, page_no: u64) -> Result<String> {
+    let page_count = db.page_count as u64;
+    if page_no == 0 || page_no > page_count {
+        return Err(DbError::Query(format!("page {} is out of range 1..={}", page_no, page_count)));
+    }
+    let raw = db.read_page(page_no)?;
+    let usable = (db.header.usable_size() as usize).min(raw.len());
+    let mut out = String::new();
+    // writing to a String never fails
+    let _ = writeln!(out, "page {} of {}: {} bytes, {} usable", page_no, page_count, raw.len(), usable);
+
+    let mut labels: Vec<Option<String>> = vec![None; raw.len()];
+    let mut label = |range: std::ops::Range<usize>, name: String| {
+        for l in &mut labels[range.start.min(raw.len())..range.end.min(raw.len())] {
+            l.get_or_insert_with(|| name.clone());
+        }
+    };
+    if page_no == 1 {
+        label(0..100, "file header".to_string());
+    }
+    label(usable..raw.len(), "reserved".to_string());
+
+    match Page::from_raw(raw.clone(), page_no, usable as u32) {
+        Ok(page) => describe_btree_page(db, &page, &mut out, &mut label),
+        Err(e) => {
+            let freelist = read_freelist(db)?;
+            let role = freelist.trunks.iter().find_map(|trunk| {
+                if trunk.page as u64 == page_no {
+                    Some(format!("freelist trunk page listing {} leaves", trunk.leaves.len()))
+                } else {
+                    trunk.leaves.contains(&(page_no as u32)).then(|| format!("freelist leaf of trunk {}", trunk.page))
+                }
+            });
+            match role {
+                Some(role) => {
+                    let _ = writeln!(out, "type: {}", role);
+                }
+                None => {
+                    let _ = writeln!(out, "type: not a b-tree page ({})", e);
+                }
+            }
+        }
+    }
+
+    let _ = writeln!(out, "hexdump:");
+    hexdump(&raw, &labels, &mut out);
+    Ok(out)
+}
+
+fn describe_btree_page(db: &mut DB, page: &Page, out: &mut String, label: &mut impl FnMut(std::ops::Range<usize>, String)) {
+    let start = if page.number == 1 { 100 } else { 0 };
+    let usable = (page.usable_size as usize).min(page.raw.len());
+    let type_byte = page.raw[start];
+    let _ = writeln!(out, "type: 0x{:02x} {}", type_byte, page_type_name(&page.page_type));
+    let _ = writeln!(out, "header at {}..{}:", start, start + page.header_offset);
+    let _ = writeln!(out, "  {:>5}  first freeblock       {}", start + 1, page.free_block_start);
+    let _ = writeln!(out, "  {:>5}  cell count            {}", start + 3, page.cell_count);
+    let _ = writeln!(out, "  {:>5}  cell content start    {}", start + 5, page.cell_start);
+    let _ = writeln!(out, "  {:>5}  fragmented bytes      {}", start + 7, page.free_block_size);
+    if let Some(right) = page.right_most_ptr {
+        let _ = writeln!(out, "  {:>5}  right-most pointer    {}", start + 8, right);
+    }
+    label(start..start + page.header_offset, "page header".to_string());
+
+    let array_start = start + page.header_offset;
+    let array_end = array_start + 2 * page.cell_ptrs.len();
+    let ptrs: Vec<String> = page.cell_ptrs.iter().map(|p| p.to_string()).collect();
+    let _ = writeln!(out, "cell pointers at {}..{}: {}", array_start, array_end, ptrs.join(" "));
+    label(array_start..array_end, "cell pointers".to_string());
+
+    let _ = writeln!(out, "cells:");
+    for (i, &offset) in page.cell_ptrs.iter().enumerate() {
+        let info = match CellInfo::new(page, offset) {
+            Ok(info) => info,
+            Err(e) => {
+                let _ = writeln!(out, "  cell {} at {}: {}", i, offset, e);
+                continue;
+            }
+        };
+        label(offset..offset + info.size, format!("cell {}", i));
+        let mut line = format!("  cell {} at {}..{}, {} bytes", i, offset, offset + info.size, info.size);
+        if let Some(left) = info.left_child {
+            let _ = write!(line, ", left child {}", left);
+        }
+        if let Some(rowid) = info.rowid {
+            let _ = write!(line, ", rowid {}", rowid);
+        }
+        if let Some(payload) = info.payload_size {
+            let local = payload - info.overflow.map_or(0, |(rest, _)| rest);
+            let _ = write!(line, ", payload {} bytes ({} local)", payload, local);
+        }
+        if let Some((rest, first)) = info.overflow {
+            let _ = write!(line, ", {} bytes overflow to page {}", rest, first);
+        }
+        let _ = writeln!(out, "{}", line);
+        if info.payload_size.is_none() {
+            continue;
+        }
+
+        if let Ok((types, _)) = parse_cell_header(page.raw.get(info.payload_start..usable).unwrap_or_default()) {
+            let types: Vec<String> = types.iter().map(|t| t.val.to_string()).collect();
+            let _ = writeln!(out, "    serial types: {}", types.join(" "));
+        }
+        match Cell::new(db, page, offset, ContentVariant::RowCell) {
+            Ok(Cell {
+                content: Content::RowCell(record),
+                ..
+            }) => {
+                let values: Vec<String> = record.row.iter().map(|r| sql_literal(&Value::from(r))).collect();
+                let _ = writeln!(out, "    values: {}", values.join(", "));
+            }
+            Ok(_) => (),
+            Err(e) => {
+                let _ = writeln!(out, "    {}", e);
+            }
+        }
+    }
+
+    if page.free_blocks.is_empty() {
+        let _ = writeln!(out, "freeblocks: none");
+    } else {
+        let _ = writeln!(out, "freeblocks:");
+    }
+    for block in &page.free_blocks {
+        let _ = writeln!(out, "  at {}..{}, {} bytes", block.offset, block.offset + block.size, block.size);
+        label(block.offset..block.offset + block.size, "freeblock".to_string());
+    }
+    let content_start = (page.cell_start as usize).clamp(array_end, usable);
+    let _ = writeln!(out, "unallocated: {}..{}, {} bytes", array_end, content_start, content_start - array_end);
+    label(array_end..content_start, "unallocated".to_string());
+    let _ = writeln!(out, "fragmented bytes: {}", page.free_block_size);
+    label(content_start..usable, "fragment".to_string());
+}
+
+/// 16 bytes per line with the labels of the regions they overlap. Runs of
+/// identical lines collapse into a `*` like `hexdump` does.
+fn hexdump(raw: &[u8], labels: &[Option<String>], out: &mut String) {
+    let mut previous: Option<(&[u8], Vec<&str>)> = None;
+    let mut collapsed = false;
+    for (n, chunk) in raw.chunks(LINE).enumerate() {
+        let offset = n * LINE;
+        let mut names: Vec<&str> = vec![];
+        for name in labels[offset..offset + chunk.len()].iter().flatten() {
+            if names.last() != Some(&name.as_str()) && !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+        let last = offset + LINE >= raw.len();
+        if !last && previous.as_ref().is_some_and(|(bytes, prev)| *bytes == chunk && *prev == names) {
+            if !collapsed {
+                let _ = writeln!(out, "*");
+                collapsed = true;
+            }
+            continue;
+        }
+        collapsed = false;
+
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        let _ = writeln!(out, "{:06x}  {:<47}  |{:<16}|  {}", offset, hex.join(" "), ascii, names.join(", "));
+        previous = Some((chunk, names));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::open;
+
+    fn describe(file: &str, page_no: u64) -> Result<String> {
+        let raw = std::fs::read(format!("{}/{}", env!("CARGO_MANIFEST_DIR"), file)).unwrap();
+        describe_page(&mut open(raw), page_no)
+    }
+
+    #[test]
+    fn describes_cells() {
+        let page = describe("sample.db", 2).unwrap();
+        assert!(page.contains("type: 0x0d leaf table"), "{}", page);
+        assert!(page.contains("cell 1 at 4054..4067, 13 bytes, rowid 2"), "{}", page);
+        assert!(page.contains("values: NULL, 'Fuji', 'Red'"), "{}", page);
+        assert!(describe("sample.db", 1).unwrap().contains("|SQLite format 3.|  file header"));
+
+        let interior = describe("tests/fixtures/deleted.db", 2).unwrap();
+        assert!(interior.contains("  right-most pointer    5\n"), "{}", interior);
+        assert!(interior.contains("cell 0 at 507..512, 5 bytes, left child 3, rowid 22\n"), "{}", interior);
+
+        assert_eq!(describe("sample.db", 0).unwrap_err().to_string(), "page 0 is out of range 1..=4");
+        assert_eq!(describe("sample.db", 5).unwrap_err().to_string(), "page 5 is out of range 1..=4");
+    }
+
+    #[test]
+    fn describes_free_space() {
+        // a row was deleted between the two left on the page
+        let page = describe("tests/fixtures/dropped.db", 6).unwrap();
+        assert!(page.contains("freeblocks:\n  at 487..500, 13 bytes\nunallocated: 12..473, 461 bytes\n"), "{}", page);
+        let lines: Vec<&str> = page.lines().skip_while(|l| *l != "hexdump:").skip(1).collect();
+        assert!(lines[0].ends_with("|  page header, cell pointers, unallocated"), "{}", lines[0]);
+        assert_eq!(lines[2], "*");
+        assert!(lines[6].ends_with("|  freeblock, cell 0"), "{}", lines[6]);
+
+        // the pages of a dropped table
+        let trunk = describe("tests/fixtures/dropped.db", 2).unwrap();
+        assert!(trunk.contains("type: freelist trunk page listing 3 leaves\n"), "{}", trunk);
+        let leaf = describe("tests/fixtures/dropped.db", 4).unwrap();
+        assert!(leaf.contains("type: freelist leaf of trunk 2\n"), "{}", leaf);
+    }
+}
@@ -0,0 +1,725 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use crate::error::Result;
+
+use crate::btree::{btree_cells, MAX_DEPTH};
+use crate::cell::{Cell, CellInfo, Content, ContentVariant};
+use crate::db::DB;
+use crate::freelist::read_freelist;
+use crate::page::{Page, PageType};
+use crate::query::Executor;
+use crate::schema::{read_schema, SchemaEntry, Table};
+use crate::sql::ast::{CreateIndex, IndexedColumn};
+use crate::sql::Parser;
+use crate::value::{Collation, Value};
+
+/// Page holding the byte at offset 2^30, which sqlite never uses.
+const PENDING_BYTE: u64 = 0x4000_0000;
+
+/// Checks the whole file like `PRAGMA integrity_check` and returns at most
+/// `max_errors` problems, none for a healthy database.
+///
+/// The freelist and every b-tree in `sqlite_schema` are walked to verify
+/// page types, cell bounds, freeblocks and fragmentation, key order and
+/// overflow chains, and that every page is used exactly once. Finally each
+/// index is compared against the rows of its table.
+pub fn integrity_check(db: &mut DB, max_errors: usize) -> Result<Vec<String>> {
+    check(db, max_errors, true)
+}
+
+/// `PRAGMA quick_check`: everything but comparing indexes against their tables.
+pub fn quick_check(db: &mut DB, max_errors: usize) -> Result<Vec<String>> {
+    check(db, max_errors, false)
+}
+
+fn check(db: &mut DB, max_errors: usize, compare_indexes: bool) -> Result<Vec<String>> {
+    let page_count = db.page_count;
+    let mut checker = Checker {
+        db,
+        page_count,
+        used: HashSet::new(),
+        problems: vec![],
+        max_errors,
+    };
+    checker.check_freelist();
+    checker.mark_reserved_pages();
+
+    let schema = match read_schema(checker.db) {
+        Ok(schema) => schema,
+        Err(e) => {
+            checker.problem(format!("sqlite_schema: {}", e));
+            vec![]
+        }
+    };
+    checker.check_tree(1, Tree::table());
+    for entry in schema.iter().filter(|e| e.rootpage != 0) {
+        let tree = checker.tree_for(entry, &schema);
+        checker.check_tree(entry.rootpage, tree);
+    }
+    for page in 1..=page_count {
+        if checker.is_full() {
+            break;
+        }
+        if !checker.used.contains(&page) {
+            checker.problem(format!("Page {}: never used", page));
+        }
+    }
+    for entry in schema.iter().filter(|e| compare_indexes && e.kind == "index" && e.rootpage != 0) {
+        checker.check_index(entry, &schema);
+    }
+
+    let mut problems = checker.problems;
+    if let Some(first) = problems.first_mut() {
+        *first = format!("*** in database main ***\n{}", first);
+    }
+    Ok(problems)
+}
+
+/// What a b-tree stores, and how its keys are ordered.
+#[derive(Debug, Clone)]
+struct Tree {
+    /// Table b-trees are keyed by rowid, index b-trees by record.
+    table: bool,
+    /// Collation and direction of each indexed column, the rest compare binary ascending.
+    columns: Vec<(Collation, bool)>,
+    /// Whether the order of index keys can be verified at all.
+    ordered: bool,
+}
+
+impl Tree {
+    fn table() -> Self {
+        Tree {
+            table: true,
+            columns: vec![],
+            ordered: true,
+        }
+    }
+
+    fn index(columns: Vec<(Collation, bool)>, ordered: bool) -> Self {
+        Tree {
+            table: false,
+            columns,
+            ordered,
+        }
+    }
+
+    fn compare(&self, a: &Key, b: &Key) -> Ordering {
+        match (a, b) {
+            (Key::Rowid(a), Key::Rowid(b)) => a.cmp(b),
+            (Key::Record(a), Key::Record(b)) => compare_records(&self.columns, a, b),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+fn compare_records(columns: &[(Collation, bool)], a: &[Value], b: &[Value]) -> Ordering {
+    for (i, (x, y)) in a.iter().zip(b).enumerate() {
+        let (collation, descending) = columns.get(i).copied().unwrap_or_default();
+        let order = collation.compare(x, y);
+        if order != Ordering::Equal {
+            return if descending { order.reverse() } else { order };
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+#[derive(Debug, Clone)]
+enum Key {
+    Rowid(i64),
+    Record(Vec<Value>),
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Key::Rowid(rowid) => write!(f, "Rowid {}", rowid),
+            Key::Record(_) => write!(f, "Index key"),
+        }
+    }
+}
+
+/// The constraint behind `sqlite_autoindex_<table>_<n>`, the n-th key of the
+/// table that needed an index. `None` when that can't be worked out reliably.
+fn autoindex_definition(entry: &SchemaEntry, schema: &[SchemaEntry]) -> Option<CreateIndex> {
+    let number: usize = entry.name.strip_prefix("sqlite_autoindex_")?.rsplit_once('_')?.1.parse().ok()?;
+    let sql = schema
+        .iter()
+        .find(|t| t.kind == "table" && t.name.eq_ignore_ascii_case(&entry.tbl_name))?
+        .sql
+        .as_ref()?;
+    let table = Parser::parse_create_table(sql).ok()?;
+    if table.without_rowid {
+        // the primary key is the table itself and doesn't get an index
+        return None;
+    }
+
+    let same_columns = |a: &[IndexedColumn], b: &[IndexedColumn]| {
+        a.len() == b.len()
+            && a.iter().zip(b).all(|(x, y)| {
+                x.name.as_ref().zip(y.name.as_ref()).is_some_and(|(x, y)| x.eq_ignore_ascii_case(y))
+                    && x.collation == y.collation
+            })
+    };
+    let mut keys: Vec<&Vec<IndexedColumn>> = vec![];
+    for key in &table.keys {
+        let rowid_alias = match key.as_slice() {
+            [IndexedColumn { name: Some(name), .. }] => table
+                .columns
+                .iter()
+                .any(|c| c.rowid_alias && c.name.eq_ignore_ascii_case(name)),
+            _ => false,
+        };
+        if !rowid_alias && !keys.iter().any(|k| same_columns(k, key)) {
+            keys.push(key);
+        }
+    }
+    let autoindexes = schema
+        .iter()
+        .filter(|e| e.kind == "index" && e.sql.is_none() && e.tbl_name.eq_ignore_ascii_case(&entry.tbl_name))
+        .count();
+    if autoindexes != keys.len() {
+        return None;
+    }
+    Some(CreateIndex {
+        name: entry.name.clone(),
+        table: entry.tbl_name.clone(),
+        unique: true,
+        columns: keys.get(number.checked_sub(1)?)?.to_vec(),
+        where_clause: None,
+    })
+}
+
+struct Checker<'a> {
+    db: &'a mut DB,
+    /// Size of the database in pages.
+    page_count: u32,
+    used: HashSet<u32>,
+    problems: Vec<String>,
+    max_errors: usize,
+}
+
+impl Checker<'_> {
+    /// Whether `max_errors` problems were found and the rest would be dropped.
+    fn is_full(&self) -> bool {
+        self.problems.len() >= self.max_errors
+    }
+
+    fn problem(&mut self, message: String) {
+        if !self.is_full() {
+            self.problems.push(message);
+        }
+    }
+
+    /// Records a reference to `page`, false if it is invalid or already in use.
+    fn mark(&mut self, page: u32, context: &str) -> bool {
+        if page == 0 || page > self.page_count {
+            self.problem(format!("{}: invalid page number {}", context, page));
+            return false;
+        }
+        if !self.used.insert(page) {
+            self.problem(format!("{}: 2nd reference to page {}", context, page));
+            return false;
+        }
+        true
+    }
+
+    fn check_freelist(&mut self) {
+        let freelist = match read_freelist(self.db) {
+            Ok(freelist) => freelist,
+            Err(e) => {
+                self.problem(format!("Freelist: {}", e));
+                return;
+            }
+        };
+        for page in freelist.pages() {
+            self.mark(page, "Freelist");
+        }
+        let expected = self.db.header.freelist_count as usize;
+        if freelist.len() != expected {
+            self.problem(format!("Freelist: size is {} but should be {}", freelist.len(), expected));
+        }
+    }
+
+    /// The pending byte page and, in auto-vacuum mode, the pointer map pages.
+    fn mark_reserved_pages(&mut self) {
+        let page_size = self.db.header.page_size as u64;
+        let pending = (PENDING_BYTE / page_size + 1) as u32;
+        if self.page_count >= pending {
+            self.mark(pending, "Pending byte page");
+        }
+        if self.db.header.largest_root_page != 0 {
+            let step = self.db.header.usable_size() / 5 + 1;
+            let mut page = 2;
+            while page <= self.page_count {
+                if page != pending {
+                    self.mark(page, "Pointer map");
+                }
+                page += step;
+            }
+        }
+    }
+
+    /// Key order of the b-tree described by `entry`.
+    fn tree_for(&mut self, entry: &SchemaEntry, schema: &[SchemaEntry]) -> Tree {
+        if entry.kind != "index" {
+            // WITHOUT ROWID tables are index b-trees, their root page tells
+            return match Page::new(self.db, entry.rootpage as u64) {
+                Ok(page) if matches!(page.page_type, PageType::InteriorIndex | PageType::LeafIndex) => {
+                    match Table::from_entry(entry) {
+                        Ok(table) => Tree::index(
+                            table.primary_key.iter().map(|k| (k.collation.unwrap_or_default(), k.descending)).collect(),
+                            table.primary_key.iter().all(|k| k.collation.is_some()),
+                        ),
+                        Err(_) => Tree::index(vec![], false),
+                    }
+                }
+                _ => Tree::table(),
+            };
+        }
+        let table = schema
+            .iter()
+            .find(|t| t.kind == "table" && t.name.eq_ignore_ascii_case(&entry.tbl_name))
+            .and_then(|t| Table::from_entry(t).ok());
+        let column_collation = |name: &str| {
+            table
+                .as_ref()
+                .and_then(|t| t.columns.iter().find(|c| c.name.eq_ignore_ascii_case(name)))
+                .and_then(|c| c.collation.as_deref())
+                .and_then(Collation::from_name)
+                .unwrap_or_default()
+        };
+
+        let Some(index) = self.index_definition(entry, schema) else {
+            // only binary collations can be checked without knowing the columns
+            let binary = table.as_ref().map_or(true, |t| t.columns.iter().all(|c| c.collation.is_none()));
+            return Tree::index(vec![], entry.sql.is_none() && binary);
+        };
+        let mut ordered = true;
+        let columns = index
+            .columns
+            .iter()
+            .map(|c| {
+                let collation = match (&c.collation, &c.name) {
+                    (Some(name), _) => Collation::from_name(name).unwrap_or_else(|| {
+                        ordered = false;
+                        Collation::Binary
+                    }),
+                    (None, Some(name)) => column_collation(name),
+                    (None, None) => Collation::Binary,
+                };
+                (collation, c.descending)
+            })
+            .collect();
+        Tree::index(columns, ordered)
+    }
+
+    /// Parses the SQL of an index. Automatic indexes have none, their columns
+    /// come from the PRIMARY KEY or UNIQUE constraint that created them.
+    fn index_definition(&mut self, entry: &SchemaEntry, schema: &[SchemaEntry]) -> Option<CreateIndex> {
+        let Some(sql) = &entry.sql else {
+            return autoindex_definition(entry, schema);
+        };
+        match Parser::parse_create_index(sql) {
+            Ok(index) => Some(index),
+            Err(_) => {
+                self.problem(format!("Index {}: malformed schema sql", entry.name));
+                None
+            }
+        }
+    }
+
+    fn check_tree(&mut self, root: u32, tree: Tree) {
+        let context = format!("Tree {}", root);
+        self.walk(&tree, root, root, &context, 0, None, None);
+    }
+
+    /// Checks the subtree at `page_no` whose keys have to lie between `lo`
+    /// and `hi`, returns the depth of its leaves.
+    #[allow(clippy::too_many_arguments)]
+    fn walk(
+        &mut self,
+        tree: &Tree,
+        root: u32,
+        page_no: u32,
+        context: &str,
+        depth: usize,
+        lo: Option<&Key>,
+        hi: Option<&Key>,
+    ) -> Option<usize> {
+        if depth > MAX_DEPTH {
+            self.problem(format!("Tree {} page {}: b-tree is too deep", root, page_no));
+            return None;
+        }
+        if !self.mark(page_no, context) {
+            return None;
+        }
+        let page = match Page::new(self.db, page_no as u64) {
+            Ok(page) => page,
+            Err(e) => {
+                self.problem(format!("Tree {} page {}: {}", root, page_no, e));
+                return None;
+            }
+        };
+        let is_table_page = matches!(page.page_type, PageType::InteriorTable | PageType::LeafTable);
+        if is_table_page != tree.table {
+            self.problem(format!(
+                "Tree {} page {}: {:?} page in {} b-tree",
+                root,
+                page_no,
+                page.page_type,
+                if tree.table { "a table" } else { "an index" }
+            ));
+            return None;
+        }
+
+        let cells = self.check_layout(root, &page);
+        let here = format!("Tree {} page {}", root, page_no);
+        let mut leaf_depth = None;
+        let mut prev: Option<Key> = lo.cloned();
+
+        for (i, info) in cells.iter().enumerate() {
+            let Some(info) = info else {
+                continue;
+            };
+            let cell_context = format!("{} cell {}", here, i);
+            let intact = info
+                .overflow
+                .map_or(true, |(remaining, first)| self.check_overflow(&cell_context, first, remaining));
+
+            // records are decoded to check their headers, unless the overflow chain is broken
+            let key = match info.rowid {
+                Some(rowid) => {
+                    if intact && info.left_child.is_none() {
+                        self.record(&page, info.offset, &cell_context);
+                    }
+                    Some(Key::Rowid(rowid))
+                }
+                None if intact => self.record(&page, info.offset, &cell_context).filter(|_| tree.ordered).map(Key::Record),
+                None => None,
+            };
+            if let Some(child) = info.left_child {
+                let child_depth = self.walk(tree, root, child, &cell_context, depth + 1, prev.as_ref(), key.as_ref().or(hi));
+                leaf_depth = self.same_depth(&here, leaf_depth, child_depth);
+            }
+            let Some(key) = key else {
+                continue;
+            };
+            let left_ok = prev.as_ref().map_or(true, |p| tree.compare(&key, p) == Ordering::Greater);
+            // the key of an interior table cell is the largest rowid of its left subtree
+            let right_ok = hi.map_or(true, |h| match tree.compare(&key, h) {
+                Ordering::Less => true,
+                Ordering::Equal => tree.table,
+                Ordering::Greater => false,
+            });
+            if !left_ok || !right_ok {
+                self.problem(format!("{}: {} out of order", cell_context, key));
+            }
+            prev = Some(key);
+        }
+
+        match page.right_most_ptr {
+            Some(right) => {
+                let context = format!("{} right child", here);
+                let child_depth = self.walk(tree, root, right, &context, depth + 1, prev.as_ref(), hi);
+                leaf_depth = self.same_depth(&here, leaf_depth, child_depth);
+                leaf_depth
+            }
+            None => Some(depth),
+        }
+    }
+
+    fn same_depth(&mut self, context: &str, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        match (a, b) {
+            (Some(a), Some(b)) if a != b => {
+                self.problem(format!("{}: Child page depth differs", context));
+                Some(a)
+            }
+            (a, b) => a.or(b),
+        }
+    }
+
+    fn record(&mut self, page: &Page, offset: usize, context: &str) -> Option<Vec<Value>> {
+        match Cell::new(self.db, page, offset, ContentVariant::RowCell) {
+            Ok(Cell {
+                content: Content::RowCell(record),
+                ..
+            }) => Some(record.row.iter().map(Value::from).collect()),
+            Ok(_) => None,
+            Err(e) => {
+                self.problem(format!("{}: {}", context, e));
+                None
+            }
+        }
+    }
+
+    /// Verifies that cells and freeblocks tile the cell content area without
+    /// overlapping, with the gaps adding up to the fragmented byte count.
+    fn check_layout(&mut self, root: u32, page: &Page) -> Vec<Option<CellInfo>> {
+        let here = format!("Tree {} page {}", root, page.number);
+        let usable = (page.usable_size as usize).min(page.raw.len());
+        let content_start = page.cell_start as usize;
+        let header_start = if page.number == 1 { 100 } else { 0 };
+        let array_end = header_start + page.header_offset + 2 * page.cell_count as usize;
+        if content_start < array_end || content_start > usable {
+            self.problem(format!("{}: cell content area starts at {} outside of the page", here, content_start));
+        }
+
+        let mut cells = vec![];
+        let mut extents = vec![];
+        for (i, &offset) in page.cell_ptrs.iter().enumerate() {
+            match CellInfo::new(page, offset) {
+                Ok(info) => {
+                    if offset < content_start {
+                        self.problem(format!("{} cell {}: starts before the cell content area", here, i));
+                    }
+                    extents.push((offset, info.size));
+                    cells.push(Some(info));
+                }
+                Err(e) => {
+                    self.problem(format!("{} cell {}: {}", here, i, e));
+                    cells.push(None);
+                }
+            }
+        }
+        for block in &page.free_blocks {
+            if block.offset < content_start {
+                self.problem(format!("{}: freeblock at {} is before the cell content area", here, block.offset));
+            }
+            extents.push((block.offset, block.size));
+        }
+
+        extents.sort_unstable();
+        let mut covered = 0;
+        let mut end = 0;
+        for &(offset, size) in &extents {
+            if offset < end {
+                self.problem(format!("Multiple uses for byte {} of page {}", offset, page.number));
+                return cells;
+            }
+            covered += size;
+            end = offset + size;
+        }
+        let fragmented = usable.saturating_sub(content_start).saturating_sub(covered);
+        if fragmented != page.free_block_size as usize {
+            self.problem(format!(
+                "Fragmentation of {} bytes reported as {} on page {}",
+                fragmented, page.free_block_size, page.number
+            ));
+        }
+        cells
+    }
+
+    /// Follows an overflow chain, false if it is broken.
+    fn check_overflow(&mut self, context: &str, first: u32, remaining: usize) -> bool {
+        let per_page = self.db.header.usable_size() as usize - 4;
+        let expected = remaining.div_ceil(per_page);
+        let mut next = first;
+        for n in 0..expected {
+            if next == 0 {
+                self.problem(format!(
+                    "{}: {} of {} pages missing from overflow list starting at {}",
+                    context,
+                    expected - n,
+                    expected,
+                    first
+                ));
+                return false;
+            }
+            if !self.mark(next, context) {
+                return false;
+            }
+            match self.db.read_page(next as u64) {
+                Ok(raw) => next = u32::from_be_bytes([raw[0], raw[1], raw[2], raw[3]]),
+                Err(e) => {
+                    self.problem(format!("{}: {}", context, e));
+                    return false;
+                }
+            }
+        }
+        if next != 0 {
+            self.problem(format!(
+                "{}: overflow list starting at {} continues past the end of the payload",
+                context, first
+            ));
+            return false;
+        }
+        true
+    }
+
+    /// Compares the entries of an index with the rows of its table.
+    fn check_index(&mut self, entry: &SchemaEntry, schema: &[SchemaEntry]) {
+        let Some(table_entry) = schema
+            .iter()
+            .find(|t| t.kind == "table" && t.name.eq_ignore_ascii_case(&entry.tbl_name))
+        else {
+            self.problem(format!("Index {}: no such table {}", entry.name, entry.tbl_name));
+            return;
+        };
+        let Ok(table) = Table::from_entry(table_entry) else {
+            return;
+        };
+        let rowid_table = Page::new(self.db, table.rootpage as u64)
+            .is_ok_and(|p| matches!(p.page_type, PageType::InteriorTable | PageType::LeafTable));
+        if !rowid_table {
+            return;
+        }
+        let index = match self.index_definition(entry, schema) {
+            Some(index) if index.where_clause.is_some() => return,
+            Some(index) => Some(index),
+            None if entry.sql.is_some() => return,
+            None => None,
+        };
+
+        let (Ok(rows), Ok(entries)) = (
+            btree_cells(self.db, table.rootpage as u64, ContentVariant::RowCell),
+            btree_cells(self.db, entry.rootpage as u64, ContentVariant::RowCell),
+        ) else {
+            // already reported while walking the b-trees
+            return;
+        };
+        let Some(index) = index else {
+            if rows.len() != entries.len() {
+                self.problem(format!("wrong # of entries in index {}", entry.name));
+            }
+            return;
+        };
+
+        let mut positions = vec![];
+        for column in &index.columns {
+            let Some(name) = &column.name else {
+                return;
+            };
+            let position = table.columns.iter().position(|c| c.name.eq_ignore_ascii_case(name));
+            match position {
+                Some(i) if table.columns[i].rowid_alias => positions.push(None),
+                Some(i) => positions.push(Some(i)),
+                None if ["rowid", "oid", "_rowid_"].iter().any(|r| name.eq_ignore_ascii_case(r)) => positions.push(None),
+                None => return,
+            }
+        }
+        let tree = self.tree_for(entry, schema);
+        let defaults = Executor::new(self.db)
+            .and_then(|mut executor| executor.column_defaults(&table))
+            .unwrap_or_default();
+
+        let mut expected: Vec<Vec<Value>> = rows
+            .iter()
+            .filter_map(|cell| {
+                let Content::RowCell(record) = &cell.content else {
+                    return None;
+                };
+                let rowid = Value::Integer(cell.rowid.as_ref()?.val as i64);
+                let mut key: Vec<Value> = positions
+                    .iter()
+                    .map(|p| match p {
+                        Some(i) => record
+                            .row
+                            .get(*i)
+                            .map(Value::from)
+                            .or_else(|| defaults.get(*i).cloned())
+                            .unwrap_or(Value::Null),
+                        None => rowid.clone(),
+                    })
+                    .collect();
+                key.push(rowid);
+                Some(key)
+            })
+            .collect();
+        let mut actual: Vec<Vec<Value>> = entries
+            .iter()
+            .filter_map(|cell| match &cell.content {
+                Content::RowCell(record) => Some(record.row.iter().map(Value::from).collect()),
+                _ => None,
+            })
+            .collect();
+        let cmp = |a: &Vec<Value>, b: &Vec<Value>| compare_records(&tree.columns, a, b);
+        expected.sort_by(cmp);
+        actual.sort_by(cmp);
+
+        let mut j = 0;
+        for key in &expected {
+            if self.is_full() {
+                break;
+            }
+            while j < actual.len() && cmp(&actual[j], key) == Ordering::Less {
+                j += 1;
+            }
+            if j < actual.len() && cmp(&actual[j], key) == Ordering::Equal {
+                j += 1;
+                continue;
+            }
+            let rowid = key.last().cloned().unwrap_or(Value::Null);
+            self.problem(format!("row {} missing from index {}", rowid, entry.name));
+        }
+        if expected.len() != actual.len() {
+            self.problem(format!("wrong # of entries in index {}", entry.name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{leaf_page, open, record, schema_page, varint};
+
+    fn cell(rowid: Option<i64>, values: &[Value]) -> Vec<u8> {
+        let r = record(values);
+        [varint(r.len()), rowid.map(|id| varint(id as usize)).unwrap_or_default(), r].concat()
+    }
+
+    /// Table `t(a, x DEFAULT 5)` on page 2 holding `rows` (rowid, a) and index `tx` on page 3 holding `keys` (x, rowid).
+    fn indexed(rows: &[(i64, i64)], keys: &[(i64, i64)]) -> DB {
+        let schema = [
+            ("table", "t", 2, "CREATE TABLE t(a, x DEFAULT 5)"),
+            ("index", "tx", 3, "CREATE INDEX tx ON t(x)"),
+        ];
+        let rows = rows.iter().map(|&(id, a)| cell(Some(id), &[Value::Integer(a)])).collect();
+        let keys = keys.iter().map(|&(x, id)| cell(None, &[Value::Integer(x), Value::Integer(id)])).collect();
+        open([schema_page(&schema, 3), leaf_page(0x0d, 0, rows), leaf_page(0x0a, 0, keys)].concat())
+    }
+
+    #[test]
+    fn accounts_for_every_page() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db");
+        let mut raw = std::fs::read(path).unwrap();
+        assert!(integrity_check(&mut open(raw.clone()), 100).unwrap().is_empty());
+
+        // claim one more fragmented byte on the last page
+        let page_size = u16::from_be_bytes([raw[16], raw[17]]) as usize;
+        let page = raw.len() / page_size;
+        raw[(page - 1) * page_size + 7] += 1;
+        let problems = integrity_check(&mut open(raw), 100).unwrap();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].ends_with(&format!("reported as 1 on page {}", page)), "{}", problems[0]);
+
+        // reporting stops after max_errors unused pages
+        let mut db = open([schema_page(&[], 6), vec![0; 5 * 4096]].concat());
+        let problems = integrity_check(&mut db, 2).unwrap();
+        assert_eq!(problems, ["*** in database main ***\nPage 2: never used", "Page 3: never used"]);
+    }
+
+    #[test]
+    fn checks_key_order() {
+        let problems = integrity_check(&mut indexed(&[(1, 1), (3, 3), (2, 2)], &[(5, 1), (5, 2), (5, 3)]), 100).unwrap();
+        assert_eq!(problems, ["*** in database main ***\nTree 2 page 2 cell 2: Rowid 2 out of order"]);
+        let problems = integrity_check(&mut indexed(&[(1, 1), (2, 2)], &[(5, 2), (5, 1)]), 100).unwrap();
+        assert_eq!(problems, ["*** in database main ***\nTree 3 page 3 cell 1: Index key out of order"]);
+    }
+
+    #[test]
+    fn compares_indexes_with_their_tables() {
+        // rows from before ADD COLUMN x DEFAULT 5 are indexed under the default
+        let mut db = indexed(&[(1, 1), (2, 2), (3, 3)], &[(5, 1), (5, 2), (5, 3)]);
+        assert_eq!(integrity_check(&mut db, 100).unwrap(), Vec::<String>::new());
+
+        let mut db = indexed(&[(1, 1), (2, 2), (3, 3)], &[(5, 1), (5, 3)]);
+        let problems = integrity_check(&mut db, 100).unwrap();
+        assert_eq!(problems, ["*** in database main ***\nrow 2 missing from index tx", "wrong # of entries in index tx"]);
+        let mut db = indexed(&[(1, 1), (3, 3)], &[(5, 1), (5, 2), (5, 3)]);
+        let problems = integrity_check(&mut db, 100).unwrap();
+        assert_eq!(problems, ["*** in database main ***\nwrong # of entries in index tx"]);
+        // quick_check leaves the indexes alone
+        assert!(quick_check(&mut db, 100).unwrap().is_empty());
+    }
+}
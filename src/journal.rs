@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use crate::error::{DbError, Result};
+use crate::pager::PageBuf;
+use crate::storage::Storage;
+
+const MAGIC: [u8; 8] = [0xd9, 0xd5, 0x05, 0xf9, 0x20, 0xa1, 0x63, 0xd7];
+
+// Rollback journal header, all fields big-endian, padded to the sector size
+// Offset	Size	Description
+// 0	8	Header string: 0xd9, 0xd5, 0x05, 0xf9, 0x20, 0xa1, 0x63, 0xd7
+// 8	4	The "Page Count" - The number of pages in the next segment of the journal, or -1 to mean all content to the end of the file
+// 12	4	A random nonce for the checksum
+// 16	4	Initial size of the database in pages
+// 20	4	Size of a disk sector assumed by the process that wrote this journal.
+// 24	4	Size of pages in this journal.
+//
+// Page record
+// 0	4	The page number in the database file
+// 4	N	Original content of the page prior to the start of the transaction
+// N+4	4	Checksum
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+/// sqlite only sums every 200th byte of a page, counting down from the end.
+fn checksum(nonce: u32, page: &[u8]) -> u32 {
+    let mut sum = nonce;
+    let mut i = page.len() as isize - 200;
+    while i > 0 {
+        sum = sum.wrapping_add(page[i as usize] as u32);
+        i -= 200;
+    }
+    sum
+}
+
+/// A hot rollback journal left behind by a transaction that never finished.
+/// Its page records hold the content from before the transaction, so
+/// reading them instead of the main file gives the rolled back database
+/// without writing to either file.
+pub struct Journal {
+    storage: Box<dyn Storage>,
+    page_size: u32,
+    /// Page number to the offset of its original content.
+    pages: HashMap<u64, u64>,
+    /// Size of the database in pages before the transaction.
+    pub database_size: u32,
+}
+
+impl Journal {
+    /// Parses the journal, `None` if it is not hot: empty, or with the header
+    /// zeroed out by a committed transaction. Page records are read up to the
+    /// first one with a bad checksum, like sqlite's playback does.
+    pub fn open(mut storage: Box<dyn Storage>, page_size: u32) -> Result<Option<Self>> {
+        let eof = |e: &std::io::Error| e.kind() == std::io::ErrorKind::UnexpectedEof;
+        let header = match storage.read_at(0, 28) {
+            Err(e) if eof(&e) => return Ok(None),
+            r => r?,
+        };
+        if header[..8] != MAGIC {
+            return Ok(None);
+        }
+        let database_size = read_u32(&header, 16);
+        let sector_size = read_u32(&header, 20) as u64;
+        if read_u32(&header, 24) != page_size || !(32..=65536).contains(&sector_size) {
+            return Err(DbError::Corrupt("hot journal header does not match the database".to_string()));
+        }
+
+        let mut journal = Self {
+            storage,
+            page_size,
+            pages: HashMap::new(),
+            database_size,
+        };
+        let record_size = page_size as u64 + 8;
+        let mut header_offset = 0;
+        'segments: loop {
+            let header = match journal.storage.read_at(header_offset, 28) {
+                Err(e) if eof(&e) => break,
+                r => r?,
+            };
+            if header[..8] != MAGIC {
+                break;
+            }
+            let records = match read_u32(&header, 8) {
+                u32::MAX => u64::MAX,
+                n => n as u64,
+            };
+            let nonce = read_u32(&header, 12);
+            let mut offset = header_offset + sector_size;
+            for _ in 0..records {
+                let record = match journal.storage.read_at(offset, record_size as usize) {
+                    Err(e) if eof(&e) => break 'segments,
+                    r => r?,
+                };
+                let page = read_u32(&record, 0) as u64;
+                let content = &record[4..4 + page_size as usize];
+                if page == 0 || checksum(nonce, content) != read_u32(&record, 4 + page_size as usize) {
+                    break 'segments;
+                }
+                journal.pages.entry(page).or_insert(offset + 4);
+                offset += record_size;
+            }
+            if records == u64::MAX {
+                break;
+            }
+            header_offset = offset.div_ceil(sector_size) * sector_size;
+        }
+        Ok(Some(journal))
+    }
+
+    pub fn contains(&self, page_ind: u64) -> bool {
+        self.pages.contains_key(&page_ind)
+    }
+
+    /// Number of pages the journal restores.
+    #[cfg(test)]
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// The content of page `page_ind` from before the interrupted transaction,
+    /// if the transaction changed it.
+    pub fn read_page(&mut self, page_ind: u64) -> Result<Option<PageBuf>> {
+        let Some(&offset) = self.pages.get(&page_ind) else {
+            return Ok(None);
+        };
+        Ok(Some(self.storage.read_at(offset, self.page_size as usize)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn record(page: u32, nonce: u32, content: &[u8]) -> Vec<u8> {
+        let mut raw = page.to_be_bytes().to_vec();
+        raw.extend_from_slice(content);
+        raw.extend_from_slice(&checksum(nonce, content).to_be_bytes());
+        raw
+    }
+
+    #[test]
+    fn reads_original_pages() {
+        let mut raw = MAGIC.to_vec();
+        for word in [u32::MAX, 42, 3, 512, 512] {
+            raw.extend_from_slice(&word.to_be_bytes());
+        }
+        raw.resize(512, 0);
+        raw.extend(record(2, 42, &[7; 512]));
+        raw.extend(record(2, 42, &[8; 512]));
+        raw.extend(record(3, 1, &[9; 512]));
+
+        let mut journal = Journal::open(Box::new(MemoryStorage::new(raw.clone())), 512).unwrap().unwrap();
+        assert_eq!(journal.database_size, 3);
+        assert_eq!(journal.page_count(), 1);
+        assert_eq!(journal.read_page(2).unwrap().unwrap()[0], 7);
+        assert!(!journal.contains(3));
+
+        // a committed transaction zeroes the header
+        raw[..8].fill(0);
+        assert!(Journal::open(Box::new(MemoryStorage::new(raw)), 512).unwrap().is_none());
+    }
+}
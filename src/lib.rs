@@ -0,0 +1,48 @@
+//! Minimal read-only SQLite file parser.
+//!
+//! ```no_run
+//! let mut db = codecrafters_sqlite::Database::open("sample.db")?;
+//! for row in db.query("SELECT name, color FROM apples")? {
+//!     let name: String = row.get_as(0)?;
+//!     println!("{}", name);
+//! }
+//! # Ok::<(), codecrafters_sqlite::DbError>(())
+//! ```
+
+pub(crate) mod analyze;
+pub(crate) mod btree;
+pub(crate) mod cell;
+pub(crate) mod database;
+pub(crate) mod db;
+pub(crate) mod error;
+#[cfg(test)]
+pub(crate) mod fixtures;
+pub(crate) mod freelist;
+pub(crate) mod inspect;
+pub(crate) mod integrity;
+pub(crate) mod journal;
+pub(crate) mod output;
+pub(crate) mod page;
+pub(crate) mod pager;
+pub(crate) mod query;
+pub(crate) mod recover;
+pub(crate) mod schema;
+pub(crate) mod sql;
+pub(crate) mod storage;
+pub(crate) mod utils;
+pub(crate) mod value;
+pub(crate) mod wal;
+
+pub use analyze::{Analysis, TreeStats};
+pub use database::{Database, Row, Rows, Statement};
+pub use db::{DataBaseHeader, StringEncoding};
+pub use error::{DbError, Result};
+pub use freelist::{Freelist, TrunkPage};
+pub use output::{sql_literal, OutputMode, OutputSettings};
+pub use pager::CacheStats;
+pub use recover::{RecoveredRecord, Source};
+pub use schema::SchemaEntry;
+#[cfg(feature = "mmap")]
+pub use storage::MmapStorage;
+pub use storage::{MemoryStorage, ReaderStorage, Storage};
+pub use value::{FromValue, Value};
@@ -1,36 +1,217 @@
-mod cell;
 mod commands;
-mod page;
-mod utils;
-mod db;
 
-use db::DB;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use codecrafters_sqlite::{Database, DbError, OutputMode, OutputSettings, Value};
 
 use anyhow::{bail, Ok, Result};
 
 // const ELEMENTS_OFFSETS: usize = 2;
 
 fn main() -> Result<()> {
-    let args = std::env::args().collect::<Vec<_>>();
-    match args.len() {
-        0 | 1 => bail!("Missing <database path> and <command>"),
-        2 => bail!("Missing <command>"),
-        _ => {}
+    let mut settings = OutputSettings::default();
+    let mut parameters = BTreeMap::new();
+    let mut positional = vec![];
+    let mut mmap = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        // flags may follow the database path, everything after the first command is a command
+        if positional.len() > 1 || !arg.starts_with('-') {
+            positional.push(arg);
+            continue;
+        }
+        match arg.trim_start_matches('-') {
+            "header" | "headers" => settings.headers = true,
+            "noheader" | "noheaders" => settings.headers = false,
+            "separator" => match args.next() {
+                Some(sep) => settings.separator = Some(sep),
+                None => bail!("Missing argument to -separator"),
+            },
+            "mmap" => match args.next().map(|n| n.parse::<u64>()) {
+                Some(std::result::Result::Ok(n)) => mmap = n > 0,
+                _ => bail!("Missing or invalid argument to -mmap"),
+            },
+            mode @ ("list" | "csv" | "json" | "markdown" | "table" | "box" | "line" | "tabs" | "insert" | "quote") => {
+                settings.set_mode(mode.parse()?)
+            }
+            _ => bail!("unknown option: {}", arg),
+        }
+    }
+
+    let Some(path) = positional.first() else {
+        bail!("Missing <database path>");
+    };
+    let mut db = match open(path, mmap) {
+        std::result::Result::Ok(db) => db,
+        Err(e) => {
+            eprintln!("Error: unable to open database \"{}\": {}", path, e);
+            std::process::exit(1);
+        }
+    };
+
+    if positional.len() > 1 {
+        for command in &positional[1..] {
+            if let Err(e) = run_command(&mut db, &mut settings, &mut parameters, command) {
+                report(command, &e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    repl(&mut db, &mut settings, &mut parameters)
+}
+
+#[cfg(feature = "mmap")]
+fn open(path: &str, mmap: bool) -> codecrafters_sqlite::Result<Database> {
+    if mmap {
+        Database::open_mmap(path)
+    } else {
+        Database::open(path)
+    }
+}
+
+#[cfg(not(feature = "mmap"))]
+fn open(path: &str, _mmap: bool) -> codecrafters_sqlite::Result<Database> {
+    Database::open(path)
+}
+
+/// Reads dot-commands and `;`-terminated SQL statements from stdin.
+fn repl(db: &mut Database, settings: &mut OutputSettings, parameters: &mut BTreeMap<String, Value>) -> Result<()> {
+    let interactive = io::stdin().is_terminal();
+    let mut statement = String::new();
+
+    loop {
+        if interactive {
+            print!("{}", if statement.is_empty() { "sqlite> " } else { "   ...> " });
+            io::stdout().flush()?;
+        }
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            break;
+        }
+
+        let command = if statement.is_empty() && line.trim_start().starts_with('.') {
+            line.trim().to_string()
+        } else {
+            statement.push_str(&line);
+            if !statement.trim_end().ends_with(';') {
+                continue;
+            }
+            std::mem::take(&mut statement)
+        };
+
+        if matches!(command.as_str(), ".quit" | ".exit") {
+            break;
+        }
+        if let Err(e) = run_command(db, settings, parameters, &command) {
+            report(&command, &e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints an error like sqlite3 does, pointing at the offending token of a syntax error.
+fn report(command: &str, e: &anyhow::Error) {
+    eprintln!("Error: {}", e);
+    if let Some(DbError::SyntaxError { span, .. }) = e.downcast_ref::<DbError>() {
+        if span.start > command.len() || !command.is_char_boundary(span.start) {
+            return;
+        }
+        let line_start = command[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = command[line_start..].find('\n').map_or(command.len(), |i| line_start + i);
+        let column = command[line_start..span.start].chars().count();
+        eprintln!("  {}", &command[line_start..line_end]);
+        eprintln!("  {}^--- error here", " ".repeat(column));
     }
+}
 
-    let mut db = DB::new(&args[1])?;
+/// Splits a dot-command into arguments like sqlite3 does: on whitespace,
+/// with '...' or "..." quoting an argument that contains spaces.
+fn dot_args(command: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut chars = command.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(c) = chars.next() else {
+            return args;
+        };
+        let mut arg = String::new();
+        if c == '\'' || c == '"' {
+            arg.extend(chars.by_ref().take_while(|&x| x != c));
+        } else {
+            arg.push(c);
+            while let Some(x) = chars.next_if(|x| !x.is_whitespace()) {
+                arg.push(x);
+            }
+        }
+        args.push(arg);
+    }
+}
 
-    let command = &args[2];
-    match command.as_str() {
+fn run_command(
+    db: &mut Database,
+    settings: &mut OutputSettings,
+    parameters: &mut BTreeMap<String, Value>,
+    command: &str,
+) -> Result<()> {
+    let mut words = command.split_whitespace();
+    match words.next().unwrap_or_default() {
         ".dbinfo" => {
-            commands::db_info(&mut db)?;
+            commands::db_info(db)?;
+        }
+        ".analyze" => {
+            commands::analyze(db)?;
+        }
+        ".check" => {
+            commands::check(db)?;
+        }
+        ".freelist" => {
+            commands::freelist(db)?;
+        }
+        ".page" => match words.next() {
+            Some(page_no) => commands::page(db, page_no.parse()?)?,
+            None => bail!("Usage: .page N"),
+        },
+        ".recover" => {
+            let min_confidence = match words.next() {
+                Some(min) => min.parse()?,
+                None => 0.6,
+            };
+            commands::recover(db, min_confidence)?;
         }
         ".tables" => {
-            commands::tables(&mut db)?;
+            commands::tables(db)?;
         }
-        _ => {
-            commands::sql_query(&mut db, command)?;
+        ".mode" => match words.next() {
+            Some(mode) => {
+                settings.set_mode(mode.parse::<OutputMode>()?);
+                if settings.mode == OutputMode::Insert {
+                    // `.mode insert` without a name goes back to "table" like in sqlite3
+                    settings.table = dot_args(command).get(2).cloned().unwrap_or_else(|| "table".to_string());
+                }
+            }
+            None => println!("current output mode: {}", settings.mode),
         },
+        ".headers" => match words.next() {
+            Some("on") => settings.headers = true,
+            Some("off") => settings.headers = false,
+            _ => bail!("Usage: .headers on|off"),
+        },
+        ".separator" => match words.next() {
+            Some(sep) => settings.separator = Some(sep.replace("\\t", "\t")),
+            None => bail!("Usage: .separator SEPARATOR"),
+        },
+        ".parameter" => {
+            commands::parameter(db, parameters, &dot_args(command)[1..])?;
+        }
+        dot if dot.starts_with('.') => bail!("unknown command: {}", dot),
+        _ => {
+            commands::sql_query(db, settings, parameters, command)?;
+        }
     }
 
     Ok(())
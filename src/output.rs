@@ -0,0 +1,584 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::error::{DbError, Result};
+use crate::value::{format_real, Value};
+
+/// The sqlite3 shell `.mode` options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    List,
+    Csv,
+    Json,
+    Markdown,
+    Table,
+    Box,
+    Line,
+    Tabs,
+    Insert,
+    Quote,
+}
+
+impl OutputMode {
+    const ALL: [OutputMode; 10] = [
+        OutputMode::List,
+        OutputMode::Csv,
+        OutputMode::Json,
+        OutputMode::Markdown,
+        OutputMode::Table,
+        OutputMode::Box,
+        OutputMode::Line,
+        OutputMode::Tabs,
+        OutputMode::Insert,
+        OutputMode::Quote,
+    ];
+
+    /// The name sqlite3 gives the mode, as accepted by `.mode`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputMode::List => "list",
+            OutputMode::Csv => "csv",
+            OutputMode::Json => "json",
+            OutputMode::Markdown => "markdown",
+            OutputMode::Table => "table",
+            OutputMode::Box => "box",
+            OutputMode::Line => "line",
+            OutputMode::Tabs => "tabs",
+            OutputMode::Insert => "insert",
+            OutputMode::Quote => "quote",
+        }
+    }
+
+    fn default_separator(&self) -> &'static str {
+        match self {
+            OutputMode::Csv | OutputMode::Quote => ",",
+            OutputMode::Tabs => "\t",
+            _ => "|",
+        }
+    }
+}
+
+impl FromStr for OutputMode {
+    type Err = DbError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        OutputMode::ALL
+            .into_iter()
+            .find(|mode| mode.name() == s)
+            .ok_or_else(|| DbError::UnsupportedFeature(format!("mode {}", s)))
+    }
+}
+
+impl std::fmt::Display for OutputMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Shell state controlling how result rows are printed.
+#[derive(Debug, Clone)]
+pub struct OutputSettings {
+    pub mode: OutputMode,
+    pub headers: bool,
+    /// Overrides the mode's default column separator, set by `.separator`.
+    pub separator: Option<String>,
+    /// Table name used by `insert` mode.
+    pub table: String,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            mode: OutputMode::List,
+            headers: false,
+            separator: None,
+            table: "table".to_string(),
+        }
+    }
+}
+
+impl OutputSettings {
+    /// Switches the mode the way `.mode` does, resetting the separator.
+    pub fn set_mode(&mut self, mode: OutputMode) {
+        self.mode = mode;
+        self.separator = None;
+    }
+
+    pub fn separator(&self) -> &str {
+        self.separator
+            .as_deref()
+            .unwrap_or(self.mode.default_separator())
+    }
+
+    pub fn formatter(&self) -> Box<dyn OutputFormatter> {
+        let separator = self.separator().to_string();
+        match self.mode {
+            OutputMode::List | OutputMode::Tabs => Box::new(ListFormatter {
+                separator,
+                headers: self.headers,
+            }),
+            OutputMode::Csv => Box::new(CsvFormatter {
+                separator,
+                headers: self.headers,
+            }),
+            OutputMode::Json => Box::new(JsonFormatter),
+            OutputMode::Markdown => Box::new(GridFormatter {
+                style: GridStyle::Markdown,
+            }),
+            OutputMode::Table => Box::new(GridFormatter {
+                style: GridStyle::Table,
+            }),
+            OutputMode::Box => Box::new(GridFormatter {
+                style: GridStyle::Box,
+            }),
+            OutputMode::Line => Box::new(LineFormatter),
+            OutputMode::Insert => Box::new(InsertFormatter {
+                table: self.table.clone(),
+                headers: self.headers,
+            }),
+            OutputMode::Quote => Box::new(QuoteFormatter {
+                separator,
+                headers: self.headers,
+            }),
+        }
+    }
+}
+
+/// Renders a complete result set. Implementations get every row up front
+/// because the grid modes need column widths before printing anything.
+pub trait OutputFormatter {
+    fn write(&self, out: &mut dyn Write, columns: &[String], rows: &[Vec<Value>]) -> io::Result<()>;
+}
+
+/// The bytes sqlite3 prints for a value: its text, or a blob as is. The shell
+/// prints blobs as C strings, so they end at the first NUL byte.
+fn display_bytes(v: &Value) -> Vec<u8> {
+    match v {
+        Value::Blob(b) => b.iter().take_while(|&&byte| byte != 0).copied().collect(),
+        v => v.to_string().into_bytes(),
+    }
+}
+
+/// Number of characters `bytes` take on screen, an invalid UTF-8 byte counting as one.
+fn display_width(bytes: &[u8]) -> usize {
+    String::from_utf8_lossy(bytes).chars().count()
+}
+
+/// Writes `fields` joined by `separator` as one line.
+fn write_fields(out: &mut dyn Write, fields: &[Vec<u8>], separator: &str) -> io::Result<()> {
+    out.write_all(&fields.join(separator.as_bytes()))?;
+    writeln!(out)
+}
+
+pub struct ListFormatter {
+    separator: String,
+    headers: bool,
+}
+
+impl OutputFormatter for ListFormatter {
+    fn write(&self, out: &mut dyn Write, columns: &[String], rows: &[Vec<Value>]) -> io::Result<()> {
+        if self.headers {
+            writeln!(out, "{}", columns.join(&self.separator))?;
+        }
+        for row in rows {
+            let fields: Vec<Vec<u8>> = row.iter().map(display_bytes).collect();
+            write_fields(out, &fields, &self.separator)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct CsvFormatter {
+    separator: String,
+    headers: bool,
+}
+
+impl CsvFormatter {
+    fn field(&self, s: &[u8]) -> Vec<u8> {
+        let separator = self.separator.as_bytes();
+        let needs_quote = s.is_empty()
+            || separator.is_empty()
+            || s.windows(separator.len()).any(|w| w == separator)
+            || s.iter().any(|&b| b <= b' ' || b == b'"' || b == b'\'' || b >= 0x7f);
+        if !needs_quote {
+            return s.to_vec();
+        }
+        let mut quoted = vec![b'"'];
+        for &b in s {
+            if b == b'"' {
+                quoted.push(b'"');
+            }
+            quoted.push(b);
+        }
+        quoted.push(b'"');
+        quoted
+    }
+}
+
+impl OutputFormatter for CsvFormatter {
+    fn write(&self, out: &mut dyn Write, columns: &[String], rows: &[Vec<Value>]) -> io::Result<()> {
+        if self.headers {
+            let fields: Vec<Vec<u8>> = columns.iter().map(|c| self.field(c.as_bytes())).collect();
+            write_fields(out, &fields, &self.separator)?;
+        }
+        for row in rows {
+            let fields: Vec<Vec<u8>> = row
+                .iter()
+                .map(|v| match v {
+                    Value::Null | Value::Integer(_) | Value::Real(_) => v.to_string().into_bytes(),
+                    _ => self.field(&display_bytes(v)),
+                })
+                .collect();
+            write_fields(out, &fields, &self.separator)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct JsonFormatter;
+
+fn json_string(s: &str) -> String {
+    let mut ret = String::from("\"");
+    json_escape(&mut ret, s);
+    ret.push('"');
+    ret
+}
+
+/// A blob as a JSON string the way sqlite3 writes it: valid UTF-8 as text
+/// and every other byte as the code point of the same value.
+fn json_blob(b: &[u8]) -> String {
+    let mut ret = String::from("\"");
+    for chunk in b.utf8_chunks() {
+        json_escape(&mut ret, chunk.valid());
+        for byte in chunk.invalid() {
+            ret.push_str(&format!("\\u{:04x}", byte));
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+fn json_escape(ret: &mut String, s: &str) {
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            '\u{8}' => ret.push_str("\\b"),
+            '\u{c}' => ret.push_str("\\f"),
+            c if (c as u32) < 0x20 || c == '\u{7f}' => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+}
+
+impl OutputFormatter for JsonFormatter {
+    fn write(&self, out: &mut dyn Write, columns: &[String], rows: &[Vec<Value>]) -> io::Result<()> {
+        for (i, row) in rows.iter().enumerate() {
+            let fields: Vec<String> = columns
+                .iter()
+                .zip(row)
+                .map(|(name, v)| {
+                    let v = match v {
+                        Value::Null => "null".to_string(),
+                        Value::Integer(_) | Value::Real(_) => v.to_string(),
+                        Value::Blob(b) => json_blob(b),
+                        _ => json_string(&v.to_string()),
+                    };
+                    format!("{}:{}", json_string(name), v)
+                })
+                .collect();
+            let open = if i == 0 { "[" } else { "" };
+            let close = if i + 1 == rows.len() { "]" } else { "," };
+            writeln!(out, "{}{{{}}}{}", open, fields.join(","), close)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum GridStyle {
+    Markdown,
+    Table,
+    Box,
+}
+
+pub struct GridFormatter {
+    style: GridStyle,
+}
+
+impl GridFormatter {
+    /// Horizontal rule for the given position: 0 top, 1 below header, 2 bottom.
+    fn rule(&self, widths: &[usize], position: usize) -> Option<String> {
+        let (line, left, mid, right) = match (self.style, position) {
+            (GridStyle::Markdown, 1) => ("-", "|", "|", "|"),
+            (GridStyle::Markdown, _) => return None,
+            (GridStyle::Table, _) => ("-", "+", "+", "+"),
+            (GridStyle::Box, 0) => ("─", "┌", "┬", "┐"),
+            (GridStyle::Box, 1) => ("─", "├", "┼", "┤"),
+            (GridStyle::Box, _) => ("─", "└", "┴", "┘"),
+        };
+        let segments: Vec<String> = widths.iter().map(|w| line.repeat(w + 2)).collect();
+        Some(format!("{}{}{}", left, segments.join(mid), right))
+    }
+
+    fn border(&self) -> &'static str {
+        match self.style {
+            GridStyle::Box => "│",
+            _ => "|",
+        }
+    }
+
+    /// Writes a row of already padded fields between borders.
+    fn write_line(&self, out: &mut dyn Write, fields: &[Vec<u8>]) -> io::Result<()> {
+        let border = self.border();
+        write!(out, "{} ", border)?;
+        out.write_all(&fields.join(format!(" {} ", border).as_bytes()))?;
+        writeln!(out, " {}", border)
+    }
+}
+
+fn pad_right(s: &[u8], width: usize) -> Vec<u8> {
+    let pad = width.saturating_sub(display_width(s));
+    [s, " ".repeat(pad).as_bytes()].concat()
+}
+
+fn center(s: &str, width: usize) -> String {
+    let pad = width.saturating_sub(s.chars().count());
+    format!("{}{}{}", " ".repeat(pad / 2), s, " ".repeat(pad - pad / 2))
+}
+
+impl OutputFormatter for GridFormatter {
+    fn write(&self, out: &mut dyn Write, columns: &[String], rows: &[Vec<Value>]) -> io::Result<()> {
+        let cells: Vec<Vec<Vec<u8>>> = rows
+            .iter()
+            .map(|row| row.iter().map(display_bytes).collect())
+            .collect();
+        let mut widths: Vec<usize> = columns.iter().map(|c| c.chars().count()).collect();
+        for row in &cells {
+            for (w, cell) in widths.iter_mut().zip(row) {
+                *w = (*w).max(display_width(cell));
+            }
+        }
+
+        if let Some(rule) = self.rule(&widths, 0) {
+            writeln!(out, "{}", rule)?;
+        }
+        let header: Vec<Vec<u8>> = columns.iter().zip(&widths).map(|(c, w)| center(c, *w).into_bytes()).collect();
+        self.write_line(out, &header)?;
+        if let Some(rule) = self.rule(&widths, 1) {
+            writeln!(out, "{}", rule)?;
+        }
+        for row in &cells {
+            let fields: Vec<Vec<u8>> = row.iter().zip(&widths).map(|(c, w)| pad_right(c, *w)).collect();
+            self.write_line(out, &fields)?;
+        }
+        if let Some(rule) = self.rule(&widths, 2) {
+            writeln!(out, "{}", rule)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct LineFormatter;
+
+impl OutputFormatter for LineFormatter {
+    fn write(&self, out: &mut dyn Write, columns: &[String], rows: &[Vec<Value>]) -> io::Result<()> {
+        // sqlite3 right-aligns the names to at least 5 characters
+        let width = columns.iter().map(|c| c.chars().count()).fold(5, usize::max);
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 {
+                writeln!(out)?;
+            }
+            for (name, v) in columns.iter().zip(row) {
+                let pad = width - name.chars().count();
+                write!(out, "{}{} = ", " ".repeat(pad), name)?;
+                out.write_all(&display_bytes(v))?;
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Renders a value as an SQL literal, as used by `quote` and `insert` modes.
+pub fn sql_literal(v: &Value) -> String {
+    match v {
+        Value::Null => "NULL".to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(r) => format_real(*r),
+        Value::Text(s) => format!("'{}'", s.replace('\'', "''")),
+        Value::Blob(b) => {
+            let hex: String = b.iter().map(|byte| format!("{:02x}", byte)).collect();
+            format!("X'{}'", hex)
+        }
+    }
+}
+
+pub struct InsertFormatter {
+    table: String,
+    headers: bool,
+}
+
+/// Every keyword of sqlite's SQL dialect, which `insert` mode quotes as names.
+const KEYWORDS: [&str; 147] = [
+    "ABORT", "ACTION", "ADD", "AFTER", "ALL", "ALTER", "ALWAYS", "ANALYZE", "AND", "AS", "ASC", "ATTACH",
+    "AUTOINCREMENT", "BEFORE", "BEGIN", "BETWEEN", "BY", "CASCADE", "CASE", "CAST", "CHECK", "COLLATE", "COLUMN",
+    "COMMIT", "CONFLICT", "CONSTRAINT", "CREATE", "CROSS", "CURRENT", "CURRENT_DATE", "CURRENT_TIME",
+    "CURRENT_TIMESTAMP", "DATABASE", "DEFAULT", "DEFERRABLE", "DEFERRED", "DELETE", "DESC", "DETACH", "DISTINCT",
+    "DO", "DROP", "EACH", "ELSE", "END", "ESCAPE", "EXCEPT", "EXCLUDE", "EXCLUSIVE", "EXISTS", "EXPLAIN", "FAIL",
+    "FILTER", "FIRST", "FOLLOWING", "FOR", "FOREIGN", "FROM", "FULL", "GENERATED", "GLOB", "GROUP", "GROUPS",
+    "HAVING", "IF", "IGNORE", "IMMEDIATE", "IN", "INDEX", "INDEXED", "INITIALLY", "INNER", "INSERT", "INSTEAD",
+    "INTERSECT", "INTO", "IS", "ISNULL", "JOIN", "KEY", "LAST", "LEFT", "LIKE", "LIMIT", "MATCH", "MATERIALIZED",
+    "NATURAL", "NO", "NOT", "NOTHING", "NOTNULL", "NULL", "NULLS", "OF", "OFFSET", "ON", "OR", "ORDER", "OTHERS",
+    "OUTER", "OVER", "PARTITION", "PLAN", "PRAGMA", "PRECEDING", "PRIMARY", "QUERY", "RAISE", "RANGE", "RECURSIVE",
+    "REFERENCES", "REGEXP", "REINDEX", "RELEASE", "RENAME", "REPLACE", "RESTRICT", "RETURNING", "RIGHT",
+    "ROLLBACK", "ROW", "ROWS", "SAVEPOINT", "SELECT", "SET", "TABLE", "TEMP", "TEMPORARY", "THEN", "TIES", "TO",
+    "TRANSACTION", "TRIGGER", "UNBOUNDED", "UNION", "UNIQUE", "UPDATE", "USING", "VACUUM", "VALUES", "VIEW",
+    "VIRTUAL", "WHEN", "WHERE", "WINDOW", "WITH", "WITHOUT",
+];
+
+/// A table or column name as sqlite3 writes it in `insert` mode, in double
+/// quotes unless it is a plain identifier that isn't a keyword.
+fn quote_name(name: &str) -> String {
+    let plain = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(name));
+    if plain {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+impl OutputFormatter for InsertFormatter {
+    fn write(&self, out: &mut dyn Write, columns: &[String], rows: &[Vec<Value>]) -> io::Result<()> {
+        let target = if self.headers {
+            let names: Vec<String> = columns.iter().map(|c| quote_name(c)).collect();
+            format!("{}({})", quote_name(&self.table), names.join(","))
+        } else {
+            quote_name(&self.table)
+        };
+        for row in rows {
+            let values: Vec<String> = row.iter().map(sql_literal).collect();
+            writeln!(out, "INSERT INTO {} VALUES({});", target, values.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+pub struct QuoteFormatter {
+    separator: String,
+    headers: bool,
+}
+
+impl OutputFormatter for QuoteFormatter {
+    fn write(&self, out: &mut dyn Write, columns: &[String], rows: &[Vec<Value>]) -> io::Result<()> {
+        if self.headers {
+            let names: Vec<String> = columns
+                .iter()
+                .map(|c| sql_literal(&Value::Text(c.clone())))
+                .collect();
+            writeln!(out, "{}", names.join(&self.separator))?;
+        }
+        for row in rows {
+            let values: Vec<String> = row.iter().map(sql_literal).collect();
+            writeln!(out, "{}", values.join(&self.separator))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(settings: &OutputSettings) -> String {
+        let rows = vec![
+            vec![Value::Integer(1), Value::Text("a,b".to_string())],
+            vec![Value::Integer(22), Value::Null],
+        ];
+        String::from_utf8(render_rows(settings, rows)).unwrap()
+    }
+
+    fn render_rows(settings: &OutputSettings, rows: Vec<Vec<Value>>) -> Vec<u8> {
+        let columns = vec!["id".to_string(), "name".to_string()];
+        let mut out = vec![];
+        settings
+            .formatter()
+            .write(&mut out, &columns, &rows)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn modes_match_sqlite3() {
+        let mut settings = OutputSettings::default();
+        assert_eq!(render(&settings), "1|a,b\n22|\n");
+
+        settings.headers = true;
+        settings.set_mode(OutputMode::Csv);
+        assert_eq!(render(&settings), "id,name\n1,\"a,b\"\n22,\n");
+
+        settings.set_mode(OutputMode::Json);
+        assert_eq!(
+            render(&settings),
+            "[{\"id\":1,\"name\":\"a,b\"},\n{\"id\":22,\"name\":null}]\n"
+        );
+
+        settings.set_mode(OutputMode::Table);
+        assert_eq!(
+            render(&settings),
+            "+----+------+\n| id | name |\n+----+------+\n| 1  | a,b  |\n| 22 |      |\n+----+------+\n"
+        );
+
+        settings.set_mode(OutputMode::Line);
+        assert_eq!(render(&settings), "   id = 1\n name = a,b\n\n   id = 22\n name = \n");
+
+        settings.set_mode(OutputMode::Insert);
+        assert_eq!(
+            render(&settings),
+            "INSERT INTO \"table\"(id,name) VALUES(1,'a,b');\nINSERT INTO \"table\"(id,name) VALUES(22,NULL);\n"
+        );
+
+        // blobs are written as raw bytes, up to a NUL byte outside of json
+        let blobs = || vec![vec![Value::Blob(vec![0x41, 0xff]), Value::Blob(vec![0x00, 0xff])]];
+        let mut settings = OutputSettings::default();
+        assert_eq!(render_rows(&settings, blobs()), b"A\xff|\n");
+        settings.set_mode(OutputMode::Csv);
+        assert_eq!(render_rows(&settings, blobs()), b"\"A\xff\",\"\"\n");
+        settings.set_mode(OutputMode::Tabs);
+        assert_eq!(render_rows(&settings, blobs()), b"A\xff\t\n");
+        settings.set_mode(OutputMode::Json);
+        assert_eq!(render_rows(&settings, blobs()), b"[{\"id\":\"A\\u00ff\",\"name\":\"\\u0000\\u00ff\"}]\n");
+        settings.set_mode(OutputMode::Markdown);
+        assert_eq!(render_rows(&settings, blobs()), b"| id | name |\n|----|------|\n| A\xff |      |\n");
+        settings.set_mode(OutputMode::Quote);
+        assert_eq!(render_rows(&settings, blobs()), b"X'41ff',X'00ff'\n");
+        settings.set_mode(OutputMode::Insert);
+        settings.table = "foo".to_string();
+        assert_eq!(render_rows(&settings, blobs()), b"INSERT INTO foo VALUES(X'41ff',X'00ff');\n");
+        settings.table = "select".to_string();
+        assert_eq!(render_rows(&settings, blobs()), b"INSERT INTO \"select\" VALUES(X'41ff',X'00ff');\n");
+    }
+
+    #[test]
+    fn mode_names_round_trip() {
+        for mode in OutputMode::ALL {
+            assert_eq!(mode.name().parse::<OutputMode>().unwrap(), mode);
+        }
+        assert_eq!(OutputMode::Csv.to_string(), "csv");
+        assert!("Csv".parse::<OutputMode>().is_err());
+    }
+
+    #[test]
+    fn reals_use_sqlite_formatting() {
+        assert_eq!(format_real(2.0), "2.0");
+        assert_eq!(format_real(0.1), "0.1");
+        assert_eq!(format_real(1.0 / 3.0), "0.333333333333333");
+        assert_eq!(format_real(1e20), "1.0e+20");
+        assert_eq!(format_real(2.5e-7), "2.5e-07");
+    }
+}
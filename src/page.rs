@@ -1,10 +1,9 @@
-use anyhow::{Ok, Result};
-use core::panic;
 use std::fmt;
 // use std::fs::File;
-use std::io::prelude::*;
 
-use crate::{cell::{Cell, Content, ContentVariant}, db::DB};
+use crate::{cell::{Cell, ContentVariant}, db::DB};
+use crate::error::{DbError, Result};
+use crate::pager::PageBuf;
 
 #[derive(Debug)]
 pub enum PageType {
@@ -15,35 +14,32 @@ pub enum PageType {
 }
 
 
-pub fn tables_from_page(page: &Page) -> Result<Vec<Cell>> {
+/// Parses every cell on the page in cell pointer order.
+pub fn cells_from_page(db: &mut DB, page: &Page, content_type: ContentVariant) -> Result<Vec<Cell>> {
     let mut cells = vec![];
     for cell_start in &page.cell_ptrs {
-        cells.push(Cell::new(&page.raw[cell_start.clone()..], &page.page_type, ContentVariant::TableCell)?);
+        cells.push(Cell::new(db, page, *cell_start, content_type)?);
     }
 
-    cells.retain(|c| {
-        if let Content::TableCell(c) = &c.content {
-            c.is_table()
-        } else { false }
-    });
     Ok(cells)
 }
 
-pub fn rows_from_page(page: &Page) -> Result<Vec<Content>> {
-    let mut cells = vec![];
-    for cell_start in &page.cell_ptrs {
-        cells.push(Cell::new(&page.raw[cell_start.clone()..], &page.page_type, ContentVariant::RowCell)?.content);
-    }
-
-    Ok(cells)
-}
 
 
+/// An entry of the freeblock chain, unused space between cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreeBlock {
+    pub offset: usize,
+    pub size: usize,
+}
 
 #[derive(Debug)]
 pub struct Page {
-    pub raw: Vec<u8>,
-    pub size: u16,
+    pub raw: PageBuf,
+    pub number: u64,
+    pub size: u32,
+    /// Page size minus the reserved bytes at the end of the page.
+    pub usable_size: u32,
     pub cell_count: u16,
     pub page_type: PageType,
     pub header_offset: usize,
@@ -52,40 +48,54 @@ pub struct Page {
     pub cell_start: u32,
     pub cell_ptrs: Vec<usize>,
     pub right_most_ptr: Option<u32>,
+    pub free_blocks: Vec<FreeBlock>,
 }
 
 impl Page {
     pub fn new(db: &mut DB, page_ind: u64) -> Result<Self> {
-        db.root_page(page_ind)?;
-        if db.header.page_size < 8 {
-            panic!("Page Size is smaller than page header!");
-        }
-        let page_header_start = if db.file.stream_position()? == 0 {
+        let raw = db.read_page(page_ind)?;
+        Self::from_raw(raw, page_ind, db.header.usable_size())
+    }
+
+    /// Parses the b-tree page header, cell pointer array and freeblock chain of
+    /// an already read page. Everything past `usable_size` is reserved space.
+    pub fn from_raw(raw: impl Into<PageBuf>, page_ind: u64, usable_size: u32) -> Result<Self> {
+        let raw = raw.into();
+        let size = raw.len() as u32;
+        let usable = (usable_size as usize).min(raw.len());
+        let page_header_start = if page_ind == 1 {
             100
         } else {
             0
         };
+        let corrupt = |offset: usize, reason: &str| DbError::corrupt_page(page_ind, offset, reason);
+        if usable < page_header_start + 12 {
+            return Err(corrupt(0, "page is smaller than the page header"));
+        }
+
         let mut ind = page_header_start;
+        let page_type = match raw[ind] {
+            0x02 => PageType::InteriorIndex,
+            0x05 => PageType::InteriorTable,
+            0x0a => PageType::LeafIndex,
+            0x0d => PageType::LeafTable,
+            page_type => return Err(corrupt(ind, &format!("unknown page type {}", page_type))),
+        };
         let mut page = Self {
-            raw: vec![0u8; db.header.page_size as usize],
+            raw,
+            number: page_ind,
             cell_count: 0,
-            page_type: PageType::InteriorIndex,
+            page_type,
             header_offset: 0,
             free_block_start: 0,
-            size: db.header.page_size,
+            size,
+            usable_size,
             cell_start: 0,
             cell_ptrs: vec![],
             free_block_size: 0,
             right_most_ptr: None,
+            free_blocks: vec![],
         };
-        db.file.read_exact(&mut page.raw)?;
-        match page.raw[ind] {
-            0x02 => page.page_type = PageType::InteriorIndex,
-            0x05 => page.page_type = PageType::InteriorTable,
-            0x0a => page.page_type = PageType::LeafIndex,
-            0x0d => page.page_type = PageType::LeafTable,
-            page_type => panic!("Unknown Page Type {}!", page_type),
-        }
         ind += 1;
         page.free_block_start = u16::from_be_bytes([page.raw[ind], page.raw[ind + 1]]);
         ind += 2;
@@ -110,24 +120,58 @@ impl Page {
             }
             _ => (),
         }
+        let content_start = ind + 2 * page.cell_count as usize;
+        if content_start > usable {
+            return Err(corrupt(ind, "cell pointer array runs past the end of the page"));
+        }
         for _ in 0..page.cell_count {
-            page.cell_ptrs
-                .push(u16::from_be_bytes([page.raw[ind], page.raw[ind + 1]]) as usize);
+            let ptr = u16::from_be_bytes([page.raw[ind], page.raw[ind + 1]]) as usize;
+            if ptr < content_start || ptr >= usable {
+                return Err(corrupt(ind, &format!("cell pointer {} is outside the cell content area", ptr)));
+            }
+            page.cell_ptrs.push(ptr);
             ind += 2;
         }
         match page.page_type {
             PageType::InteriorIndex | PageType::InteriorTable => page.header_offset = 12,
             _ => page.header_offset = 8,
         }
+        page.free_blocks = free_blocks(&page.raw[..usable], page.free_block_start as usize, ind)
+            .map_err(|(offset, reason)| corrupt(offset, &reason))?;
         Ok(page)
     }
 }
 
+/// Follows the freeblock chain starting at `first`. Blocks have to lie between
+/// the end of the cell pointer array and the end of the usable area, in
+/// ascending order without overlapping.
+fn free_blocks(usable: &[u8], first: usize, content_start: usize) -> std::result::Result<Vec<FreeBlock>, (usize, String)> {
+    let mut blocks = vec![];
+    let mut offset = first;
+    while offset != 0 {
+        if offset < content_start || offset + 4 > usable.len() {
+            return Err((offset, format!("freeblock at {} is outside the cell content area", offset)));
+        }
+        let next = u16::from_be_bytes([usable[offset], usable[offset + 1]]) as usize;
+        let size = u16::from_be_bytes([usable[offset + 2], usable[offset + 3]]) as usize;
+        if size < 4 || offset + size > usable.len() {
+            return Err((offset, format!("freeblock of {} bytes runs past the usable area", size)));
+        }
+        if next != 0 && next <= offset + size {
+            return Err((offset, format!("freeblock chain is not ascending at {}", next)));
+        }
+        blocks.push(FreeBlock { offset, size });
+        offset = next;
+    }
+    Ok(blocks)
+}
+
 impl fmt::Display for Page {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Page {{")?;
         writeln!(f, "\tPageType: {:?}", self.page_type)?;
         writeln!(f, "\tsize: {}", self.size)?;
+        writeln!(f, "\tusable_size: {}", self.usable_size)?;
         writeln!(f, "\tcell_count: {}", self.cell_count)?;
         writeln!(f, "\theader_offset: {}", self.header_offset)?;
         writeln!(f, "\tfree_block_start: {}", self.free_block_start)?;
@@ -135,3 +179,68 @@ impl fmt::Display for Page {
         writeln!(f, "}}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_corrupt_headers() {
+        let mut raw = vec![0u8; 512];
+        raw[0] = 0x07;
+        assert!(matches!(
+            Page::from_raw(raw.clone(), 2, 512),
+            Err(DbError::CorruptPage { page: 2, offset: 0, .. })
+        ));
+
+        raw[0] = 0x0d;
+        raw[3..5].copy_from_slice(&300u16.to_be_bytes());
+        assert!(matches!(
+            Page::from_raw(raw.clone(), 2, 512),
+            Err(DbError::CorruptPage { page: 2, .. })
+        ));
+
+        raw[3..5].copy_from_slice(&1u16.to_be_bytes());
+        raw[8..10].copy_from_slice(&500u16.to_be_bytes());
+        let page = Page::from_raw(raw.clone(), 2, 512).unwrap();
+        assert_eq!(page.cell_ptrs, [500]);
+
+        // the cell pointer lands in the reserved space
+        assert!(Page::from_raw(raw.clone(), 2, 480).is_err());
+
+        // or in the cell pointer array itself
+        raw[3..5].copy_from_slice(&2u16.to_be_bytes());
+        raw[8..10].copy_from_slice(&10u16.to_be_bytes());
+        raw[10..12].copy_from_slice(&500u16.to_be_bytes());
+        assert!(matches!(
+            Page::from_raw(raw.clone(), 2, 512),
+            Err(DbError::CorruptPage { page: 2, offset: 8, .. })
+        ));
+        raw[8..10].copy_from_slice(&12u16.to_be_bytes());
+        assert!(Page::from_raw(raw, 2, 512).is_ok());
+    }
+
+    #[test]
+    fn parses_freeblock_chain() {
+        let mut raw = vec![0u8; 512];
+        raw[0] = 0x0d;
+        raw[1..3].copy_from_slice(&100u16.to_be_bytes());
+        raw[100..104].copy_from_slice(&[0, 200, 0, 10]);
+        raw[200..204].copy_from_slice(&[0, 0, 0, 20]);
+        let page = Page::from_raw(raw.clone(), 2, 512).unwrap();
+        assert_eq!(
+            page.free_blocks,
+            [FreeBlock { offset: 100, size: 10 }, FreeBlock { offset: 200, size: 20 }]
+        );
+
+        // a block pointing backwards would loop forever
+        raw[200..204].copy_from_slice(&[0, 100, 0, 20]);
+        assert!(matches!(
+            Page::from_raw(raw.clone(), 2, 512),
+            Err(DbError::CorruptPage { page: 2, offset: 200, .. })
+        ));
+
+        raw[200..204].copy_from_slice(&[0, 0, 0, 20]);
+        assert!(Page::from_raw(raw, 2, 210).is_err());
+    }
+}
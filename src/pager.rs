@@ -0,0 +1,282 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+
+use crate::error::{DbError, Result};
+use crate::storage::Storage;
+use crate::journal::Journal;
+use crate::wal::Wal;
+
+/// Cache size sqlite uses when the header leaves it at 0: 2000 KiB.
+const DEFAULT_CACHE_SIZE: i32 = -2000;
+
+/// The bytes of one page, shared between the cache and every `Page` parsed
+/// from it. Either an owned copy or a window into a buffer holding the whole
+/// file, like a memory mapping. `slice` narrows the window without copying,
+/// which is how records borrow their text and blob fields.
+#[derive(Clone)]
+pub struct PageBuf {
+    buf: Buf,
+    range: Range<usize>,
+}
+
+#[derive(Clone)]
+enum Buf {
+    Owned(Arc<[u8]>),
+    Shared(Arc<dyn AsRef<[u8]> + Send + Sync>),
+}
+
+impl PageBuf {
+    /// Borrows `range` of `owner` without copying it.
+    pub fn shared(owner: Arc<dyn AsRef<[u8]> + Send + Sync>, range: Range<usize>) -> Self {
+        Self { buf: Buf::Shared(owner), range }
+    }
+
+    /// Whether the bytes are borrowed rather than a copy.
+    pub fn is_shared(&self) -> bool {
+        matches!(self.buf, Buf::Shared(..))
+    }
+
+    /// The bytes in `range` of this buffer, sharing its storage. Panics like
+    /// slicing when `range` is out of bounds.
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        assert!(range.start <= range.end && range.end <= self.len(), "{:?} out of bounds of {:?}", range, self);
+        let start = self.range.start + range.start;
+        Self { buf: self.buf.clone(), range: start..start + range.len() }
+    }
+}
+
+impl Deref for PageBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.buf {
+            Buf::Owned(raw) => &raw[self.range.clone()],
+            Buf::Shared(owner) => &(**owner).as_ref()[self.range.clone()],
+        }
+    }
+}
+
+impl fmt::Debug for PageBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PageBuf({} bytes{})", self.len(), if self.is_shared() { ", shared" } else { "" })
+    }
+}
+
+impl From<Vec<u8>> for PageBuf {
+    fn from(raw: Vec<u8>) -> Self {
+        let range = 0..raw.len();
+        Self { buf: Buf::Owned(raw.into()), range }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Pages currently held by the cache.
+    pub len: usize,
+    pub capacity: usize,
+}
+
+/// Turns a cache size as stored at header offset 48 (or given to
+/// `PRAGMA cache_size`) into a number of pages. Positive values count pages,
+/// negative ones KiB.
+pub fn cache_pages(cache_size: i32, page_size: u32) -> usize {
+    let cache_size = if cache_size == 0 { DEFAULT_CACHE_SIZE } else { cache_size };
+    if cache_size > 0 {
+        cache_size as usize
+    } else {
+        (cache_size.unsigned_abs() as usize * 1024 / page_size as usize).max(1)
+    }
+}
+
+/// Least recently used cache of page buffers.
+#[derive(Debug, Default)]
+struct LruCache {
+    capacity: usize,
+    /// Page number to its buffer and the tick of its last use.
+    pages: HashMap<u64, (PageBuf, u64)>,
+    /// Tick of last use to page number, the first entry is evicted next.
+    recency: BTreeMap<u64, u64>,
+    tick: u64,
+}
+
+impl LruCache {
+    fn get(&mut self, page: u64) -> Option<PageBuf> {
+        let (buf, used) = self.pages.get_mut(&page)?;
+        self.recency.remove(used);
+        self.tick += 1;
+        *used = self.tick;
+        self.recency.insert(self.tick, page);
+        Some(buf.clone())
+    }
+
+    fn insert(&mut self, page: u64, buf: PageBuf) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.pages.len() >= self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.pages.remove(&oldest);
+        }
+        self.tick += 1;
+        self.recency.insert(self.tick, page);
+        self.pages.insert(page, (buf, self.tick));
+    }
+
+    fn clear(&mut self) {
+        self.pages.clear();
+        self.recency.clear();
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.pages.len() > capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.pages.remove(&oldest);
+        }
+    }
+}
+
+/// Owns the database storage and hands out pages through a bounded LRU
+/// cache, so walking the same b-tree twice only reads it from disk once.
+/// In-memory storages such as a memory mapping bypass the cache. Pages with
+/// a committed frame in the write-ahead log, or with their original content
+/// in a hot rollback journal, are read from there instead.
+pub struct Pager {
+    storage: Box<dyn Storage>,
+    wal: Option<Wal>,
+    journal: Option<Journal>,
+    page_size: u32,
+    cache: LruCache,
+    hits: u64,
+    misses: u64,
+}
+
+impl Pager {
+    pub fn new(storage: Box<dyn Storage>, page_size: u32, cache_pages: usize) -> Self {
+        Self {
+            storage,
+            wal: None,
+            journal: None,
+            page_size,
+            cache: LruCache {
+                capacity: cache_pages,
+                ..Default::default()
+            },
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns page `page_ind`, counting from 1.
+    pub fn get(&mut self, page_ind: u64) -> Result<PageBuf> {
+        if page_ind == 0 {
+            return Err(DbError::corrupt_page(0, 0, "page numbers start at 1"));
+        }
+        let offset = (page_ind - 1) * self.page_size as u64;
+        let overlaid = self.wal.as_ref().is_some_and(|wal| wal.contains(page_ind))
+            || self.journal.as_ref().is_some_and(|journal| journal.contains(page_ind));
+        if self.storage.is_in_memory() && !overlaid {
+            return self.read(page_ind, offset);
+        }
+        if let Some(buf) = self.cache.get(page_ind) {
+            self.hits += 1;
+            return Ok(buf);
+        }
+        self.misses += 1;
+
+        let buf = self.read(page_ind, offset)?;
+        self.cache.insert(page_ind, buf.clone());
+        Ok(buf)
+    }
+
+    fn read(&mut self, page_ind: u64, offset: u64) -> Result<PageBuf> {
+        if let Some(journal) = &mut self.journal {
+            if page_ind > journal.database_size as u64 {
+                return Err(DbError::corrupt_page(page_ind, 0, "page is past the end of the rolled back database"));
+            }
+            if let Some(buf) = journal.read_page(page_ind)? {
+                return Ok(buf);
+            }
+        }
+        if let Some(buf) = self.wal.as_mut().map(|wal| wal.read_page(page_ind)).transpose()?.flatten() {
+            return Ok(buf);
+        }
+        match self.storage.read_at(offset, self.page_size as usize) {
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                Err(DbError::corrupt_page(page_ind, 0, "page is past the end of the file"))
+            }
+            r => Ok(r?),
+        }
+    }
+
+    /// Reads pages from `wal` from now on where it has a newer version.
+    pub fn set_wal(&mut self, wal: Wal) {
+        self.cache.clear();
+        self.wal = Some(wal);
+    }
+
+    pub fn wal(&self) -> Option<&Wal> {
+        self.wal.as_ref()
+    }
+
+    /// Reads the pages `journal` restores from it from now on, presenting
+    /// the database as it was before the interrupted transaction.
+    pub fn set_journal(&mut self, journal: Journal) {
+        self.cache.clear();
+        self.journal = Some(journal);
+    }
+
+    /// Changes how many pages are kept, evicting the least recently used ones.
+    pub fn set_cache_pages(&mut self, pages: usize) {
+        self.cache.set_capacity(pages);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            len: self.cache.pages.len(),
+            capacity: self.cache.capacity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = LruCache {
+            capacity: 2,
+            ..Default::default()
+        };
+        cache.insert(1, vec![1].into());
+        cache.insert(2, vec![2].into());
+        assert!(cache.get(1).is_some());
+        cache.insert(3, vec![3].into());
+        assert!(cache.get(2).is_none());
+        assert_eq!(cache.get(1).map(|b| b[0]), Some(1));
+        assert_eq!(cache.get(3).map(|b| b[0]), Some(3));
+
+        cache.set_capacity(1);
+        assert_eq!(cache.pages.len(), 1);
+        assert!(cache.get(3).is_some());
+    }
+
+    #[test]
+    fn cache_size_from_header() {
+        assert_eq!(cache_pages(0, 4096), 500);
+        assert_eq!(cache_pages(100, 4096), 100);
+        assert_eq!(cache_pages(-64, 4096), 16);
+        assert_eq!(cache_pages(-1, 65536), 1);
+    }
+}
@@ -0,0 +1,469 @@
+use crate::error::{DbError, Result};
+
+use crate::query::functions::{aggregate, is_aggregate, scalar};
+use crate::query::{Executor, ResultSet, SourceColumn};
+use crate::sql::ast::{BinaryOp, Expr, ExprKind, UnaryOp};
+use crate::value::{Affinity, Value};
+
+/// The row an expression is evaluated against, plus the rows of its group
+/// when aggregates are being computed and the values of the window function
+/// calls when the row is projected.
+pub struct Env<'a> {
+    pub columns: &'a [SourceColumn],
+    pub row: &'a [Value],
+    pub group: Option<&'a [Vec<Value>]>,
+    pub windows: &'a [(&'a Expr, Value)],
+}
+
+const ROWID_NAMES: [&str; 3] = ["rowid", "oid", "_rowid_"];
+
+/// Finds the index of a column, falling back to the hidden rowid column for
+/// `rowid`, `oid` and `_rowid_` unless a real column shadows the name.
+pub fn resolve_column(columns: &[SourceColumn], table: Option<&str>, name: &str) -> Result<usize> {
+    let table_matches = |c: &SourceColumn| table.map_or(true, |t| c.table.eq_ignore_ascii_case(t));
+
+    let mut found = columns
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| !c.hidden && c.name.eq_ignore_ascii_case(name) && table_matches(c));
+    if let Some((i, _)) = found.next() {
+        if found.next().is_some() {
+            return Err(DbError::Query(format!("ambiguous column name: {}", name)));
+        }
+        return Ok(i);
+    }
+
+    if ROWID_NAMES.iter().any(|r| r.eq_ignore_ascii_case(name)) {
+        if let Some(i) = columns.iter().position(|c| c.hidden && table_matches(c)) {
+            return Ok(i);
+        }
+    }
+
+    match table {
+        Some(t) => Err(DbError::NoSuchColumn(format!("{}.{}", t, name))),
+        None => Err(DbError::NoSuchColumn(name.to_string())),
+    }
+}
+
+pub fn contains_aggregate(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Function { name, args, .. } => {
+            is_aggregate(name, args.len()) || args.iter().any(contains_aggregate)
+        }
+        ExprKind::Unary { expr, .. } => contains_aggregate(expr),
+        ExprKind::Binary { left, right, .. } => contains_aggregate(left) || contains_aggregate(right),
+        ExprKind::InSelect { expr, .. } => contains_aggregate(expr),
+        ExprKind::InList { expr, list, .. } => contains_aggregate(expr) || list.iter().any(contains_aggregate),
+        ExprKind::Between { expr, low, high, .. } => [expr, low, high].into_iter().any(|e| contains_aggregate(e)),
+        ExprKind::Case {
+            operand,
+            branches,
+            otherwise,
+        } => {
+            operand.iter().chain(otherwise).any(|e| contains_aggregate(e))
+                || branches.iter().any(|(when, then)| contains_aggregate(when) || contains_aggregate(then))
+        }
+        ExprKind::Cast { expr, .. } => contains_aggregate(expr),
+        // the window function itself aggregates over the window, not the group
+        ExprKind::Window { args, over, .. } => {
+            args.iter().any(contains_aggregate)
+                || over.partition_by.iter().any(contains_aggregate)
+                || over.order_by.iter().any(|o| contains_aggregate(&o.expr))
+        }
+        // aggregates in a subquery belong to the subquery
+        ExprKind::Literal(_)
+        | ExprKind::Parameter(_)
+        | ExprKind::Column { .. }
+        | ExprKind::Subquery(_)
+        | ExprKind::Exists(_) => false,
+    }
+}
+
+fn single_column(result: &ResultSet) -> Result<()> {
+    match result.columns.len() {
+        1 => Ok(()),
+        n => Err(DbError::Query(format!("sub-select returns {} columns - expected 1", n))),
+    }
+}
+
+/// Whether `v` is among `values`, `None` when that is unknown because `v`
+/// or one of the values it didn't match is NULL.
+fn in_values<'v>(v: &Value, values: impl Iterator<Item = &'v Value>) -> Option<bool> {
+    let mut saw_null = false;
+    let mut empty = true;
+    for x in values {
+        empty = false;
+        if x.is_null() {
+            saw_null = true;
+        } else if !v.is_null() && v.compare(x).is_eq() {
+            return Some(true);
+        }
+    }
+    if empty {
+        Some(false)
+    } else if v.is_null() || saw_null {
+        None
+    } else {
+        Some(false)
+    }
+}
+
+/// The affinity an expression brings to a comparison: that of a column or
+/// the type of a CAST, none for anything else.
+pub fn expr_affinity(expr: &Expr, columns: &[SourceColumn]) -> Option<Affinity> {
+    match &expr.kind {
+        ExprKind::Column { table, name } => {
+            resolve_column(columns, table.as_deref(), name).ok().and_then(|i| columns[i].affinity)
+        }
+        ExprKind::Cast { type_name, .. } => Some(Affinity::from_type_name(type_name)),
+        _ => None,
+    }
+}
+
+/// Converts `value`, an operand of affinity `own`, before comparing it with
+/// an operand of affinity `other`: text compared with a numeric column is
+/// read as a number, a number compared with a text column as text.
+pub fn compare_affinity(value: Value, own: Option<Affinity>, other: Option<Affinity>) -> Value {
+    let numeric = |a| matches!(a, Some(Affinity::Integer | Affinity::Real | Affinity::Numeric));
+    match value {
+        Value::Text(_) if numeric(other) && !numeric(own) => Affinity::Numeric.apply(value),
+        Value::Integer(_) | Value::Real(_) if other == Some(Affinity::Text) && own.is_none() => Affinity::Text.apply(value),
+        v => v,
+    }
+}
+
+fn bool_value(b: bool) -> Value {
+    Value::Integer(b as i64)
+}
+
+/// The comparison operators, NULL when either side is NULL.
+fn comparison(op: BinaryOp, left: &Value, right: &Value) -> Value {
+    if left.is_null() || right.is_null() {
+        return Value::Null;
+    }
+    let ord = left.compare(right);
+    bool_value(match op {
+        BinaryOp::Eq => ord.is_eq(),
+        BinaryOp::Ne => ord.is_ne(),
+        BinaryOp::Lt => ord.is_lt(),
+        BinaryOp::Le => ord.is_le(),
+        BinaryOp::Gt => ord.is_gt(),
+        _ => ord.is_ge(),
+    })
+}
+
+fn arithmetic(op: BinaryOp, left: &Value, right: &Value) -> Value {
+    if left.is_null() || right.is_null() {
+        return Value::Null;
+    }
+    let (l, r) = (left.to_numeric(), right.to_numeric());
+    if let (Value::Integer(a), Value::Integer(b)) = (&l, &r) {
+        let (a, b) = (*a, *b);
+        let checked = match op {
+            BinaryOp::Add => a.checked_add(b),
+            BinaryOp::Sub => a.checked_sub(b),
+            BinaryOp::Mul => a.checked_mul(b),
+            BinaryOp::Div if b == 0 => return Value::Null,
+            BinaryOp::Div => a.checked_div(b),
+            BinaryOp::Mod if b == 0 => return Value::Null,
+            BinaryOp::Mod => Some(a.checked_rem(b).unwrap_or(0)),
+            _ => unreachable!("not an arithmetic operator"),
+        };
+        if let Some(v) = checked {
+            return Value::Integer(v);
+        }
+    }
+    let (a, b) = (l.to_f64(), r.to_f64());
+    match op {
+        BinaryOp::Add => Value::Real(a + b),
+        BinaryOp::Sub => Value::Real(a - b),
+        BinaryOp::Mul => Value::Real(a * b),
+        BinaryOp::Div if b == 0.0 => Value::Null,
+        BinaryOp::Div => Value::Real(a / b),
+        // the remainder of reals is that of their integer parts
+        BinaryOp::Mod => match (a as i64, b as i64) {
+            (_, 0) => Value::Null,
+            (a, b) => Value::Real(a.checked_rem(b).unwrap_or(0) as f64),
+        },
+        _ => unreachable!("not an arithmetic operator"),
+    }
+}
+
+/// `a << b` when `left`, else `a >> b`. A negative amount shifts the other
+/// way and shifting by 64 or more leaves nothing but the sign, as in sqlite.
+fn shift(left: bool, a: i64, b: i64) -> i64 {
+    let left = left == (b >= 0);
+    match b.unsigned_abs() {
+        n if n >= 64 => match left || a >= 0 {
+            true => 0,
+            false => -1,
+        },
+        n if left => a << n,
+        n => a >> n,
+    }
+}
+
+impl Executor<'_> {
+    pub fn eval(&mut self, expr: &Expr, env: &Env) -> Result<Value> {
+        let value = match &expr.kind {
+            ExprKind::Literal(v) => v.clone(),
+            ExprKind::Parameter(i) => self.parameters.get(i - 1).cloned().unwrap_or(Value::Null),
+            ExprKind::Column { table, name } => match resolve_column(env.columns, table.as_deref(), name) {
+                Ok(i) => env.row.get(i).cloned().unwrap_or(Value::Null),
+                Err(e @ DbError::NoSuchColumn(_)) => self.outer_column(table.as_deref(), name)?.ok_or(e)?,
+                Err(e) => return Err(e),
+            },
+            ExprKind::Window { name, .. } => match env.windows.iter().find(|(call, _)| *call == expr) {
+                Some((_, v)) => v.clone(),
+                None => return Err(DbError::Query(format!("misuse of window function {}()", name))),
+            },
+            ExprKind::Subquery(select) => {
+                let result = self.subquery(select, env)?;
+                single_column(&result)?;
+                result.rows.first().and_then(|r| r.first()).cloned().unwrap_or(Value::Null)
+            }
+            ExprKind::Exists(select) => bool_value(!self.subquery(select, env)?.rows.is_empty()),
+            ExprKind::InSelect { expr, select, negated } => {
+                let v = self.eval(expr, env)?;
+                let result = self.subquery(select, env)?;
+                single_column(&result)?;
+                let (la, ra) = (expr_affinity(expr, env.columns), result.affinities[0]);
+                let v = compare_affinity(v, la, ra);
+                let values: Vec<Value> = result.rows.iter().map(|r| compare_affinity(r[0].clone(), ra, la)).collect();
+                match in_values(&v, values.iter()) {
+                    Some(found) => bool_value(found != *negated),
+                    None => Value::Null,
+                }
+            }
+            ExprKind::InList { expr, list, negated } => {
+                let v = self.eval(expr, env)?;
+                let affinity = expr_affinity(expr, env.columns);
+                let mut values = vec![];
+                for e in list {
+                    values.push(compare_affinity(self.eval(e, env)?, None, affinity));
+                }
+                match in_values(&v, values.iter()) {
+                    Some(found) => bool_value(found != *negated),
+                    None => Value::Null,
+                }
+            }
+            ExprKind::Between { expr, low, high, negated } => {
+                let above = self.comparison(BinaryOp::Ge, expr, low, env)?.truthy();
+                let below = self.comparison(BinaryOp::Le, expr, high, env)?.truthy();
+                match (above, below) {
+                    (Some(false), _) | (_, Some(false)) => bool_value(*negated),
+                    (Some(true), Some(true)) => bool_value(!*negated),
+                    _ => Value::Null,
+                }
+            }
+            ExprKind::Case {
+                operand,
+                branches,
+                otherwise,
+            } => {
+                let mut then = otherwise.as_deref();
+                for (when, branch) in branches {
+                    let hit = match operand {
+                        Some(operand) => self.comparison(BinaryOp::Eq, operand, when, env)?.truthy(),
+                        None => self.eval(when, env)?.truthy(),
+                    };
+                    if hit == Some(true) {
+                        then = Some(branch);
+                        break;
+                    }
+                }
+                match then {
+                    Some(e) => self.eval(e, env)?,
+                    None => Value::Null,
+                }
+            }
+            ExprKind::Cast { expr, type_name } => Affinity::from_type_name(type_name).cast(self.eval(expr, env)?),
+            ExprKind::Unary { op, expr } => {
+                let v = self.eval(expr, env)?;
+                match (op, v) {
+                    (_, Value::Null) => Value::Null,
+                    (UnaryOp::Plus, v) => v,
+                    (UnaryOp::Not, v) => bool_value(!v.truthy().unwrap_or(false)),
+                    (UnaryOp::BitNot, v) => Value::Integer(!v.to_i64()),
+                    (UnaryOp::Neg, v) => match v.to_numeric() {
+                        Value::Integer(i) => i.checked_neg().map_or(Value::Real(-(i as f64)), Value::Integer),
+                        n => Value::Real(-n.to_f64()),
+                    },
+                }
+            }
+            ExprKind::Binary { op, left, right } => self.binary(*op, left, right, env)?,
+            ExprKind::Function {
+                name,
+                args,
+                distinct,
+                star,
+                filter,
+            } => {
+                if is_aggregate(name, args.len()) {
+                    let Some(group) = env.group else {
+                        return Err(DbError::Query(format!("misuse of aggregate function {}()", name)));
+                    };
+                    let mut values = vec![];
+                    for row in group {
+                        let row_env = Env {
+                            columns: env.columns,
+                            row,
+                            group: None,
+                            windows: &[],
+                        };
+                        if let Some(filter) = filter {
+                            if self.eval(filter, &row_env)?.truthy() != Some(true) {
+                                continue;
+                            }
+                        }
+                        let mut row_args = vec![];
+                        for arg in args {
+                            row_args.push(self.eval(arg, &row_env)?);
+                        }
+                        values.push(row_args);
+                    }
+                    if *distinct {
+                        let mut unique: Vec<Vec<Value>> = vec![];
+                        for v in values {
+                            if !unique.iter().any(|u| u.first() == v.first()) {
+                                unique.push(v);
+                            }
+                        }
+                        values = unique;
+                    }
+                    let rows: Vec<&[Value]> = values.iter().map(Vec::as_slice).collect();
+                    aggregate(name, &rows, *star)?
+                } else if filter.is_some() {
+                    return Err(DbError::Query(format!("FILTER may not be used with non-aggregate {}()", name)));
+                } else {
+                    let mut values = vec![];
+                    for arg in args {
+                        values.push(self.eval(arg, env)?);
+                    }
+                    scalar(name, &values)?
+                }
+            }
+        };
+        Ok(value)
+    }
+
+    /// Evaluates `left op right` for a comparison operator, converting the
+    /// operands by their affinities first.
+    fn comparison(&mut self, op: BinaryOp, left: &Expr, right: &Expr, env: &Env) -> Result<Value> {
+        let (l, r) = self.compared(left, right, env)?;
+        Ok(comparison(op, &l, &r))
+    }
+
+    fn compared(&mut self, left: &Expr, right: &Expr, env: &Env) -> Result<(Value, Value)> {
+        let (la, ra) = (expr_affinity(left, env.columns), expr_affinity(right, env.columns));
+        let l = compare_affinity(self.eval(left, env)?, la, ra);
+        let r = compare_affinity(self.eval(right, env)?, ra, la);
+        Ok((l, r))
+    }
+
+    fn binary(&mut self, op: BinaryOp, left: &Expr, right: &Expr, env: &Env) -> Result<Value> {
+        match op {
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                return self.comparison(op, left, right, env)
+            }
+            BinaryOp::Is | BinaryOp::IsNot => {
+                let (l, r) = self.compared(left, right, env)?;
+                let same = match (l.is_null(), r.is_null()) {
+                    (true, true) => true,
+                    (false, false) => l.compare(&r).is_eq(),
+                    _ => false,
+                };
+                return Ok(bool_value(same == (op == BinaryOp::Is)));
+            }
+            _ => (),
+        }
+        let l = self.eval(left, env)?;
+        match op {
+            BinaryOp::And => {
+                if l.truthy() == Some(false) {
+                    return Ok(bool_value(false));
+                }
+                let r = self.eval(right, env)?.truthy();
+                return Ok(match (l.truthy(), r) {
+                    (_, Some(false)) => bool_value(false),
+                    (Some(true), Some(true)) => bool_value(true),
+                    _ => Value::Null,
+                });
+            }
+            BinaryOp::Or => {
+                if l.truthy() == Some(true) {
+                    return Ok(bool_value(true));
+                }
+                let r = self.eval(right, env)?.truthy();
+                return Ok(match (l.truthy(), r) {
+                    (_, Some(true)) => bool_value(true),
+                    (Some(false), Some(false)) => bool_value(false),
+                    _ => Value::Null,
+                });
+            }
+            _ => (),
+        }
+
+        let r = self.eval(right, env)?;
+        let value = match op {
+            BinaryOp::Concat => {
+                if l.is_null() || r.is_null() {
+                    return Ok(Value::Null);
+                }
+                Value::Text(format!("{}{}", l, r))
+            }
+            BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+                if l.is_null() || r.is_null() {
+                    return Ok(Value::Null);
+                }
+                let (a, b) = (l.to_i64(), r.to_i64());
+                Value::Integer(match op {
+                    BinaryOp::BitAnd => a & b,
+                    BinaryOp::BitOr => a | b,
+                    _ => shift(op == BinaryOp::ShiftLeft, a, b),
+                })
+            }
+            _ => arithmetic(op, &l, &r),
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{query, strings};
+
+    #[test]
+    fn filters_the_rows_of_aggregates() {
+        assert_eq!(
+            strings("SELECT count(*) FILTER (WHERE color LIKE '%red%') || ' ' || group_concat(id) FILTER (WHERE id > 2) FROM apples"),
+            ["2 3,4"]
+        );
+        assert_eq!(strings("SELECT sum(id) FILTER (WHERE 0) FROM apples"), [""]);
+        assert_eq!(strings("SELECT count(*) filter FROM apples"), ["4"]);
+        let error = |sql| query(sql).unwrap_err().to_string();
+        assert_eq!(
+            error("SELECT lower(name) FILTER (WHERE 1) FROM apples"),
+            "FILTER may not be used with non-aggregate lower()"
+        );
+        assert_eq!(
+            error("SELECT count(*) FILTER (WHERE sum(id) > 1) FROM apples"),
+            "misuse of aggregate function sum()"
+        );
+    }
+
+    #[test]
+    fn takes_the_remainder_of_integer_parts() {
+        let sql = "SELECT 5.5 % 2 UNION ALL SELECT -5.5 % 2 UNION ALL SELECT 7 % -2.5 UNION ALL SELECT 5.5 % 0.5 \
+                   UNION ALL SELECT 9223372036854775807.0 % 2 UNION ALL SELECT 5 % 'a' UNION ALL SELECT -7 % 3";
+        assert_eq!(strings(sql), ["1.0", "-1.0", "1.0", "", "1.0", "", "-1"]);
+    }
+
+    #[test]
+    fn shifts_like_sqlite() {
+        let sql = "SELECT 1 << -1 UNION ALL SELECT 8 >> -1 UNION ALL SELECT 3 << -2 UNION ALL SELECT 1 << 64 \
+                   UNION ALL SELECT -1 >> 64 UNION ALL SELECT 1 >> 64 UNION ALL SELECT -8 >> -70 \
+                   UNION ALL SELECT 1 << 63 UNION ALL SELECT 1 << 63 >> 63 UNION ALL SELECT 1 << -9223372036854775808";
+        assert_eq!(strings(sql), ["0", "16", "0", "0", "-1", "0", "0", "-9223372036854775808", "-1", "0"]);
+    }
+}
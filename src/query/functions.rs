@@ -0,0 +1,423 @@
+use crate::error::{DbError, Result};
+
+use crate::query::window::is_window_function;
+use crate::value::{format_real, Value};
+
+const AGGREGATES: [&str; 7] = ["avg", "count", "group_concat", "max", "min", "sum", "total"];
+
+/// `min` and `max` are aggregates with one argument and scalar functions with more.
+pub fn is_aggregate(name: &str, arg_count: usize) -> bool {
+    match name {
+        "min" | "max" => arg_count == 1,
+        _ => AGGREGATES.contains(&name),
+    }
+}
+
+/// Folds the argument values of an aggregate over a group, `args[i]` holding the
+/// arguments evaluated against the i-th row. `count(*)` passes an empty list per row.
+pub fn aggregate(name: &str, args: &[&[Value]], star: bool) -> Result<Value> {
+    let first = |row: &&[Value]| row.first().cloned().unwrap_or(Value::Null);
+    let values: Vec<Value> = args.iter().map(first).filter(|v| !v.is_null()).collect();
+
+    let ret = match name {
+        "count" if star => Value::Integer(args.len() as i64),
+        "count" => Value::Integer(values.len() as i64),
+        "sum" | "total" | "avg" => {
+            if values.iter().all(|v| matches!(v.to_numeric(), Value::Integer(_))) && name == "sum" {
+                let mut sum: i64 = 0;
+                for v in &values {
+                    sum = match sum.checked_add(v.to_i64()) {
+                        Some(s) => s,
+                        None => return Err(DbError::Query("integer overflow".to_string())),
+                    };
+                }
+                if values.is_empty() {
+                    Value::Null
+                } else {
+                    Value::Integer(sum)
+                }
+            } else {
+                let sum: f64 = values.iter().map(Value::to_f64).sum();
+                match name {
+                    "total" => Value::Real(sum),
+                    _ if values.is_empty() => Value::Null,
+                    "avg" => Value::Real(sum / values.len() as f64),
+                    _ => Value::Real(sum),
+                }
+            }
+        }
+        "min" => values.into_iter().min_by(|a, b| a.compare(b)).unwrap_or(Value::Null),
+        "max" => values.into_iter().max_by(|a, b| a.compare(b)).unwrap_or(Value::Null),
+        "group_concat" => {
+            if values.is_empty() {
+                return Ok(Value::Null);
+            }
+            let mut s = String::new();
+            for (i, row) in args.iter().filter(|row| !first(row).is_null()).enumerate() {
+                if i > 0 {
+                    match row.get(1) {
+                        Some(sep) => s.push_str(&sep.to_string()),
+                        None => s.push(','),
+                    }
+                }
+                s.push_str(&first(row).to_string());
+            }
+            Value::Text(s)
+        }
+        _ => return Err(DbError::Query(format!("no such function: {}", name))),
+    };
+    Ok(ret)
+}
+
+fn text_arg(args: &[Value], i: usize) -> Option<String> {
+    match args.get(i) {
+        None | Some(Value::Null) => None,
+        Some(v) => Some(v.to_string()),
+    }
+}
+
+/// The special characters of a LIKE or GLOB pattern. LIKE has an optional
+/// escape character where GLOB has `[...]` sets.
+struct Wildcards {
+    all: Option<char>,
+    one: Option<char>,
+    set: bool,
+    no_case: bool,
+}
+
+#[derive(PartialEq)]
+enum PatternMatch {
+    Match,
+    NoMatch,
+    /// Not even with more characters matched by a wildcard, ending the search early.
+    NoWildcardMatch,
+}
+
+/// sqlite's `patternCompare`, `other` being the escape character of LIKE or
+/// the `[` of GLOB. Only ASCII letters are case-insensitive.
+fn pattern_compare(pattern: &[char], string: &[char], w: &Wildcards, other: Option<char>) -> PatternMatch {
+    let (mut p, mut s) = (0, 0);
+    let mut escaped_at = None;
+    while p < pattern.len() {
+        let mut c = pattern[p];
+        p += 1;
+        if Some(c) == w.all {
+            let mut next = None;
+            while let Some(&x) = pattern.get(p) {
+                p += 1;
+                if Some(x) == w.one {
+                    if s >= string.len() {
+                        return PatternMatch::NoWildcardMatch;
+                    }
+                    s += 1;
+                } else if Some(x) != w.all {
+                    next = Some(x);
+                    break;
+                }
+            }
+            let Some(mut c) = next else {
+                return PatternMatch::Match;
+            };
+            if Some(c) == other {
+                if w.set {
+                    // a set right after `*` is tried at every position
+                    while s < string.len() {
+                        let m = pattern_compare(&pattern[p - 1..], &string[s..], w, other);
+                        if m != PatternMatch::NoMatch {
+                            return m;
+                        }
+                        s += 1;
+                    }
+                    return PatternMatch::NoWildcardMatch;
+                }
+                let Some(&escaped) = pattern.get(p) else {
+                    return PatternMatch::NoWildcardMatch;
+                };
+                c = escaped;
+                p += 1;
+            }
+            while s < string.len() {
+                let c2 = string[s];
+                s += 1;
+                if c == c2 || w.no_case && c.eq_ignore_ascii_case(&c2) {
+                    let m = pattern_compare(&pattern[p..], &string[s..], w, other);
+                    if m != PatternMatch::NoMatch {
+                        return m;
+                    }
+                }
+            }
+            return PatternMatch::NoWildcardMatch;
+        }
+        if Some(c) == other {
+            if !w.set {
+                let Some(&escaped) = pattern.get(p) else {
+                    return PatternMatch::NoMatch;
+                };
+                c = escaped;
+                p += 1;
+                escaped_at = Some(p);
+            } else {
+                let Some(&c) = string.get(s) else {
+                    return PatternMatch::NoMatch;
+                };
+                s += 1;
+                let (mut seen, mut invert, mut prior) = (false, false, None);
+                let mut c2 = pattern.get(p).copied();
+                p += 1;
+                if c2 == Some('^') {
+                    invert = true;
+                    c2 = pattern.get(p).copied();
+                    p += 1;
+                }
+                if c2 == Some(']') {
+                    seen = c == ']';
+                    c2 = pattern.get(p).copied();
+                    p += 1;
+                }
+                while let Some(x) = c2.filter(|&x| x != ']') {
+                    match prior {
+                        Some(low) if x == '-' && !matches!(pattern.get(p), Some(']') | None) => {
+                            seen |= (low..=pattern[p]).contains(&c);
+                            p += 1;
+                            prior = None;
+                        }
+                        _ => {
+                            seen |= c == x;
+                            prior = Some(x);
+                        }
+                    }
+                    c2 = pattern.get(p).copied();
+                    p += 1;
+                }
+                if c2.is_none() || seen == invert {
+                    return PatternMatch::NoMatch;
+                }
+                continue;
+            }
+        }
+        let c2 = string.get(s).copied();
+        s += 1;
+        match c2 {
+            Some(c2) if c == c2 || w.no_case && c.eq_ignore_ascii_case(&c2) => continue,
+            Some(_) if Some(c) == w.one && escaped_at != Some(p) => continue,
+            _ => return PatternMatch::NoMatch,
+        }
+    }
+    if s >= string.len() {
+        PatternMatch::Match
+    } else {
+        PatternMatch::NoMatch
+    }
+}
+
+pub fn scalar(name: &str, args: &[Value]) -> Result<Value> {
+    let arg = |i: usize| args.get(i).cloned().unwrap_or(Value::Null);
+    let null_in = args.iter().any(Value::is_null);
+
+    let ret = match (name, args.len()) {
+        ("coalesce", n) | ("ifnull", n) if n >= 2 => {
+            args.iter().find(|v| !v.is_null()).cloned().unwrap_or(Value::Null)
+        }
+        ("nullif", 2) => {
+            if arg(0).compare(&arg(1)).is_eq() {
+                Value::Null
+            } else {
+                arg(0)
+            }
+        }
+        ("typeof", 1) => Value::Text(arg(0).type_name().to_string()),
+        ("min", n) | ("max", n) if n >= 2 => {
+            if null_in {
+                return Ok(Value::Null);
+            }
+            let pick = args.iter().cloned();
+            if name == "min" {
+                pick.min_by(|a, b| a.compare(b)).unwrap_or(Value::Null)
+            } else {
+                pick.max_by(|a, b| a.compare(b)).unwrap_or(Value::Null)
+            }
+        }
+        ("like", 2) | ("like", 3) | ("glob", 2) => {
+            let escape = match args.get(2) {
+                None => None,
+                Some(Value::Null) => return Ok(Value::Null),
+                Some(e) => {
+                    let e = e.to_string();
+                    let mut chars = e.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => Some(c),
+                        _ => return Err(DbError::Query("ESCAPE expression must be a single character".to_string())),
+                    }
+                }
+            };
+            if null_in {
+                return Ok(Value::Null);
+            }
+            let wildcards = match name {
+                "like" => Wildcards {
+                    all: Some('%').filter(|&c| Some(c) != escape),
+                    one: Some('_').filter(|&c| Some(c) != escape),
+                    set: false,
+                    no_case: true,
+                },
+                _ => Wildcards {
+                    all: Some('*'),
+                    one: Some('?'),
+                    set: true,
+                    no_case: false,
+                },
+            };
+            let other = if wildcards.set { Some('[') } else { escape };
+            let pattern: Vec<char> = arg(0).to_string().chars().collect();
+            let string: Vec<char> = arg(1).to_string().chars().collect();
+            Value::Integer((pattern_compare(&pattern, &string, &wildcards, other) == PatternMatch::Match) as i64)
+        }
+        _ if null_in && name != "trim" && name != "ltrim" && name != "rtrim" => Value::Null,
+        ("length", 1) => match arg(0) {
+            Value::Blob(b) => Value::Integer(b.len() as i64),
+            v => Value::Integer(v.to_string().chars().count() as i64),
+        },
+        ("lower", 1) => Value::Text(arg(0).to_string().to_lowercase()),
+        ("upper", 1) => Value::Text(arg(0).to_string().to_uppercase()),
+        ("abs", 1) => match arg(0).to_numeric() {
+            Value::Integer(i) => match i.checked_abs() {
+                Some(a) => Value::Integer(a),
+                None => return Err(DbError::Query("integer overflow".to_string())),
+            },
+            v => Value::Real(v.to_f64().abs()),
+        },
+        ("round", 1) | ("round", 2) => {
+            let digits = if args.len() == 2 { arg(1).to_i64().clamp(0, 30) } else { 0 };
+            let factor = 10f64.powi(digits as i32);
+            Value::Real((arg(0).to_f64() * factor).round() / factor)
+        }
+        ("substr", 2) | ("substr", 3) | ("substring", 2) | ("substring", 3) => {
+            let s: Vec<char> = arg(0).to_string().chars().collect();
+            let len = s.len() as i64;
+            let mut start = arg(1).to_i64();
+            let mut count = if args.len() == 3 { arg(2).to_i64() } else { len + 1 };
+            if start < 0 {
+                start += len + 1;
+            } else if start == 0 {
+                count -= 1;
+                start = 1;
+            }
+            let (from, to) = if count < 0 {
+                ((start - 1 + count).max(1), start - 1)
+            } else {
+                (start, start + count - 1)
+            };
+            let from = from.clamp(1, len + 1) as usize - 1;
+            let to = to.clamp(0, len) as usize;
+            Value::Text(s.get(from..to.max(from)).unwrap_or_default().iter().collect())
+        }
+        ("instr", 2) => {
+            let hay = arg(0).to_string();
+            let needle = arg(1).to_string();
+            Value::Integer(hay.find(&needle).map_or(0, |i| hay[..i].chars().count() as i64 + 1))
+        }
+        ("replace", 3) => {
+            let pattern = arg(1).to_string();
+            if pattern.is_empty() {
+                arg(0)
+            } else {
+                Value::Text(arg(0).to_string().replace(&pattern, &arg(2).to_string()))
+            }
+        }
+        ("trim", 1..=2) | ("ltrim", 1..=2) | ("rtrim", 1..=2) => {
+            let (Some(s), chars) = (text_arg(args, 0), text_arg(args, 1)) else {
+                return Ok(Value::Null);
+            };
+            if args.len() == 2 && chars.is_none() {
+                return Ok(Value::Null);
+            }
+            let chars: Vec<char> = chars.unwrap_or_else(|| " ".to_string()).chars().collect();
+            let trimmed = match name {
+                "ltrim" => s.trim_start_matches(chars.as_slice()),
+                "rtrim" => s.trim_end_matches(chars.as_slice()),
+                _ => s.trim_matches(chars.as_slice()),
+            };
+            Value::Text(trimmed.to_string())
+        }
+        ("hex", 1) => {
+            let bytes = match arg(0) {
+                Value::Blob(b) => b,
+                Value::Real(r) => format_real(r).into_bytes(),
+                v => v.to_string().into_bytes(),
+            };
+            Value::Text(bytes.iter().map(|b| format!("{:02X}", b)).collect())
+        }
+        _ if is_aggregate(name, args.len()) => {
+            return Err(DbError::Query(format!("misuse of aggregate function {}()", name)))
+        }
+        _ if is_window_function(name) => return Err(DbError::Query(format!("misuse of window function {}()", name))),
+        _ => return Err(DbError::Query(format!("no such function: {}", name))),
+    };
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether `string` matches `pattern`, as `like(pattern, string[, escape])` or `glob(pattern, string)`.
+    fn matches(function: &str, pattern: &str, string: &str, escape: Option<&str>) -> bool {
+        let mut args = vec![Value::Text(pattern.to_string()), Value::Text(string.to_string())];
+        args.extend(escape.map(|e| Value::Text(e.to_string())));
+        scalar(function, &args).unwrap() == Value::Integer(1)
+    }
+
+    #[test]
+    fn escapes_like_wildcards() {
+        let like = |pattern, string| matches("like", pattern, string, Some("\\"));
+        assert!(like("a\\%", "a%"));
+        assert!(!like("a\\%", "ab"));
+        assert!(!like("a\\_c", "abc"));
+        assert!(like("a\\_c", "a_c"));
+        assert!(like("%\\%", "50%"));
+        assert!(!like("%\\%", "50"));
+        // an escape character with nothing after it matches nothing
+        assert!(!like("a\\", "a"));
+        assert!(like("a\\\\b", "a\\b"));
+        assert!(like("%\\_%", "x_y"));
+        assert_eq!(
+            scalar("like", &[Value::Text("a".into()), Value::Text("a".into()), Value::Text("ab".into())])
+                .unwrap_err()
+                .to_string(),
+            "ESCAPE expression must be a single character"
+        );
+
+        let like = |pattern, string| matches("like", pattern, string, None);
+        assert!(like("A%", "apple"));
+        // only ASCII letters are case-insensitive
+        assert!(!like("Ä%", "äx"));
+        assert!(like("%x", "xx"));
+        assert!(!like("%_", ""));
+        assert!(like("a__", "abc"));
+        assert!(!like("a__", "ab"));
+    }
+
+    #[test]
+    fn matches_glob_character_classes() {
+        let glob = |pattern, string| matches("glob", pattern, string, None);
+        assert!(glob("[abc]*", "banana"));
+        assert!(!glob("[^abc]*", "banana"));
+        assert!(glob("[a-c]x", "bx"));
+        assert!(!glob("[a-c]x", "dx"));
+        // a `]` right after the opening bracket is part of the set
+        assert!(!glob("[]]", "x"));
+        assert!(glob("[]]", "]"));
+        assert!(!glob("[^]]", "]"));
+        // so is a `-` at either end
+        assert!(glob("[a-]", "-"));
+        assert!(glob("[-a]", "-"));
+        assert!(glob("*[0-9]", "abc7"));
+        assert!(!glob("*[0-9]", "abc"));
+        // an unclosed set matches nothing
+        assert!(!glob("[", "["));
+        assert!(glob("a?c", "abc"));
+        assert!(!glob("a*", "A"));
+        assert!(!glob("[z-a]", "m"));
+        assert!(glob("*[xy]?", "zzyq"));
+    }
+}
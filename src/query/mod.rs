@@ -0,0 +1,1237 @@
+pub mod eval;
+pub mod functions;
+pub mod window;
+
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use crate::error::{DbError, Result};
+
+use crate::btree::{btree_cells, index_seek};
+use crate::cell::{Cell, Content, ContentVariant};
+use crate::db::DB;
+use crate::integrity::{integrity_check, quick_check};
+use crate::schema::{find_table, read_schema, SchemaEntry, Table};
+use crate::sql::ast::*;
+use crate::sql::Parser;
+use crate::value::{Affinity, Value};
+use eval::{compare_affinity, contains_aggregate, expr_affinity, resolve_column, Env};
+use window::{contains_window, window_calls};
+
+/// A column of the rows flowing through a query, qualified by the table
+/// name or alias it came from.
+#[derive(Debug, Clone)]
+pub struct SourceColumn {
+    pub table: String,
+    pub name: String,
+    /// Not expanded by `*`, used for the rowid.
+    pub hidden: bool,
+    /// Affinity of a table column, `None` for a column computed by a query.
+    pub affinity: Option<Affinity>,
+}
+
+#[derive(Debug, Default)]
+pub struct Relation {
+    pub columns: Vec<SourceColumn>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+#[derive(Debug, Default)]
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    /// Affinity of each column, which a view or derived table passes on.
+    pub affinities: Vec<Option<Affinity>>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+pub struct Executor<'a> {
+    db: &'a mut DB,
+    schema: Vec<SchemaEntry>,
+    /// Views being expanded, innermost last, to catch views defined in terms of themselves.
+    views: Vec<String>,
+    /// Columns and row of each query enclosing the running subquery,
+    /// innermost last, for correlated column references.
+    outer: Vec<(Vec<SourceColumn>, Vec<Value>)>,
+    /// Lowest position in `outer` a column reference resolved to while the
+    /// current subquery ran.
+    outer_hit: Option<usize>,
+    /// Results of uncorrelated subqueries, which only run once per statement,
+    /// with the ids of the common table expressions they could see.
+    subqueries: Vec<(Select, Vec<usize>, Rc<ResultSet>)>,
+    /// Common table expressions of the WITH clauses in scope, innermost last.
+    ctes: Vec<CteBinding>,
+    next_cte_id: usize,
+    /// Values bound to the statement's parameters, unbound ones are NULL.
+    parameters: Vec<Value>,
+}
+
+struct CteBinding {
+    cte: Rc<Cte>,
+    id: usize,
+    /// Position in `ctes` past the last table of the same WITH clause. They
+    /// can read from each other but not from those of nested WITH clauses.
+    scope_end: usize,
+    /// Length of `outer` at the WITH clause.
+    outer_depth: usize,
+    state: CteState,
+}
+
+enum CteState {
+    Idle,
+    /// Its select is running, reading from the table now is a circular reference.
+    Running,
+    /// The recursive select is running, the table is the single row it
+    /// continues from. `depth` is the length of `outer` to tell apart reads
+    /// from subqueries.
+    Recursing {
+        columns: Vec<String>,
+        row: Vec<Value>,
+        depth: usize,
+    },
+}
+
+/// Makes repeated column names distinct like sqlite does, `a`, `a:1`, `a:2`.
+fn unique_names(names: Vec<String>) -> Vec<String> {
+    let mut unique: Vec<String> = vec![];
+    for name in names {
+        let mut candidate = name.clone();
+        let mut n = 0;
+        while unique.iter().any(|u| u.eq_ignore_ascii_case(&candidate)) {
+            n += 1;
+            candidate = format!("{}:{}", name, n);
+        }
+        unique.push(candidate);
+    }
+    unique
+}
+
+/// Splits `expr` at its top-level ANDs.
+fn conjuncts<'e>(expr: &'e Expr, out: &mut Vec<&'e Expr>) {
+    match &expr.kind {
+        ExprKind::Binary { op: BinaryOp::And, left, right } => {
+            conjuncts(left, out);
+            conjuncts(right, out);
+        }
+        _ => out.push(expr),
+    }
+}
+
+/// Whether `expr` has the same value for every row. Functions are left out
+/// since some, like `random()`, don't.
+fn is_constant(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Literal(_) | ExprKind::Parameter(_) => true,
+        ExprKind::Unary { expr, .. } => is_constant(expr),
+        ExprKind::Binary { left, right, .. } => is_constant(left) && is_constant(right),
+        ExprKind::InList { expr, list, .. } => is_constant(expr) && list.iter().all(is_constant),
+        ExprKind::Between { expr, low, high, .. } => [expr, low, high].into_iter().all(|e| is_constant(e)),
+        ExprKind::Case {
+            operand,
+            branches,
+            otherwise,
+        } => {
+            operand.iter().chain(otherwise).all(|e| is_constant(e))
+                && branches.iter().all(|(when, then)| is_constant(when) && is_constant(then))
+        }
+        ExprKind::Cast { expr, .. } => is_constant(expr),
+        ExprKind::Column { .. }
+        | ExprKind::Function { .. }
+        | ExprKind::Window { .. }
+        | ExprKind::Subquery(_)
+        | ExprKind::Exists(_)
+        | ExprKind::InSelect { .. } => false,
+    }
+}
+
+/// Orders the primary key of a WITHOUT ROWID record against the leading key values `key`.
+fn compare_key(table: &Table, key: &[Value], cell: &Cell) -> Ordering {
+    let Content::RowCell(record) = &cell.content else {
+        return Ordering::Equal;
+    };
+    for ((pk, value), field) in table.primary_key.iter().zip(key).zip(&record.row) {
+        let ord = pk.collation.unwrap_or_default().compare(&Value::from(field), value);
+        let ord = if pk.descending { ord.reverse() } else { ord };
+        if ord.is_ne() {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+fn compare_keys(order_by: &[OrderingTerm], a: &[Value], b: &[Value]) -> Ordering {
+    for (term, (ka, kb)) in order_by.iter().zip(a.iter().zip(b)) {
+        let ord = ka.compare(kb);
+        let ord = if term.descending { ord.reverse() } else { ord };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+fn compare_rows(a: &[Value], b: &[Value]) -> Ordering {
+    a.iter().zip(b).map(|(x, y)| x.compare(y)).find(|o| o.is_ne()).unwrap_or(Ordering::Equal)
+}
+
+fn compare_by(order: &[(usize, bool)], a: &[Value], b: &[Value]) -> Ordering {
+    for &(i, descending) in order {
+        let ord = a[i].compare(&b[i]);
+        let ord = if descending { ord.reverse() } else { ord };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Sorts the rows and drops duplicates. Of equal rows the last one stays,
+/// so `SELECT 1.0 UNION SELECT 1` is 1 as in sqlite.
+fn distinct(mut rows: Vec<Vec<Value>>) -> Vec<Vec<Value>> {
+    rows.sort_by(|a, b| compare_rows(a, b));
+    let mut unique: Vec<Vec<Value>> = vec![];
+    for row in rows {
+        match unique.last_mut() {
+            Some(last) if compare_rows(last, &row).is_eq() => *last = row,
+            _ => unique.push(row),
+        }
+    }
+    unique
+}
+
+/// Result columns the ORDER BY terms of a compound select pick, by number
+/// or name, and whether they sort descending.
+fn compound_order(order_by: &[OrderingTerm], columns: &[String]) -> Result<Vec<(usize, bool)>> {
+    let mut order = vec![];
+    for (n, term) in order_by.iter().enumerate() {
+        let i = match &term.expr.kind {
+            ExprKind::Literal(Value::Integer(i)) => match (*i as usize).checked_sub(1).filter(|&i| i < columns.len()) {
+                Some(i) => Some(i),
+                None => {
+                    return Err(DbError::Query(format!(
+                        "{} ORDER BY term out of range - should be between 1 and {}",
+                        ordinal(n + 1),
+                        columns.len()
+                    )))
+                }
+            },
+            ExprKind::Column { name, .. } => columns.iter().position(|c| c.eq_ignore_ascii_case(name)),
+            _ => None,
+        };
+        let Some(i) = i else {
+            return Err(DbError::Query(format!(
+                "{} ORDER BY term does not match any column in the result set",
+                ordinal(n + 1)
+            )));
+        };
+        order.push((i, term.descending));
+    }
+    Ok(order)
+}
+
+/// The GROUP BY terms, with an integer literal standing for the result column it numbers.
+fn group_terms(group_by: &[Expr], outputs: &[(Expr, String)]) -> Result<Vec<Expr>> {
+    let mut terms = vec![];
+    for (n, term) in group_by.iter().enumerate() {
+        let expr = match &term.kind {
+            ExprKind::Literal(Value::Integer(i)) => match (*i as usize).checked_sub(1).and_then(|i| outputs.get(i)) {
+                Some((expr, _)) => expr,
+                None => {
+                    return Err(DbError::Query(format!(
+                        "{} GROUP BY term out of range - should be between 1 and {}",
+                        ordinal(n + 1),
+                        outputs.len()
+                    )))
+                }
+            },
+            _ => term,
+        };
+        if contains_aggregate(expr) {
+            return Err(DbError::Query("aggregate functions are not allowed in the GROUP BY clause".to_string()));
+        }
+        terms.push(expr.clone());
+    }
+    Ok(terms)
+}
+
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+/// Whether column `i` of a compound select term can hold numbers and text,
+/// given the affinity it has or else the literal it is.
+fn term_kinds(term: &Select, affinity: Option<Affinity>, i: usize) -> (bool, bool) {
+    match affinity {
+        Some(Affinity::Text) => return (false, true),
+        Some(Affinity::Blob) => return (true, true),
+        Some(_) => return (true, false),
+        None => (),
+    }
+    let expanded = term.columns.iter().any(|c| !matches!(c, ResultColumn::Expr { .. }));
+    match term.columns.get(i) {
+        Some(ResultColumn::Expr { expr, .. }) if !expanded => match &expr.kind {
+            ExprKind::Literal(Value::Integer(_) | Value::Real(_)) => (true, false),
+            ExprKind::Literal(Value::Text(_)) => (false, true),
+            ExprKind::Literal(_) => (false, false),
+            _ => (true, true),
+        },
+        _ => (true, true),
+    }
+}
+
+/// A compound select column has the affinity of its leftmost term, dropped
+/// to BLOB when a later term can hold values of the other kind.
+fn compound_affinity(affinity: Option<Affinity>, (numbers, text): (bool, bool)) -> Option<Affinity> {
+    match affinity {
+        Some(Affinity::Text) if numbers => Some(Affinity::Blob),
+        Some(Affinity::Integer | Affinity::Real | Affinity::Numeric) if text => Some(Affinity::Blob),
+        a => a,
+    }
+}
+
+fn arity_error(op: CompoundOp) -> DbError {
+    DbError::Query(format!(
+        "SELECTs to the left and right of {} do not have the same number of result columns",
+        op
+    ))
+}
+
+/// Names of the columns of a common table expression, `result` being those of its select.
+fn cte_names(cte: &Cte, result: Vec<String>) -> Result<Vec<String>> {
+    if cte.columns.is_empty() {
+        Ok(unique_names(result))
+    } else if cte.columns.len() == result.len() {
+        Ok(cte.columns.clone())
+    } else {
+        Err(DbError::Query(format!(
+            "table {} has {} values for {} columns",
+            cte.name,
+            result.len(),
+            cte.columns.len()
+        )))
+    }
+}
+
+fn order_by_range_error(columns: usize) -> DbError {
+    DbError::Query(format!("ORDER BY term out of range - should be between 1 and {}", columns))
+}
+
+/// Rows with equal GROUP BY keys.
+type Group = Vec<Vec<Value>>;
+
+/// Projected output row together with its ORDER BY keys.
+struct OutputRow {
+    values: Vec<Value>,
+    keys: Vec<Value>,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new(db: &'a mut DB) -> Result<Self> {
+        let schema = read_schema(db)?;
+        Ok(Self {
+            db,
+            schema,
+            views: vec![],
+            outer: vec![],
+            outer_hit: None,
+            subqueries: vec![],
+            ctes: vec![],
+            next_cte_id: 0,
+            parameters: vec![],
+        })
+    }
+
+    /// Binds `parameters[i]` to the parameter with index `i + 1`.
+    pub fn with_parameters(mut self, parameters: Vec<Value>) -> Self {
+        self.parameters = parameters;
+        self
+    }
+
+    pub fn execute(&mut self, statement: &Statement) -> Result<ResultSet> {
+        match statement {
+            Statement::Select(select) => self.select(select),
+            Statement::Pragma(pragma) => self.pragma(pragma),
+        }
+    }
+
+    /// Runs the read-only pragmas, any other pragma is a no-op with no result like in sqlite.
+    fn pragma(&mut self, pragma: &Pragma) -> Result<ResultSet> {
+        let name = pragma.name.to_ascii_lowercase();
+        let check = match name.as_str() {
+            "integrity_check" => integrity_check,
+            "quick_check" => quick_check,
+            _ => return Ok(ResultSet::default()),
+        };
+        let max_errors = match &pragma.value {
+            None => 100,
+            Some(Value::Integer(n)) if *n > 0 => *n as usize,
+            Some(value) => return Err(DbError::Query(format!("invalid {} limit: {}", name, value))),
+        };
+        let problems = check(self.db, max_errors)?;
+        let rows = if problems.is_empty() {
+            vec![vec![Value::Text("ok".to_string())]]
+        } else {
+            problems.into_iter().map(|p| vec![Value::Text(p)]).collect()
+        };
+        Ok(ResultSet {
+            columns: vec![name],
+            affinities: vec![None],
+            rows,
+        })
+    }
+
+    /// Reads every row of a table, the rowid first as a hidden column.
+    /// WITHOUT ROWID tables have no rowid, and equality constraints of
+    /// `where_clause` on their leading primary key columns turn the scan into a seek.
+    /// `needed` caps the rows a recursive common table expression produces.
+    fn scan(&mut self, table_ref: &TableRef, where_clause: Option<&Expr>, needed: Option<usize>) -> Result<Relation> {
+        if let Some(i) = self.ctes.iter().rposition(|b| b.cte.name.eq_ignore_ascii_case(&table_ref.name)) {
+            return self.cte_table(i, table_ref, needed);
+        }
+        let view = self
+            .schema
+            .iter()
+            .find(|e| e.kind == "view" && e.name.eq_ignore_ascii_case(&table_ref.name));
+        if let Some(view) = view.cloned() {
+            return self.view(&view, table_ref);
+        }
+        let table = find_table(&self.schema, &table_ref.name)?;
+        let label = table_ref.alias.clone().unwrap_or(table.name.clone());
+
+        let mut columns = vec![];
+        if !table.without_rowid {
+            columns.push(SourceColumn {
+                table: label.clone(),
+                name: "rowid".to_string(),
+                hidden: true,
+                affinity: Some(Affinity::Integer),
+            });
+        }
+        let first = columns.len();
+        columns.extend(table.columns.iter().map(|c| SourceColumn {
+            table: label.clone(),
+            name: c.name.clone(),
+            hidden: false,
+            affinity: Some(c.affinity),
+        }));
+
+        let root = table.rootpage as u64;
+        let key = match (table.without_rowid, where_clause) {
+            (true, Some(cond)) => self.seek_key(&table, &label, cond)?,
+            _ => vec![],
+        };
+        let cells = if key.is_empty() {
+            btree_cells(self.db, root, ContentVariant::RowCell)?
+        } else {
+            index_seek(self.db, root, ContentVariant::RowCell, &|cell| compare_key(&table, &key, cell))?
+        };
+
+        let defaults = self.column_defaults(&table)?;
+
+        let order = table.record_order();
+        let mut rows = vec![];
+        for cell in cells {
+            let Content::RowCell(record) = &cell.content else {
+                continue;
+            };
+            let rowid = cell.rowid.as_ref().map_or(0, |r| r.val as i64);
+            let mut row = vec![Value::Null; columns.len()];
+            if !table.without_rowid {
+                row[0] = Value::Integer(rowid);
+            }
+            for (field, &i) in order.iter().enumerate() {
+                let column = &table.columns[i];
+                row[first + i] = if column.rowid_alias {
+                    Value::Integer(rowid)
+                } else {
+                    // REAL columns store integral values as integers to save space
+                    match (record.row.get(field).map_or_else(|| defaults[i].clone(), Value::from), column.affinity) {
+                        (Value::Integer(v), Affinity::Real) => Value::Real(v as f64),
+                        (v, _) => v,
+                    }
+                };
+            }
+            rows.push(row);
+        }
+
+        Ok(Relation { columns, rows })
+    }
+
+    /// The value of each column of `table` in records written before ALTER
+    /// TABLE ADD COLUMN added it, which lack the new trailing fields.
+    pub(crate) fn column_defaults(&mut self, table: &Table) -> Result<Vec<Value>> {
+        let no_row = Env {
+            columns: &[],
+            row: &[],
+            group: None,
+            windows: &[],
+        };
+        let mut defaults = vec![];
+        for column in &table.columns {
+            defaults.push(match &column.default {
+                Some(default) => column.affinity.apply(self.eval(default, &no_row)?),
+                None => Value::Null,
+            });
+        }
+        Ok(defaults)
+    }
+
+    /// Runs the select of a view, its rows become the rows of the relation.
+    fn view(&mut self, entry: &SchemaEntry, table_ref: &TableRef) -> Result<Relation> {
+        if self.views.iter().any(|v| v.eq_ignore_ascii_case(&entry.name)) {
+            return Err(DbError::Query(format!("view {} is circularly defined", entry.name)));
+        }
+        let Some(sql) = &entry.sql else {
+            return Err(DbError::Corrupt(format!("view {} has no schema sql", entry.name)));
+        };
+        let view = Parser::parse_create_view(sql)
+            .map_err(|e| DbError::Corrupt(format!("malformed schema for {}: {}", entry.name, e)))?;
+
+        // a view can't see the common table expressions of the query using it
+        let ctes = std::mem::take(&mut self.ctes);
+        self.views.push(entry.name.clone());
+        let result = self.detached_select(&view.select);
+        self.views.pop();
+        self.ctes = ctes;
+        let result = result?;
+
+        let names = if view.columns.is_empty() {
+            unique_names(result.columns)
+        } else if view.columns.len() == result.columns.len() {
+            view.columns
+        } else {
+            return Err(DbError::Query(format!(
+                "expected {} columns for '{}' but got {}",
+                view.columns.len(),
+                entry.name,
+                result.columns.len()
+            )));
+        };
+        let label = table_ref.alias.clone().unwrap_or(entry.name.clone());
+        Ok(Relation {
+            columns: names
+                .into_iter()
+                .zip(result.affinities)
+                .map(|(name, affinity)| SourceColumn {
+                    table: label.clone(),
+                    name,
+                    hidden: false,
+                    affinity,
+                })
+                .collect(),
+            rows: result.rows,
+        })
+    }
+
+    /// Reads from the common table expression `self.ctes[i]`.
+    fn cte_table(&mut self, i: usize, table_ref: &TableRef, needed: Option<usize>) -> Result<Relation> {
+        let name = self.ctes[i].cte.name.clone();
+        let (names, rows) = match &self.ctes[i].state {
+            CteState::Idle => self.materialize(i, needed)?,
+            CteState::Running => return Err(DbError::Query(format!("circular reference: {}", name))),
+            CteState::Recursing { depth, .. } if *depth != self.outer.len() => {
+                return Err(DbError::Query(format!("recursive reference in a subquery: {}", name)))
+            }
+            CteState::Recursing { columns, row, .. } => (columns.clone(), vec![row.clone()]),
+        };
+        let label = table_ref.alias.clone().unwrap_or(name);
+        Ok(Relation {
+            columns: names
+                .into_iter()
+                .map(|name| SourceColumn {
+                    table: label.clone(),
+                    name,
+                    hidden: false,
+                    affinity: None,
+                })
+                .collect(),
+            rows,
+        })
+    }
+
+    /// Runs the select of a common table expression, seeing only the tables
+    /// of its own and enclosing WITH clauses and the rows of enclosing queries.
+    fn materialize(&mut self, i: usize, needed: Option<usize>) -> Result<(Vec<String>, Vec<Vec<Value>>)> {
+        let binding = &self.ctes[i];
+        let (cte, scope_end, outer_depth) = (binding.cte.clone(), binding.scope_end, binding.outer_depth);
+        let nested = self.ctes.split_off(scope_end.min(self.ctes.len()));
+        let inner = self.outer.split_off(outer_depth.min(self.outer.len()));
+        self.ctes[i].state = CteState::Running;
+        let result = self.run_cte(i, &cte, needed);
+        self.ctes[i].state = CteState::Idle;
+        self.outer.extend(inner);
+        self.ctes.extend(nested);
+        result
+    }
+
+    fn run_cte(&mut self, i: usize, cte: &Cte, needed: Option<usize>) -> Result<(Vec<String>, Vec<Vec<Value>>)> {
+        let references = match cte.select.compound.last() {
+            Some((CompoundOp::Union | CompoundOp::UnionAll, step)) => step
+                .from
+                .iter()
+                .filter(|item| matches!(item, FromItem::Table(t) if t.name.eq_ignore_ascii_case(&cte.name)))
+                .count(),
+            _ => 0,
+        };
+        if references == 0 {
+            let result = self.select(&cte.select)?;
+            return Ok((cte_names(cte, result.columns)?, result.rows));
+        }
+        if references > 1 {
+            return Err(DbError::Query(format!("multiple references to recursive table: {}", cte.name)));
+        }
+
+        // the terms before the last are the initial select, ORDER BY and LIMIT apply to the recursion
+        let mut initial = cte.select.clone();
+        let Some((op, step)) = initial.compound.pop() else {
+            unreachable!("a recursive select is compound");
+        };
+        // the recursive select only ever sees one row, an aggregate over it would never run out of rows
+        let aggregates = |c: &ResultColumn| matches!(c, ResultColumn::Expr { expr, .. } if contains_aggregate(expr));
+        if !step.group_by.is_empty() || step.columns.iter().any(aggregates) {
+            return Err(DbError::Query("recursive aggregate queries not supported".to_string()));
+        }
+        let order_by = std::mem::take(&mut initial.order_by);
+        let (offset, limit) = self.limits(initial.limit.take().as_ref(), initial.offset.take().as_ref())?;
+        let limit = limit.min(needed.unwrap_or(usize::MAX));
+        let result = self.select(&initial)?;
+        let order = compound_order(&order_by, &result.columns)?;
+        let names = cte_names(cte, result.columns)?;
+
+        let mut seen: Vec<Vec<Value>> = vec![];
+        let mut fresh = |row: &Vec<Value>| {
+            if op == CompoundOp::UnionAll {
+                return true;
+            }
+            if seen.iter().any(|s| compare_rows(s, row).is_eq()) {
+                return false;
+            }
+            seen.push(row.clone());
+            true
+        };
+
+        // rows wait in a queue, in ORDER BY order if there is one, and every
+        // row taken off it is output and fed to the recursive select
+        let mut queue: VecDeque<Vec<Value>> = result.rows.into_iter().filter(|r| fresh(r)).collect();
+        let mut output = vec![];
+        let mut skip = offset;
+        while output.len() < limit {
+            let next = if order.is_empty() {
+                queue.pop_front()
+            } else {
+                let min = (0..queue.len()).min_by(|&a, &b| compare_by(&order, &queue[a], &queue[b]));
+                min.and_then(|m| queue.remove(m))
+            };
+            let Some(row) = next else {
+                break;
+            };
+            self.ctes[i].state = CteState::Recursing {
+                columns: names.clone(),
+                row: row.clone(),
+                depth: self.outer.len(),
+            };
+            let result = self.select(&step);
+            self.ctes[i].state = CteState::Running;
+            let result = result?;
+            if result.columns.len() != names.len() {
+                return Err(arity_error(op));
+            }
+            queue.extend(result.rows.into_iter().filter(|r| fresh(r)));
+            if skip > 0 {
+                skip -= 1;
+            } else {
+                output.push(row);
+            }
+        }
+        Ok((names, output))
+    }
+
+    /// Evaluates OFFSET and LIMIT, a negative limit means no limit.
+    fn limits(&mut self, limit: Option<&Expr>, offset: Option<&Expr>) -> Result<(usize, usize)> {
+        let no_row = Env {
+            columns: &[],
+            row: &[],
+            group: None,
+            windows: &[],
+        };
+        let offset = match offset {
+            Some(e) => self.eval(e, &no_row)?.to_i64().max(0) as usize,
+            None => 0,
+        };
+        let limit = match limit {
+            Some(e) => self.eval(e, &no_row)?.to_i64(),
+            None => -1,
+        };
+        Ok((offset, if limit < 0 { usize::MAX } else { limit as usize }))
+    }
+
+    /// A derived table, `FROM (SELECT ...) AS alias`.
+    fn derived(&mut self, select: &Select, alias: Option<&str>) -> Result<Relation> {
+        let result = self.detached_select(select)?;
+        let label = alias.unwrap_or_default().to_string();
+        Ok(Relation {
+            columns: unique_names(result.columns)
+                .into_iter()
+                .zip(result.affinities)
+                .map(|(name, affinity)| SourceColumn {
+                    table: label.clone(),
+                    name,
+                    hidden: false,
+                    affinity,
+                })
+                .collect(),
+            rows: result.rows,
+        })
+    }
+
+    /// Runs a select that can't see the rows of enclosing queries, like the
+    /// select of a view or a derived table.
+    fn detached_select(&mut self, select: &Select) -> Result<ResultSet> {
+        let outer = std::mem::take(&mut self.outer);
+        let result = self.select(select);
+        self.outer = outer;
+        result
+    }
+
+    /// Runs a subquery of an expression evaluated against the row `env`.
+    /// Subqueries that turn out not to reference the rows of enclosing
+    /// queries are cached and not run again.
+    fn subquery(&mut self, select: &Select, env: &Env) -> Result<Rc<ResultSet>> {
+        let visible: Vec<usize> = self.ctes.iter().map(|b| b.id).collect();
+        if let Some((_, _, result)) = self.subqueries.iter().find(|(s, ctes, _)| s == select && *ctes == visible) {
+            return Ok(result.clone());
+        }
+        let depth = self.outer.len();
+        self.outer.push((env.columns.to_vec(), env.row.to_vec()));
+        let enclosing_hit = self.outer_hit.take();
+        let result = self.select(select);
+        self.outer.pop();
+        let hit = self.outer_hit;
+        self.outer_hit = match (enclosing_hit, hit) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        let result = Rc::new(result?);
+        // references past `depth` were to rows of the subquery's own nested subqueries
+        let correlated = hit.is_some_and(|i| i <= depth);
+        if !correlated {
+            self.subqueries.push((select.clone(), visible, result.clone()));
+        }
+        Ok(result)
+    }
+
+    /// Looks up a column the current query doesn't have in the enclosing queries, innermost first.
+    pub(crate) fn outer_column(&mut self, table: Option<&str>, name: &str) -> Result<Option<Value>> {
+        for i in (0..self.outer.len()).rev() {
+            let (columns, row) = &self.outer[i];
+            match resolve_column(columns, table, name) {
+                Ok(c) => {
+                    let value = row.get(c).cloned().unwrap_or(Value::Null);
+                    self.outer_hit = Some(self.outer_hit.map_or(i, |hit| hit.min(i)));
+                    return Ok(Some(value));
+                }
+                Err(DbError::NoSuchColumn(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Values of the leading primary key columns that `cond` requires to
+    /// equal a constant, empty when the table has to be scanned in full.
+    fn seek_key(&mut self, table: &Table, label: &str, cond: &Expr) -> Result<Vec<Value>> {
+        let mut terms = vec![];
+        conjuncts(cond, &mut terms);
+        let no_row = Env {
+            columns: &[],
+            row: &[],
+            group: None,
+            windows: &[],
+        };
+
+        let mut key = vec![];
+        'columns: for pk in &table.primary_key {
+            if pk.collation.is_none() {
+                break;
+            }
+            let name = &table.columns[pk.column].name;
+            let is_pk = |e: &Expr| match &e.kind {
+                ExprKind::Column { table, name: column } => {
+                    column.eq_ignore_ascii_case(name) && table.as_ref().map_or(true, |t| t.eq_ignore_ascii_case(label))
+                }
+                _ => false,
+            };
+            for term in &terms {
+                let ExprKind::Binary { op: BinaryOp::Eq, left, right } = &term.kind else {
+                    continue;
+                };
+                let value = match (is_pk(left), is_pk(right)) {
+                    (true, _) if is_constant(right) => right,
+                    (_, true) if is_constant(left) => left,
+                    _ => continue,
+                };
+                let affinity = Some(table.columns[pk.column].affinity);
+                key.push(compare_affinity(self.eval(value, &no_row)?, expr_affinity(value, &[]), affinity));
+                continue 'columns;
+            }
+            break;
+        }
+        Ok(key)
+    }
+
+    fn select(&mut self, select: &Select) -> Result<ResultSet> {
+        if select.with.is_empty() {
+            return self.compound_select(select);
+        }
+        let start = self.ctes.len();
+        for cte in &select.with {
+            self.ctes.push(CteBinding {
+                cte: Rc::new(cte.clone()),
+                id: self.next_cte_id,
+                scope_end: start + select.with.len(),
+                outer_depth: self.outer.len(),
+                state: CteState::Idle,
+            });
+            self.next_cte_id += 1;
+        }
+        let result = self.compound_select(select);
+        self.ctes.truncate(start);
+        result
+    }
+
+    /// Combines the terms of a compound select left to right, ORDER BY and
+    /// LIMIT then apply to the result. UNION, INTERSECT and EXCEPT output
+    /// their rows sorted, like sqlite's temporary index does.
+    fn compound_select(&mut self, select: &Select) -> Result<ResultSet> {
+        if select.compound.is_empty() {
+            return self.simple_select(select, false);
+        }
+        let mut result = self.simple_select(select, true)?;
+        for (op, term) in &select.compound {
+            let right = self.simple_select(term, true)?;
+            if right.columns.len() != result.columns.len() {
+                return Err(arity_error(*op));
+            }
+            for (i, (affinity, term_affinity)) in result.affinities.iter_mut().zip(&right.affinities).enumerate() {
+                *affinity = compound_affinity(*affinity, term_kinds(term, *term_affinity, i));
+            }
+            let left = std::mem::take(&mut result.rows);
+            result.rows = match op {
+                CompoundOp::UnionAll => left.into_iter().chain(right.rows).collect(),
+                CompoundOp::Union => distinct(left.into_iter().chain(right.rows).collect()),
+                CompoundOp::Intersect | CompoundOp::Except => {
+                    let right = distinct(right.rows);
+                    let keep = *op == CompoundOp::Intersect;
+                    distinct(left)
+                        .into_iter()
+                        .filter(|row| right.binary_search_by(|r| compare_rows(r, row)).is_ok() == keep)
+                        .collect()
+                }
+            };
+        }
+
+        let order = compound_order(&select.order_by, &result.columns)?;
+        result.rows.sort_by(|a, b| compare_by(&order, a, b));
+        let (offset, limit) = self.limits(select.limit.as_ref(), select.offset.as_ref())?;
+        result.rows = result.rows.into_iter().skip(offset).take(limit).collect();
+        Ok(result)
+    }
+
+    /// A select without compound terms. As a `term` of a compound select
+    /// it leaves ORDER BY and LIMIT to the compound.
+    fn simple_select(&mut self, select: &Select, term: bool) -> Result<ResultSet> {
+        let order_by: &[OrderingTerm] = if term { &[] } else { &select.order_by };
+        // a recursive common table expression only has to produce the rows a plain LIMIT takes
+        let plain = !term
+            && select.where_clause.is_none()
+            && select.group_by.is_empty()
+            && select.order_by.is_empty()
+            && !select.distinct
+            && !select.columns.iter().any(|c| matches!(c, ResultColumn::Expr { expr, .. } if contains_aggregate(expr) || contains_window(expr)));
+        let needed = match plain && select.limit.is_some() && select.from.len() == 1 {
+            true => {
+                let (offset, limit) = self.limits(select.limit.as_ref(), select.offset.as_ref())?;
+                Some(offset.saturating_add(limit))
+            }
+            false => None,
+        };
+        let mut relations = vec![];
+        for item in &select.from {
+            relations.push(match item {
+                FromItem::Table(table_ref) => self.scan(table_ref, select.where_clause.as_ref(), needed)?,
+                FromItem::Subquery { select, alias } => self.derived(select, alias.as_deref())?,
+            });
+        }
+        let source = Relation {
+            columns: relations.iter().flat_map(|r| r.columns.iter().cloned()).collect(),
+            rows: vec![],
+        };
+
+        // nested loop over the joined tables, the last one innermost
+        let mut rows = vec![];
+        let mut cursor = vec![0; relations.len()];
+        while relations.iter().zip(&cursor).all(|(r, &c)| c < r.rows.len()) {
+            let row: Vec<Value> = relations.iter().zip(&cursor).flat_map(|(r, &c)| r.rows[c].iter().cloned()).collect();
+            let keep = match &select.where_clause {
+                Some(cond) => {
+                    let env = Env {
+                        columns: &source.columns,
+                        row: &row,
+                        group: None,
+                        windows: &[],
+                    };
+                    self.eval(cond, &env)?.truthy() == Some(true)
+                }
+                None => true,
+            };
+            if keep {
+                rows.push(row);
+            }
+            let Some(last) = (0..cursor.len()).rev().find(|&j| cursor[j] + 1 < relations[j].rows.len()) else {
+                break;
+            };
+            cursor[last] += 1;
+            cursor[last + 1..].fill(0);
+        }
+
+        // expand `*` into column references
+        let mut outputs: Vec<(Expr, String)> = vec![];
+        for column in &select.columns {
+            match column {
+                ResultColumn::Star | ResultColumn::TableStar(_) => {
+                    let table = match column {
+                        ResultColumn::TableStar(t) => Some(t),
+                        _ => None,
+                    };
+                    let before = outputs.len();
+                    for c in &source.columns {
+                        if c.hidden || table.is_some_and(|t| !c.table.eq_ignore_ascii_case(t)) {
+                            continue;
+                        }
+                        let expr = Expr {
+                            kind: ExprKind::Column {
+                                table: Some(c.table.clone()),
+                                name: c.name.clone(),
+                            },
+                            span: Default::default(),
+                        };
+                        outputs.push((expr, c.name.clone()));
+                    }
+                    if outputs.len() == before {
+                        match table {
+                            Some(t) => return Err(DbError::NoSuchTable(t.clone())),
+                            None => return Err(DbError::Query("no tables specified".to_string())),
+                        }
+                    }
+                }
+                ResultColumn::Expr { expr, alias, text } => {
+                    let name = match (alias, &expr.kind) {
+                        (Some(alias), _) => alias.clone(),
+                        (None, ExprKind::Column { table, name }) => {
+                            let i = resolve_column(&source.columns, table.as_deref(), name)?;
+                            match source.columns[i].hidden {
+                                true => name.clone(),
+                                false => source.columns[i].name.clone(),
+                            }
+                        }
+                        (None, _) => text.clone(),
+                    };
+                    outputs.push((expr.clone(), name));
+                }
+            }
+        }
+
+        let group_by = group_terms(&select.group_by, &outputs)?;
+        let aggregate = !group_by.is_empty()
+            || outputs.iter().any(|(e, _)| contains_aggregate(e))
+            || select.having.as_ref().is_some_and(contains_aggregate)
+            || order_by.iter().any(|o| contains_aggregate(&o.expr));
+
+        // the rows to project, with their group in an aggregate query
+        let mut projected: Vec<(Vec<Value>, Option<Group>)> = vec![];
+        if aggregate {
+            for group in self.group(&group_by, &source.columns, rows)? {
+                let row = group.first().cloned().unwrap_or_else(|| vec![Value::Null; source.columns.len()]);
+                let env = Env {
+                    columns: &source.columns,
+                    row: &row,
+                    group: Some(&group),
+                    windows: &[],
+                };
+                if let Some(having) = &select.having {
+                    if self.eval(having, &env)?.truthy() != Some(true) {
+                        continue;
+                    }
+                }
+                projected.push((row, Some(group)));
+            }
+        } else {
+            projected = rows.into_iter().map(|row| (row, None)).collect();
+        }
+
+        let mut calls = vec![];
+        for (expr, _) in &outputs {
+            window_calls(expr, &mut calls);
+        }
+        for term in order_by {
+            window_calls(&term.expr, &mut calls);
+        }
+        let envs: Vec<Env> = projected
+            .iter()
+            .map(|(row, group)| Env {
+                columns: &source.columns,
+                row,
+                group: group.as_deref(),
+                windows: &[],
+            })
+            .collect();
+        let (window_values, order) = match calls.is_empty() {
+            true => (vec![vec![]; envs.len()], (0..envs.len()).collect()),
+            false => self.window_values(&calls, &select.windows, &envs)?,
+        };
+        let mut output = vec![];
+        for i in order {
+            let windows: Vec<(&Expr, Value)> = calls.iter().copied().zip(window_values[i].iter().cloned()).collect();
+            let env = Env {
+                windows: &windows,
+                ..envs[i]
+            };
+            output.push(self.project(order_by, &outputs, &env)?);
+        }
+
+        if select.distinct {
+            let mut unique: Vec<OutputRow> = vec![];
+            for row in output {
+                if !unique.iter().any(|u| u.values == row.values) {
+                    unique.push(row);
+                }
+            }
+            output = unique;
+        }
+
+        if !order_by.is_empty() {
+            output.sort_by(|a, b| compare_keys(order_by, &a.keys, &b.keys));
+        }
+        let (offset, limit) = match term {
+            true => (0, usize::MAX),
+            false => self.limits(select.limit.as_ref(), select.offset.as_ref())?,
+        };
+
+        Ok(ResultSet {
+            affinities: outputs.iter().map(|(expr, _)| expr_affinity(expr, &source.columns)).collect(),
+            columns: outputs.into_iter().map(|(_, name)| name).collect(),
+            rows: output
+                .into_iter()
+                .skip(offset)
+                .take(limit)
+                .map(|r| r.values)
+                .collect(),
+        })
+    }
+
+    /// Splits the rows into groups of equal GROUP BY keys, in key order.
+    /// Without GROUP BY all rows form a single, possibly empty, group.
+    fn group(&mut self, group_by: &[Expr], columns: &[SourceColumn], rows: Vec<Vec<Value>>) -> Result<Vec<Group>> {
+        if group_by.is_empty() {
+            return Ok(vec![rows]);
+        }
+        let mut keyed = vec![];
+        for row in rows {
+            let env = Env {
+                columns,
+                row: &row,
+                group: None,
+                windows: &[],
+            };
+            let mut key = vec![];
+            for e in group_by {
+                key.push(self.eval(e, &env)?);
+            }
+            keyed.push((key, row));
+        }
+        keyed.sort_by(|(a, _), (b, _)| {
+            a.iter()
+                .zip(b)
+                .map(|(x, y)| x.compare(y))
+                .find(|o| o.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let mut groups: Vec<Group> = vec![];
+        let mut last_key: Option<Vec<Value>> = None;
+        for (key, row) in keyed {
+            let same = last_key
+                .as_ref()
+                .is_some_and(|k| k.iter().zip(&key).all(|(x, y)| x.compare(y).is_eq()));
+            match groups.last_mut() {
+                Some(group) if same => group.push(row),
+                _ => groups.push(vec![row]),
+            }
+            last_key = Some(key);
+        }
+        Ok(groups)
+    }
+
+    fn project(&mut self, order_by: &[OrderingTerm], outputs: &[(Expr, String)], env: &Env) -> Result<OutputRow> {
+        let mut values = vec![];
+        for (expr, _) in outputs {
+            values.push(self.eval(expr, env)?);
+        }
+
+        let mut keys = vec![];
+        for term in order_by {
+            let key = match &term.expr.kind {
+                ExprKind::Literal(Value::Integer(i)) => match values.get((*i as usize).wrapping_sub(1)) {
+                    Some(v) => v.clone(),
+                    None => return Err(order_by_range_error(values.len())),
+                },
+                ExprKind::Column { table: None, name } => {
+                    match outputs.iter().position(|(_, alias)| alias.eq_ignore_ascii_case(name)) {
+                        Some(i) => values[i].clone(),
+                        None => self.eval(&term.expr, env)?,
+                    }
+                }
+                _ => self.eval(&term.expr, env)?,
+            };
+            keys.push(key);
+        }
+
+        Ok(OutputRow { values, keys })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    /// Runs `sql` against `db`.
+    pub(super) fn run(db: &mut DB, sql: &str) -> Result<ResultSet> {
+        let statement = Parser::parse_statement(sql)?;
+        Executor::new(db)?.execute(&statement)
+    }
+
+    /// Runs `sql` against sample.db.
+    pub(super) fn query(sql: &str) -> Result<Vec<Vec<Value>>> {
+        let mut db = DB::new(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db"))?;
+        Ok(run(&mut db, sql)?.rows)
+    }
+
+    /// The first column of the rows `sql` returns, as strings.
+    pub(super) fn strings(sql: &str) -> Vec<String> {
+        query(sql).unwrap().into_iter().map(|r| r[0].to_string()).collect()
+    }
+
+    #[test]
+    fn groups_by_result_column_ordinals() {
+        let rows = query("SELECT length(color) > 5, count(*) FROM apples GROUP BY 1").unwrap();
+        assert_eq!(rows, [[Value::Integer(0), Value::Integer(1)], [Value::Integer(1), Value::Integer(3)]]);
+        assert_eq!(strings("SELECT *, count(*) FROM apples GROUP BY 3 ORDER BY 1").len(), 4);
+        // only a bare integer is an ordinal
+        assert_eq!(strings("SELECT count(*) FROM apples GROUP BY 1 + 0"), ["4"]);
+        assert_eq!(strings("SELECT count(*) FROM apples GROUP BY 1.0"), ["4"]);
+
+        let error = |sql| query(sql).unwrap_err().to_string();
+        assert_eq!(
+            error("SELECT name, count(*) FROM apples GROUP BY name, 3"),
+            "2nd GROUP BY term out of range - should be between 1 and 2"
+        );
+        assert_eq!(
+            error("SELECT name FROM apples GROUP BY 0"),
+            "1st GROUP BY term out of range - should be between 1 and 1"
+        );
+        assert_eq!(
+            error("SELECT count(*) FROM apples GROUP BY 1"),
+            "aggregate functions are not allowed in the GROUP BY clause"
+        );
+    }
+
+    #[test]
+    fn expands_views() {
+        let rows: Vec<_> = (1..=3).map(|i| vec![Value::Integer(i), Value::Text(format!("b{}", i))]).collect();
+        let views = [
+            ("v1", "CREATE VIEW v1 AS SELECT a, b, a * 2 AS d FROM t WHERE a > 1"),
+            ("v2", "CREATE VIEW v2(p, q) AS SELECT d, b FROM v1"),
+            ("v3", "CREATE VIEW v3 AS SELECT a, a FROM t"),
+            ("twice", "CREATE VIEW twice AS SELECT x.a FROM v1 AS x JOIN v1 AS y ON x.a = y.a"),
+            ("nested", "CREATE VIEW nested AS SELECT a FROM twice"),
+        ];
+        let mut db = fixtures::open(fixtures::one_table("CREATE TABLE t(a, b)", &rows, &views));
+        let mut column = |sql| run(&mut db, sql).unwrap().rows.into_iter().map(|r| r[0].to_string()).collect::<Vec<_>>();
+        assert_eq!(column("SELECT x.q FROM v2 AS x WHERE p > 4"), ["b3"]);
+        // reading a view more than once, or through another view, is no cycle
+        assert_eq!(column("SELECT * FROM twice"), ["2", "3"]);
+        assert_eq!(column("SELECT count(*) FROM nested JOIN v1 ON nested.a = v1.a"), ["2"]);
+        assert_eq!(run(&mut db, "SELECT * FROM v3").unwrap().columns, ["a", "a:1"]);
+    }
+
+    #[test]
+    fn detects_circular_views() {
+        let views = [
+            ("c1", "CREATE VIEW c1 AS SELECT * FROM c2"),
+            ("c2", "CREATE VIEW c2 AS SELECT * FROM c1"),
+            ("s", "CREATE VIEW s AS SELECT * FROM s"),
+            ("via", "CREATE VIEW via AS SELECT * FROM t WHERE a IN (SELECT a FROM via)"),
+            // the table of the WITH clause hides the view
+            ("w", "CREATE VIEW w AS WITH w(a) AS (SELECT 10) SELECT a FROM w"),
+        ];
+        let mut db = fixtures::open(fixtures::one_table("CREATE TABLE t(a)", &[vec![Value::Integer(1)]], &views));
+        let mut error = |sql| run(&mut db, sql).unwrap_err().to_string();
+        assert_eq!(error("SELECT * FROM c1"), "view c1 is circularly defined");
+        assert_eq!(error("SELECT * FROM c2"), "view c2 is circularly defined");
+        assert_eq!(error("SELECT * FROM s"), "view s is circularly defined");
+        assert_eq!(error("SELECT * FROM via"), "view via is circularly defined");
+        assert_eq!(run(&mut db, "SELECT * FROM w").unwrap().rows, [[Value::Integer(10)]]);
+    }
+
+    #[test]
+    fn runs_common_table_expressions() {
+        let rows: Vec<_> = [(1, None), (2, Some(1)), (3, Some(1)), (4, Some(2))]
+            .into_iter()
+            .map(|(id, boss): (i64, Option<i64>)| vec![Value::Integer(id), boss.map_or(Value::Null, Value::Integer)])
+            .collect();
+        let mut db = fixtures::open(fixtures::one_table("CREATE TABLE t(id, boss)", &rows, &[]));
+        let mut column = |sql| run(&mut db, sql).unwrap().rows.into_iter().map(|r| r[0].to_string()).collect::<Vec<_>>();
+        let below = "WITH RECURSIVE below(id, depth) AS (SELECT 2, 0 UNION ALL \
+                     SELECT t.id, depth + 1 FROM t JOIN below ON t.boss = below.id) SELECT depth FROM below";
+        assert_eq!(column(below), ["0", "1"]);
+        let forward = "WITH a AS (SELECT id FROM b), b AS (SELECT id FROM t WHERE boss = 1) SELECT * FROM a";
+        assert_eq!(column(forward), ["2", "3"]);
+        assert_eq!(column("WITH c(x) AS (SELECT boss FROM t UNION SELECT 1) SELECT count(*) FROM c"), ["3"]);
+        let error = run(&mut db, "WITH c AS (SELECT * FROM c) SELECT * FROM c").unwrap_err();
+        assert_eq!(error.to_string(), "circular reference: c");
+    }
+
+    #[test]
+    fn stops_recursive_ctes() {
+        let counting = |step: &str, select: &str| {
+            strings(&format!("WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL {}) {}", step, select))
+        };
+        // when the recursive select returns no rows
+        assert_eq!(counting("SELECT x + 1 FROM c WHERE x < 5", "SELECT group_concat(x) FROM c"), ["1,2,3,4,5"]);
+        assert_eq!(counting("SELECT x + 1 FROM c WHERE 0", "SELECT count(*) FROM c"), ["1"]);
+        // at the LIMIT of the recursion or of the query reading it
+        assert_eq!(counting("SELECT x + 1 FROM c LIMIT 3", "SELECT group_concat(x) FROM c"), ["1,2,3"]);
+        assert_eq!(counting("SELECT x * 2 FROM c LIMIT 2 OFFSET 3", "SELECT group_concat(x) FROM c"), ["8,16"]);
+        assert_eq!(counting("SELECT x + 1 FROM c", "SELECT x FROM c LIMIT 3 OFFSET 2"), ["3", "4", "5"]);
+        // UNION drops rows seen before, which ends a cycle
+        let cycle = "WITH RECURSIVE c(x) AS (SELECT 0 UNION SELECT (x + 1) % 3 FROM c) SELECT group_concat(x) FROM c";
+        assert_eq!(strings(cycle), ["0,1,2"]);
+
+        // the recursive select sees one row at a time, so it can't aggregate or join the table with itself
+        let error = |step| query(&format!("WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL {}) SELECT x FROM c", step));
+        let error = |step| error(step).unwrap_err().to_string();
+        assert_eq!(error("SELECT count(*) FROM c"), "recursive aggregate queries not supported");
+        assert_eq!(error("SELECT x FROM c GROUP BY x"), "recursive aggregate queries not supported");
+        assert_eq!(error("SELECT c.x FROM c, c AS d"), "multiple references to recursive table: c");
+        assert_eq!(error("SELECT x + 1 FROM (SELECT * FROM c) WHERE x < 3"), "circular reference: c");
+    }
+}
@@ -0,0 +1,535 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use crate::error::{DbError, Result};
+
+use crate::query::eval::Env;
+use crate::query::functions::{aggregate, is_aggregate};
+use crate::query::{compare_keys, compare_rows, Executor};
+use crate::sql::ast::{Expr, ExprKind, Frame, FrameBound, FrameExclude, FrameUnits, WindowSpec};
+use crate::value::Value;
+
+/// Window functions that aren't aggregates, with the number of arguments they take.
+const WINDOW_FUNCTIONS: [(&str, Range<usize>); 11] = [
+    ("row_number", 0..1),
+    ("rank", 0..1),
+    ("dense_rank", 0..1),
+    ("percent_rank", 0..1),
+    ("cume_dist", 0..1),
+    ("ntile", 1..2),
+    ("lag", 1..4),
+    ("lead", 1..4),
+    ("first_value", 1..2),
+    ("last_value", 1..2),
+    ("nth_value", 2..3),
+];
+
+pub fn is_window_function(name: &str) -> bool {
+    WINDOW_FUNCTIONS.iter().any(|(f, _)| *f == name)
+}
+
+/// Collects the window function calls of `expr`, not looking into subqueries.
+pub fn window_calls<'e>(expr: &'e Expr, out: &mut Vec<&'e Expr>) {
+    match &expr.kind {
+        ExprKind::Window { .. } => out.push(expr),
+        ExprKind::Unary { expr, .. } | ExprKind::InSelect { expr, .. } | ExprKind::Cast { expr, .. } => window_calls(expr, out),
+        ExprKind::Binary { left, right, .. } => {
+            window_calls(left, out);
+            window_calls(right, out);
+        }
+        ExprKind::Function { args, .. } => args.iter().for_each(|a| window_calls(a, out)),
+        ExprKind::InList { expr, list, .. } => {
+            window_calls(expr, out);
+            list.iter().for_each(|e| window_calls(e, out));
+        }
+        ExprKind::Between { expr, low, high, .. } => [expr, low, high].into_iter().for_each(|e| window_calls(e, out)),
+        ExprKind::Case {
+            operand,
+            branches,
+            otherwise,
+        } => {
+            operand.iter().for_each(|e| window_calls(e, out));
+            for (when, then) in branches {
+                window_calls(when, out);
+                window_calls(then, out);
+            }
+            otherwise.iter().for_each(|e| window_calls(e, out));
+        }
+        ExprKind::Literal(_)
+        | ExprKind::Parameter(_)
+        | ExprKind::Column { .. }
+        | ExprKind::Subquery(_)
+        | ExprKind::Exists(_) => (),
+    }
+}
+
+pub fn contains_window(expr: &Expr) -> bool {
+    let mut calls = vec![];
+    window_calls(expr, &mut calls);
+    !calls.is_empty()
+}
+
+/// Merges a window with the named window it extends.
+fn resolve_window(over: &WindowSpec, named: &[(String, WindowSpec)]) -> Result<WindowSpec> {
+    let Some(base) = &over.base else {
+        return Ok(over.clone());
+    };
+    let Some((_, window)) = named.iter().find(|(name, _)| name.eq_ignore_ascii_case(base)) else {
+        return Err(DbError::Query(format!("no such window: {}", base)));
+    };
+    let mut window = resolve_window(window, named)?;
+    if !over.order_by.is_empty() {
+        window.order_by = over.order_by.clone();
+    }
+    if over.frame.is_some() {
+        window.frame = over.frame.clone();
+    }
+    Ok(window)
+}
+
+fn check_frame(frame: &Frame, order_terms: usize) -> Result<()> {
+    use FrameBound::*;
+    let supported = !matches!(
+        (&frame.start, &frame.end),
+        (UnboundedFollowing, _) | (_, UnboundedPreceding) | (CurrentRow, Preceding(_)) | (Following(_), Preceding(_) | CurrentRow)
+    );
+    if !supported {
+        return Err(DbError::Query("unsupported frame specification".to_string()));
+    }
+    let offset = matches!(frame.start, Preceding(_) | Following(_)) || matches!(frame.end, Preceding(_) | Following(_));
+    if frame.units == FrameUnits::Range && offset && order_terms != 1 {
+        return Err(DbError::Query("RANGE with offset PRECEDING/FOLLOWING requires one ORDER BY expression".to_string()));
+    }
+    Ok(())
+}
+
+/// A partition sorted by the window's ORDER BY, split into groups of peers.
+struct Partition<'p> {
+    /// Indexes of the partition's rows, in order.
+    rows: &'p [usize],
+    /// The peer group of each position.
+    group_of: Vec<usize>,
+    /// Start position of each peer group, plus the partition length at the end.
+    group_starts: Vec<usize>,
+}
+
+/// The frame of one row: a range of partition positions without the
+/// `excluded` ones, bar `kept`.
+#[derive(Clone, PartialEq)]
+struct FrameRows {
+    range: Range<usize>,
+    excluded: Range<usize>,
+    kept: Option<usize>,
+}
+
+impl FrameRows {
+    /// The positions of the frame whose rows pass the FILTER of the call.
+    fn positions<'f>(&'f self, passes: &'f [bool]) -> impl DoubleEndedIterator<Item = usize> + 'f {
+        self.range
+            .clone()
+            .filter(|&p| passes[p] && (!self.excluded.contains(&p) || self.kept == Some(p)))
+    }
+}
+
+impl Partition<'_> {
+    fn peers(&self, pos: usize) -> Range<usize> {
+        let g = self.group_of[pos];
+        self.group_starts[g]..self.group_starts[g + 1]
+    }
+
+    fn groups(&self) -> usize {
+        self.group_starts.len() - 1
+    }
+}
+
+impl Executor<'_> {
+    /// Computes the window function calls `calls` over the rows `envs`
+    /// being projected, `values[i][k]` being the value of call k for row i.
+    /// Also returns the rows in the order of the first call's window, which
+    /// is the order sqlite outputs them in without an ORDER BY.
+    pub(crate) fn window_values(
+        &mut self,
+        calls: &[&Expr],
+        named: &[(String, WindowSpec)],
+        envs: &[Env],
+    ) -> Result<(Vec<Vec<Value>>, Vec<usize>)> {
+        let mut values = vec![Vec::with_capacity(calls.len()); envs.len()];
+        let mut first_order = (0..envs.len()).collect();
+        for (k, call) in calls.iter().enumerate() {
+            let (column, order) = self.window_column(call, named, envs)?;
+            for (row, v) in values.iter_mut().zip(column) {
+                row.push(v);
+            }
+            if k == 0 {
+                first_order = order;
+            }
+        }
+        Ok((values, first_order))
+    }
+
+    fn window_column(&mut self, call: &Expr, named: &[(String, WindowSpec)], envs: &[Env]) -> Result<(Vec<Value>, Vec<usize>)> {
+        let ExprKind::Window {
+            name,
+            args,
+            star,
+            filter,
+            over,
+        } = &call.kind
+        else {
+            unreachable!("not a window function call");
+        };
+        match WINDOW_FUNCTIONS.iter().find(|(f, _)| f == name) {
+            Some((_, arity)) if !arity.contains(&args.len()) => {
+                return Err(DbError::Query(format!("wrong number of arguments to function {}()", name)))
+            }
+            Some(_) => (),
+            None if is_aggregate(name, args.len()) => (),
+            None => return Err(DbError::Query(format!("{}() may not be used as a window function", name))),
+        }
+        let aggregate = is_aggregate(name, args.len());
+        if filter.is_some() && !aggregate {
+            return Err(DbError::Query("FILTER clause may only be used with aggregate window functions".to_string()));
+        }
+        let spec = resolve_window(over, named)?;
+        let frame = spec.frame.clone().unwrap_or(Frame {
+            units: FrameUnits::Range,
+            start: FrameBound::UnboundedPreceding,
+            end: FrameBound::CurrentRow,
+            exclude: FrameExclude::NoOthers,
+        });
+        check_frame(&frame, spec.order_by.len())?;
+
+        let (mut partition_keys, mut order_keys, mut arg_values, mut filtered) = (vec![], vec![], vec![], vec![]);
+        for env in envs {
+            partition_keys.push(self.eval_all(&spec.partition_by, env)?);
+            order_keys.push(self.eval_all(spec.order_by.iter().map(|t| &t.expr), env)?);
+            arg_values.push(self.eval_all(args, env)?);
+            filtered.push(match filter {
+                Some(filter) => self.eval(filter, env)?.truthy() == Some(true),
+                None => true,
+            });
+        }
+        let mut order: Vec<usize> = (0..envs.len()).collect();
+        order.sort_by(|&a, &b| {
+            compare_rows(&partition_keys[a], &partition_keys[b])
+                .then_with(|| compare_keys(&spec.order_by, &order_keys[a], &order_keys[b]))
+        });
+
+        let descending = spec.order_by.first().is_some_and(|t| t.descending);
+        let mut out = vec![Value::Null; envs.len()];
+        for rows in order.chunk_by(|&a, &b| compare_rows(&partition_keys[a], &partition_keys[b]).is_eq()) {
+            let mut partition = Partition {
+                rows,
+                group_of: vec![],
+                group_starts: vec![],
+            };
+            for pos in 0..rows.len() {
+                let new_group =
+                    pos == 0 || compare_keys(&spec.order_by, &order_keys[rows[pos - 1]], &order_keys[rows[pos]]).is_ne();
+                if new_group {
+                    partition.group_starts.push(pos);
+                }
+                partition.group_of.push(partition.group_starts.len() - 1);
+            }
+            partition.group_starts.push(rows.len());
+
+            let args: Vec<Vec<Value>> = rows.iter().map(|&r| std::mem::take(&mut arg_values[r])).collect();
+            let passes: Vec<bool> = rows.iter().map(|&r| filtered[r]).collect();
+            // an aggregate only depends on the frame, which peers and whole-partition windows share
+            let mut previous: Option<(FrameRows, Value)> = None;
+            for pos in 0..rows.len() {
+                let (excluded, kept) = match frame.exclude {
+                    FrameExclude::NoOthers => (0..0, None),
+                    FrameExclude::CurrentRow => (pos..pos + 1, None),
+                    FrameExclude::Group => (partition.peers(pos), None),
+                    FrameExclude::Ties => (partition.peers(pos), Some(pos)),
+                };
+                let rows_of_frame = FrameRows {
+                    range: self.frame_rows(&frame, &partition, pos, &order_keys, descending, &envs[rows[pos]])?,
+                    excluded,
+                    kept,
+                };
+                let value = match previous {
+                    Some((ref f, ref v)) if aggregate && *f == rows_of_frame => v.clone(),
+                    _ => window_function(name, *star, &partition, pos, rows_of_frame.positions(&passes), &args)?,
+                };
+                out[rows[pos]] = value.clone();
+                previous = Some((rows_of_frame, value));
+            }
+        }
+        Ok((out, order))
+    }
+
+    fn eval_all<'e>(&mut self, exprs: impl IntoIterator<Item = &'e Expr>, env: &Env) -> Result<Vec<Value>> {
+        let mut values = vec![];
+        for e in exprs {
+            values.push(self.eval(e, env)?);
+        }
+        Ok(values)
+    }
+
+    /// Positions of the partition in the frame of the row at `pos`.
+    fn frame_rows(
+        &mut self,
+        frame: &Frame,
+        partition: &Partition,
+        pos: usize,
+        order_keys: &[Vec<Value>],
+        descending: bool,
+        env: &Env,
+    ) -> Result<Range<usize>> {
+        let len = partition.rows.len();
+        let mut bound = |b: &FrameBound, start: bool| -> Result<usize> {
+            let (offset, preceding) = match b {
+                FrameBound::UnboundedPreceding => return Ok(0),
+                FrameBound::UnboundedFollowing => return Ok(len),
+                FrameBound::CurrentRow => {
+                    return Ok(match frame.units {
+                        FrameUnits::Rows if start => pos,
+                        FrameUnits::Rows => pos + 1,
+                        _ if start => partition.peers(pos).start,
+                        _ => partition.peers(pos).end,
+                    })
+                }
+                FrameBound::Preceding(e) => (self.eval(e, env)?, true),
+                FrameBound::Following(e) => (self.eval(e, env)?, false),
+            };
+            let which = if start { "starting" } else { "ending" };
+            if frame.units == FrameUnits::Range {
+                let n = match offset.to_numeric() {
+                    Value::Integer(i) if i >= 0 => i as f64,
+                    Value::Real(r) if r >= 0.0 => r,
+                    _ => return Err(DbError::Query(format!("frame {} offset must be a non-negative number", which))),
+                };
+                return Ok(range_bound(partition, pos, order_keys, descending, n, preceding, start));
+            }
+            let n = match offset {
+                Value::Integer(i) if i >= 0 => i as usize,
+                _ => return Err(DbError::Query(format!("frame {} offset must be a non-negative integer", which))),
+            };
+            Ok(match frame.units {
+                FrameUnits::Rows => {
+                    let row = if preceding { pos.checked_sub(n) } else { Some(pos.saturating_add(n)) };
+                    match row {
+                        Some(row) if start => row.min(len),
+                        Some(row) => row.saturating_add(1).min(len),
+                        None => 0,
+                    }
+                }
+                _ => {
+                    let g = partition.group_of[pos];
+                    let group = if preceding { g.checked_sub(n) } else { Some(g.saturating_add(n)) };
+                    match group {
+                        Some(group) if group >= partition.groups() => len,
+                        Some(group) => partition.group_starts[group + !start as usize],
+                        None => 0,
+                    }
+                }
+            })
+        };
+        let from = bound(&frame.start, true)?;
+        let to = bound(&frame.end, false)?;
+        Ok(from..to.max(from))
+    }
+}
+
+/// A bound of a RANGE frame `n` away from the current row's ORDER BY value.
+/// Rows whose value is NULL only have each other as peers.
+fn range_bound(partition: &Partition, pos: usize, keys: &[Vec<Value>], descending: bool, n: f64, preceding: bool, start: bool) -> usize {
+    let key = &keys[partition.rows[pos]][0];
+    if key.is_null() || !matches!(key, Value::Integer(_) | Value::Real(_)) {
+        let peers = partition.peers(pos);
+        return if start { peers.start } else { peers.end };
+    }
+    // preceding rows come earlier in sort order, which is towards smaller values unless descending
+    let toward_smaller = preceding != descending;
+    let target = Value::Real(if toward_smaller { key.to_f64() - n } else { key.to_f64() + n });
+    let before = |row: &usize| {
+        let v = &keys[*row][0];
+        let ord = if v.is_null() { Ordering::Less } else { v.compare(&target) };
+        let ord = if descending { ord.reverse() } else { ord };
+        if start {
+            ord.is_lt()
+        } else {
+            ord.is_le()
+        }
+    };
+    partition.rows.partition_point(before)
+}
+
+/// The value of a window function for the row at `pos`, `args` holding the
+/// arguments of the partition's rows in order.
+fn window_function(
+    name: &str,
+    star: bool,
+    partition: &Partition,
+    pos: usize,
+    mut frame: impl DoubleEndedIterator<Item = usize>,
+    args: &[Vec<Value>],
+) -> Result<Value> {
+    let len = partition.rows.len();
+    let arg = |pos: usize, i: usize| args[pos].get(i).cloned().unwrap_or(Value::Null);
+    let value = match name {
+        "row_number" => Value::Integer(pos as i64 + 1),
+        "rank" => Value::Integer(partition.peers(pos).start as i64 + 1),
+        "dense_rank" => Value::Integer(partition.group_of[pos] as i64 + 1),
+        "percent_rank" if len > 1 => Value::Real(partition.peers(pos).start as f64 / (len - 1) as f64),
+        "percent_rank" => Value::Real(0.0),
+        "cume_dist" => Value::Real(partition.peers(pos).end as f64 / len as f64),
+        "ntile" => {
+            let buckets = match arg(pos, 0) {
+                Value::Integer(n) if n > 0 => n as usize,
+                _ => return Err(DbError::Query("argument of ntile must be a positive integer".to_string())),
+            };
+            // the first `len % buckets` buckets get one row more
+            let size = len / buckets;
+            let large = len % buckets;
+            let bucket = if size == 0 || pos < large * (size + 1) {
+                pos / (size + 1)
+            } else {
+                large + (pos - large * (size + 1)) / size
+            };
+            Value::Integer(bucket as i64 + 1)
+        }
+        "lag" | "lead" => {
+            let offset = if args[pos].len() > 1 { arg(pos, 1).to_i64() } else { 1 };
+            let offset = if name == "lag" { -offset } else { offset };
+            match pos.checked_add_signed(offset as isize).filter(|&p| p < len) {
+                Some(p) => arg(p, 0),
+                None => arg(pos, 2),
+            }
+        }
+        "first_value" => frame.next().map_or(Value::Null, |p| arg(p, 0)),
+        "last_value" => frame.next_back().map_or(Value::Null, |p| arg(p, 0)),
+        "nth_value" => {
+            let n = match arg(pos, 1) {
+                Value::Integer(n) if n > 0 => n as usize,
+                _ => return Err(DbError::Query("second argument to nth_value must be a positive integer".to_string())),
+            };
+            frame.nth(n - 1).map_or(Value::Null, |p| arg(p, 0))
+        }
+        _ => aggregate(name, &frame.map(|p| args[p].as_slice()).collect::<Vec<_>>(), star)?,
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{query, strings};
+
+    /// `SELECT x, <call> FROM t` over x = NULL, 1, 2, 2, 3, as strings.
+    fn window(call: &str) -> Vec<String> {
+        let sql = format!(
+            "WITH t(x) AS (SELECT 1 UNION ALL SELECT 2 UNION ALL SELECT 2 UNION ALL SELECT 3 UNION ALL SELECT NULL) \
+             SELECT x, {} FROM t ORDER BY x",
+            call
+        );
+        query(&sql).unwrap().iter().map(|r| format!("{}|{}", r[0], r[1])).collect()
+    }
+
+    #[test]
+    fn excludes_rows_from_the_frame() {
+        let all = "ORDER BY x ROWS BETWEEN UNBOUNDED PRECEDING AND UNBOUNDED FOLLOWING";
+        let sum = |exclude| window(&format!("sum(x) OVER ({} EXCLUDE {})", all, exclude));
+        assert_eq!(sum("NO OTHERS"), ["|8", "1|8", "2|8", "2|8", "3|8"]);
+        assert_eq!(sum("CURRENT ROW"), ["|8", "1|7", "2|6", "2|6", "3|5"]);
+        assert_eq!(sum("GROUP"), ["|8", "1|7", "2|4", "2|4", "3|5"]);
+        assert_eq!(sum("TIES"), ["|8", "1|8", "2|6", "2|6", "3|8"]);
+
+        // the current row keeps its place among the rows that are left
+        assert_eq!(
+            window("group_concat(x, '') OVER (ORDER BY x GROUPS BETWEEN 1 PRECEDING AND 1 FOLLOWING EXCLUDE TIES)"),
+            ["|1", "1|122", "2|123", "2|123", "3|223"]
+        );
+        assert_eq!(
+            window("first_value(x) OVER (ORDER BY x ROWS BETWEEN CURRENT ROW AND 1 FOLLOWING EXCLUDE CURRENT ROW)"),
+            ["|1", "1|2", "2|2", "2|3", "3|"]
+        );
+        assert_eq!(
+            window("last_value(x) OVER (ORDER BY x RANGE BETWEEN 1 PRECEDING AND CURRENT ROW EXCLUDE GROUP)"),
+            ["|", "1|", "2|1", "2|1", "3|2"]
+        );
+        // functions that don't look at the frame ignore it
+        assert_eq!(
+            window("rank() OVER (ORDER BY x ROWS CURRENT ROW EXCLUDE GROUP)"),
+            ["|1", "1|2", "2|3", "2|3", "3|5"]
+        );
+    }
+
+    #[test]
+    fn filters_the_rows_of_aggregates() {
+        assert_eq!(window("sum(x) FILTER (WHERE x > 1) OVER (ORDER BY x)"), ["|", "1|", "2|4", "2|4", "3|7"]);
+        assert_eq!(
+            window("sum(x) FILTER (WHERE x > 1) OVER (ORDER BY x ROWS BETWEEN 1 PRECEDING AND 1 FOLLOWING EXCLUDE CURRENT ROW)"),
+            ["|", "1|2", "2|2", "2|5", "3|2"]
+        );
+        assert_eq!(
+            window("count(*) FILTER (WHERE x <> 2) OVER ()"),
+            ["|2", "1|2", "2|2", "2|2", "3|2"]
+        );
+        assert_eq!(
+            query("SELECT row_number() FILTER (WHERE 1) OVER () FROM apples").unwrap_err().to_string(),
+            "FILTER clause may only be used with aggregate window functions"
+        );
+    }
+
+    #[test]
+    fn bounds_frames() {
+        let sum = |frame| window(&format!("sum(x) OVER (ORDER BY x {})", frame));
+        assert_eq!(sum("ROWS BETWEEN 1 FOLLOWING AND 2 FOLLOWING"), ["|3", "1|4", "2|5", "2|3", "3|"]);
+        assert_eq!(sum("ROWS BETWEEN 2 PRECEDING AND 1 PRECEDING"), ["|", "1|", "2|1", "2|3", "3|4"]);
+        // RANGE and GROUPS frames start and end at peers, NULLs being peers of each other
+        assert_eq!(sum("RANGE BETWEEN CURRENT ROW AND UNBOUNDED FOLLOWING"), ["|8", "1|8", "2|7", "2|7", "3|3"]);
+        assert_eq!(sum("RANGE BETWEEN 0.5 PRECEDING AND 0.5 FOLLOWING"), ["|", "1|1", "2|4", "2|4", "3|3"]);
+        assert_eq!(sum("GROUPS BETWEEN 1 PRECEDING AND CURRENT ROW"), ["|", "1|1", "2|5", "2|5", "3|7"]);
+        assert_eq!(sum("GROUPS BETWEEN 1 FOLLOWING AND UNBOUNDED FOLLOWING"), ["|8", "1|7", "2|3", "2|3", "3|"]);
+        // the default frame ends at the last peer, without ORDER BY every row is one
+        assert_eq!(sum(""), ["|", "1|1", "2|5", "2|5", "3|8"]);
+        assert_eq!(window("sum(x) OVER ()"), ["|8", "1|8", "2|8", "2|8", "3|8"]);
+        assert_eq!(
+            window("count(*) OVER (ORDER BY x RANGE BETWEEN 1 PRECEDING AND 1 FOLLOWING)"),
+            ["|1", "1|3", "2|4", "2|4", "3|3"]
+        );
+        // descending, PRECEDING means larger values
+        assert_eq!(
+            window("count(*) OVER (ORDER BY x DESC RANGE BETWEEN 1 PRECEDING AND CURRENT ROW)"),
+            ["|1", "1|3", "2|3", "2|3", "3|1"]
+        );
+
+        let error = |frame: &str| {
+            query(&format!("SELECT sum(id) OVER ({}) FROM apples", frame)).unwrap_err().to_string()
+        };
+        assert_eq!(error("ORDER BY id ROWS -1 PRECEDING"), "frame starting offset must be a non-negative integer");
+        assert_eq!(error("ORDER BY id GROUPS 1.5 PRECEDING"), "frame starting offset must be a non-negative integer");
+        assert_eq!(error("ORDER BY id ROWS BETWEEN 1 FOLLOWING AND CURRENT ROW"), "unsupported frame specification");
+        assert_eq!(error("ORDER BY id ROWS BETWEEN 1 FOLLOWING AND 1 PRECEDING"), "unsupported frame specification");
+        assert_eq!(
+            error("ORDER BY id, name RANGE 1 PRECEDING"),
+            "RANGE with offset PRECEDING/FOLLOWING requires one ORDER BY expression"
+        );
+        assert_eq!(error("RANGE 1 PRECEDING"), "RANGE with offset PRECEDING/FOLLOWING requires one ORDER BY expression");
+        assert!(query("SELECT sum(id) OVER (ROWS BETWEEN UNBOUNDED FOLLOWING AND CURRENT ROW) FROM apples").is_err());
+    }
+
+    #[test]
+    fn ranks_and_offsets_rows() {
+        let sql = |select: &str| {
+            format!(
+                "WITH t(day, amount) AS (SELECT 1, 10 UNION ALL SELECT 2, 20 UNION ALL SELECT 2, 5 \
+                 UNION ALL SELECT 3, 7 UNION ALL SELECT 4, 30) {}",
+                select
+            )
+        };
+        let column = |select: &str| strings(&sql(select));
+        assert_eq!(column("SELECT rank() OVER (ORDER BY day) FROM t"), ["1", "2", "2", "4", "5"]);
+        assert_eq!(column("SELECT sum(amount) OVER (ORDER BY day) FROM t"), ["10", "35", "35", "42", "72"]);
+        let moving = "SELECT sum(amount) OVER (ORDER BY day, amount ROWS BETWEEN 1 PRECEDING AND CURRENT ROW) FROM t";
+        assert_eq!(column(moving), ["10", "15", "25", "27", "37"]);
+        assert_eq!(column("SELECT count(*) OVER (ORDER BY day RANGE 1 PRECEDING) FROM t"), ["1", "3", "3", "3", "2"]);
+        let lag = "SELECT lag(amount, 1, 0) OVER w FROM t WINDOW w AS (ORDER BY amount)";
+        assert_eq!(column(lag), ["0", "5", "7", "10", "20"]);
+        assert_eq!(column("SELECT ntile(2) OVER (ORDER BY amount) FROM t"), ["1", "1", "1", "2", "2"]);
+        let misuse = query(&sql("SELECT day FROM t WHERE row_number() OVER () > 1")).unwrap_err();
+        assert_eq!(misuse.to_string(), "misuse of window function row_number()");
+    }
+}
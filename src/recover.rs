@@ -0,0 +1,839 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+
+use crate::error::Result;
+
+use crate::btree::{btree_cells, btree_pages};
+use crate::cell::{local_payload_size, read_overflow, Content, ContentVariant, Record};
+use crate::db::DB;
+use crate::freelist::read_freelist;
+use crate::page::{Page, PageType};
+use crate::pager::PageBuf;
+use crate::schema::{read_schema, SchemaEntry, Table};
+use crate::utils::VarInt;
+use crate::value::{Affinity, Value};
+
+/// Factor for each column missing from the end of a record, which only
+/// happens for rows written before an `ALTER TABLE ADD COLUMN`.
+const MISSING_COLUMN: f64 = 0.6;
+/// Factor for records whose header was overwritten by a freeblock header and had to be guessed.
+const GUESSED_HEADER: f64 = 0.8;
+/// Factor for records without a cell header in front that confirms their size.
+const NO_CELL_HEADER: f64 = 0.9;
+
+/// Where a recovered record was carved from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// A freeblock of a table leaf page, the space left by deleted cells.
+    FreeBlock,
+    /// The gap between the cell pointer array and the cell content area.
+    Unallocated,
+    /// A page on the freelist.
+    FreePage,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::FreeBlock => write!(f, "freeblock"),
+            Source::Unallocated => write!(f, "unallocated"),
+            Source::FreePage => write!(f, "freelist"),
+        }
+    }
+}
+
+/// A deleted row carved out of unused space.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveredRecord {
+    pub table: String,
+    /// Known when the cell header in front of the record survived.
+    pub rowid: Option<i64>,
+    /// Names of `values`: the table's columns, fewer for rows written before
+    /// an `ALTER TABLE ADD COLUMN`, led by `rowid` when it is known and no
+    /// column aliases it.
+    pub columns: Vec<String>,
+    pub values: Vec<Value>,
+    pub page: u32,
+    pub offset: usize,
+    pub source: Source,
+    /// How well the record matches the table's schema, from 0 to 1.
+    pub confidence: f64,
+}
+
+/// Carves records out of the freeblocks and unallocated space of table
+/// b-tree pages and out of freelist pages, and keeps those matching the
+/// schema of a table with at least `min_confidence`.
+///
+/// Records on a table's own pages are only matched against that table, ones
+/// on free pages against every table, including `sqlite_schema` and tables
+/// dropped since whose schema rows were recovered, and the overflow pages of
+/// cells on freed leaves are followed. Carved records equal to a live row are
+/// stale copies and left out, as are all but one copy of a deleted row.
+pub fn recover(db: &mut DB, min_confidence: f64) -> Result<Vec<RecoveredRecord>> {
+    let schema = read_schema(db)?;
+    let mut tables = vec![Table::sqlite_schema()];
+    tables.extend(
+        schema
+            .iter()
+            .filter(|e| e.kind == "table" && e.rootpage != 0)
+            .filter_map(|e| Table::from_entry(e).ok()),
+    );
+
+    let mut carver = Carver {
+        tables: vec![],
+        usable_size: db.header.usable_size() as usize,
+        min_confidence,
+        found: vec![],
+        seen: HashMap::new(),
+    };
+    let mut owners = HashMap::new();
+    for table in tables {
+        // only rowid tables, a WITHOUT ROWID table is an index b-tree
+        let Ok(pages) = btree_pages(db, table.rootpage as u64) else {
+            continue;
+        };
+        let rowid_table = Page::new(db, table.rootpage as u64)
+            .is_ok_and(|p| matches!(p.page_type, PageType::InteriorTable | PageType::LeafTable));
+        if !rowid_table {
+            continue;
+        }
+        let live = live_rows(db, &table);
+        let index = carver.tables.len();
+        owners.extend(pages.into_iter().map(|page| (page, index)));
+        carver.tables.push((table, live));
+    }
+
+    // sqlite_schema comes first, its deleted rows describe dropped tables
+    // whose pages are now on the freelist
+    let mut owned: Vec<_> = owners.into_iter().map(|(page, owner)| (owner, page)).collect();
+    owned.sort_unstable();
+    let schema_pages = owned.iter().take_while(|(owner, _)| carver.tables[*owner].0.rootpage == 1).count();
+    for (i, &(owner, page_no)) in owned.iter().enumerate() {
+        if i == schema_pages {
+            carver.add_dropped_tables();
+        }
+        let Ok(page) = Page::new(db, page_no) else {
+            continue;
+        };
+        carver.carve_page(&page, &[owner], Source::FreeBlock, Source::Unallocated);
+    }
+    if schema_pages == owned.len() {
+        carver.add_dropped_tables();
+    }
+
+    let all: Vec<usize> = (0..carver.tables.len()).collect();
+    let freelist = read_freelist(db).unwrap_or_default();
+    for trunk in &freelist.trunks {
+        let Ok(raw) = db.read_page(trunk.page as u64) else {
+            continue;
+        };
+        let start = 8 + 4 * trunk.leaves.len();
+        carver.carve(&raw, trunk.page, start..carver.usable_size.min(raw.len()), &all, Source::FreePage);
+        for &leaf in &trunk.leaves {
+            let Ok(raw) = db.read_page(leaf as u64) else {
+                continue;
+            };
+            match Page::from_raw(raw.clone(), leaf as u64, carver.usable_size as u32) {
+                Ok(page) => match page.page_type {
+                    PageType::LeafTable => {
+                        carver.carve_cells(db, &page, &all);
+                        carver.carve_page(&page, &all, Source::FreePage, Source::FreePage);
+                    }
+                    PageType::InteriorTable => carver.carve_page(&page, &all, Source::FreePage, Source::FreePage),
+                    // index entries aren't rows
+                    PageType::InteriorIndex | PageType::LeafIndex => {}
+                },
+                Err(_) => carver.carve(&raw, leaf, 0..carver.usable_size.min(raw.len()), &all, Source::FreePage),
+            }
+        }
+    }
+
+    Ok(carver.found)
+}
+
+/// The rows of a table as `row_key`s, to tell deleted rows from stale copies of live ones.
+fn live_rows(db: &mut DB, table: &Table) -> HashSet<String> {
+    let Ok(cells) = btree_cells(db, table.rootpage as u64, ContentVariant::RowCell) else {
+        return HashSet::new();
+    };
+    cells
+        .iter()
+        .filter_map(|cell| match &cell.content {
+            Content::RowCell(record) => Some(row_key(table, &record.row.iter().map(Value::from).collect::<Vec<_>>())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Identifies a row by its stored fields, with rowid aliases left out as
+/// they are only known for some carved records.
+fn row_key(table: &Table, values: &[Value]) -> String {
+    let fields: Vec<&Value> = values
+        .iter()
+        .zip(&table.columns)
+        .filter(|(_, column)| !column.rowid_alias)
+        .map(|(value, _)| value)
+        .collect();
+    format!("{:?}", fields)
+}
+
+/// A record header and body parsed out of raw bytes.
+struct Carved {
+    types: Vec<u64>,
+    values: Vec<Value>,
+    /// Bytes from the carving offset to the end of the body.
+    len: usize,
+    /// Whether the header size and maybe the first serial type were guessed.
+    guessed: bool,
+}
+
+/// Size of the body of a field with serial type `t`, `None` for the reserved types.
+fn serial_size(t: u64) -> Option<usize> {
+    Some(match t {
+        0 | 8 | 9 => 0,
+        1..=4 => t as usize,
+        5 => 6,
+        6 | 7 => 8,
+        10 | 11 => return None,
+        t => ((t - 12) / 2) as usize,
+    })
+}
+
+/// Decodes the fields of `types` from the body starting at `start`, which
+/// has to end within `bytes`. Text has to be valid UTF-8.
+fn body(bytes: &PageBuf, start: usize, types: &[u64]) -> Option<(Vec<Value>, usize)> {
+    let mut values = Vec::with_capacity(types.len());
+    let mut ind = start;
+    for &t in types {
+        let size = serial_size(t)?;
+        let field = bytes.get(ind..ind.checked_add(size)?)?;
+        if t >= 13 && t % 2 == 1 && std::str::from_utf8(field).is_err() {
+            return None;
+        }
+        let record = Record::new(bytes, ind, &VarInt { val: t, len: 0 }).ok()?;
+        values.push(Value::from(&record));
+        ind += size;
+    }
+    Some((values, ind - start))
+}
+
+/// Parses a record with an intact header at `start`.
+fn carve_intact(bytes: &PageBuf, start: usize) -> Option<Carved> {
+    let header_size = VarInt::from_mem(bytes.get(start..)?).ok()?;
+    let header_end = start.checked_add(header_size.val as usize)?;
+    if header_size.val as usize <= header_size.len || header_end > bytes.len() {
+        return None;
+    }
+    let mut types = vec![];
+    let mut ind = start + header_size.len;
+    while ind < header_end {
+        let t = VarInt::from_mem(&bytes[ind..header_end]).ok()?;
+        types.push(t.val);
+        ind += t.len;
+    }
+    let (values, body_len) = body(bytes, header_end, &types)?;
+    Some(Carved {
+        types,
+        values,
+        len: header_end - start + body_len,
+        guessed: false,
+    })
+}
+
+/// Parses a record whose header size was overwritten: `count` serial types start at `start`.
+fn carve_headless(bytes: &PageBuf, start: usize, count: usize) -> Option<Carved> {
+    let mut types = vec![];
+    let mut ind = start;
+    for _ in 0..count {
+        let t = VarInt::from_mem(bytes.get(ind..)?).ok()?;
+        types.push(t.val);
+        ind += t.len;
+    }
+    let (values, body_len) = body(bytes, ind, &types)?;
+    Some(Carved {
+        types,
+        values,
+        len: ind - start + body_len,
+        guessed: true,
+    })
+}
+
+/// Parses a record whose header size and first serial type were overwritten:
+/// `count` more serial types follow at `start` and the record ends at `end`,
+/// which leaves the size of the first field and so the types it can have.
+/// Of a first type taking two bytes only the first byte was lost.
+fn carve_first_lost(bytes: &PageBuf, start: usize, end: usize, count: usize) -> Vec<Carved> {
+    let mut found = vec![];
+    'kept: for kept in 0..=1 {
+        let mut types = vec![0];
+        let mut ind = start + kept;
+        for _ in 0..count {
+            let Some(t) = bytes.get(ind..).and_then(|b| VarInt::from_mem(b).ok()) else {
+                continue 'kept;
+            };
+            types.push(t.val);
+            ind += t.len;
+        }
+        let Some(rest) = types[1..].iter().map(|&t| serial_size(t)).sum::<Option<usize>>() else {
+            continue;
+        };
+        let Some(size) = end.checked_sub(ind + rest) else {
+            continue;
+        };
+        for first in lost_types(size) {
+            // without a serial type left to back it up any bytes would do as a blob or number
+            if count == 0 && !(first >= 13 && first % 2 == 1) {
+                continue;
+            }
+            let fits = match kept {
+                0 => first < 0x80,
+                _ => (0x80..0x4000).contains(&first) && bytes.get(start) == Some(&((first & 0x7f) as u8)),
+            };
+            if !fits {
+                continue;
+            }
+            types[0] = first;
+            if let Some((values, body_len)) = body(bytes, ind, &types) {
+                found.push(Carved {
+                    types: types.clone(),
+                    values,
+                    len: ind - start + body_len,
+                    guessed: true,
+                });
+            }
+        }
+    }
+    found
+}
+
+/// The serial types of a field taking `size` bytes.
+fn lost_types(size: usize) -> Vec<u64> {
+    let mut types = match size {
+        // 0 and 1 have no body either, but a lost field is more likely NULL
+        0 => return vec![0],
+        1..=4 => vec![size as u64],
+        6 => vec![5],
+        8 => vec![6, 7],
+        _ => vec![],
+    };
+    types.extend([12 + 2 * size as u64, 13 + 2 * size as u64]);
+    types
+}
+
+/// Where the record of a cell freed into a freeblock ending at `block_end`
+/// can end, its body starting before `start`: at the end of the freeblock or
+/// at the next cell merged into it. A cell freed after it is left intact, one
+/// freed before it keeps its own freeblock header.
+fn record_ends(bytes: &PageBuf, start: usize, block_end: usize, usable_size: usize) -> Vec<usize> {
+    let intact_cell = |at: usize| {
+        let Some(payload) = bytes.get(at..).and_then(|b| VarInt::from_mem(b).ok()) else {
+            return false;
+        };
+        let Some(rowid) = bytes.get(at + payload.len..).and_then(|b| VarInt::from_mem(b).ok()) else {
+            return false;
+        };
+        payload.val > 0
+            && carve_intact(bytes, at + payload.len + rowid.len).is_some_and(|c| c.len == payload.val as usize)
+    };
+    let mut ends = vec![block_end];
+    ends.extend(
+        (start..block_end).filter(|&at| freeblock_size(bytes, at + 4, usable_size).is_some() || intact_cell(at)),
+    );
+    ends
+}
+
+/// Looks for the payload size and rowid varints of a table leaf cell right
+/// in front of a record of `len` bytes at `start`, returning the rowid.
+fn cell_header(bytes: &[u8], lower: usize, start: usize, len: usize) -> Option<i64> {
+    for rowid_len in 1..=9 {
+        for payload_len in 1..=3 {
+            let Some(cell_start) = start.checked_sub(rowid_len + payload_len).filter(|&s| s >= lower) else {
+                continue;
+            };
+            let payload = VarInt::from_mem(&bytes[cell_start..]).ok()?;
+            if payload.len != payload_len || payload.val as usize != len {
+                continue;
+            }
+            let rowid = VarInt::from_mem(&bytes[cell_start + payload_len..]).ok()?;
+            if rowid.len == rowid_len {
+                return Some(rowid.val as i64);
+            }
+        }
+    }
+    None
+}
+
+/// The size of the freeblock whose header could be the 4 bytes before
+/// `offset`, as sqlite writes one over the start of every cell it frees.
+/// Every freeblock of the chain from there has to lie after the page header,
+/// fit on the page and start after the end of the one before.
+fn freeblock_size(raw: &[u8], offset: usize, usable_size: usize) -> Option<usize> {
+    let mut start = offset.checked_sub(4).filter(|&start| start >= 8)?;
+    let mut first = None;
+    loop {
+        let header = raw.get(start..start + 4)?;
+        let next = u16::from_be_bytes([header[0], header[1]]) as usize;
+        let size = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let end = start + size;
+        if size < 4 || end > usable_size || (next != 0 && next < end) {
+            return None;
+        }
+        first.get_or_insert(size);
+        if next == 0 {
+            return first;
+        }
+        start = next;
+    }
+}
+
+/// How likely a field of serial type `t` is in a column of `affinity`.
+fn field_weight(affinity: Affinity, t: u64, value: &Value) -> f64 {
+    let weight = match (t, affinity) {
+        (0, _) => 0.95,
+        (_, Affinity::Blob) => 1.0,
+        (1..=6 | 8 | 9, Affinity::Integer | Affinity::Numeric) => 1.0,
+        (7, Affinity::Integer | Affinity::Numeric) => 0.8,
+        (7, Affinity::Real) => 1.0,
+        // integral reals are stored as integers
+        (1..=6 | 8 | 9, Affinity::Real) => 0.9,
+        (_, Affinity::Text) if t >= 13 && t % 2 == 1 => 1.0,
+        (_, Affinity::Text) if t >= 12 => 0.4,
+        // numbers in a TEXT column are converted to text when stored
+        (_, Affinity::Text) => 0.1,
+        (_, _) if t % 2 == 1 => 0.4,
+        (_, _) => 0.3,
+    };
+    match value {
+        Value::Text(s) if s.chars().any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r')) => weight * 0.3,
+        _ => weight,
+    }
+}
+
+/// How plausible it is that a carved record is a row of `table`, `None` if it can't be.
+fn score(table: &Table, carved: &Carved, has_cell_header: bool) -> Option<f64> {
+    let (fields, columns) = (carved.types.len(), table.columns.len());
+    if fields == 0 || fields > columns {
+        return None;
+    }
+    // zeroed space parses as NULLs and zeros
+    let zero = |value: &Value| match value {
+        Value::Null | Value::Integer(0) => true,
+        Value::Real(r) => *r == 0.0,
+        Value::Blob(b) => b.iter().all(|&b| b == 0),
+        _ => false,
+    };
+    if carved.values.iter().all(zero) {
+        return None;
+    }
+    // and fields without a body, like the constant 1, could be anything
+    // unless a cell header confirms the record's size
+    if !has_cell_header && carved.types.iter().all(|&t| serial_size(t) == Some(0)) {
+        return None;
+    }
+    let mut confidence = MISSING_COLUMN.powi((columns - fields) as i32);
+    for ((column, &t), value) in table.columns.iter().zip(&carved.types).zip(&carved.values) {
+        // a rowid alias is always stored as NULL
+        if column.rowid_alias && t != 0 {
+            return None;
+        }
+        if !column.rowid_alias {
+            confidence *= field_weight(column.affinity, t, value);
+        }
+    }
+    if carved.guessed {
+        confidence *= GUESSED_HEADER;
+    }
+    if !has_cell_header {
+        confidence *= NO_CELL_HEADER;
+    }
+    Some(confidence)
+}
+
+/// A carved record with the table it matches best, its confidence and rowid.
+type Candidate = (usize, f64, Carved, Option<i64>);
+
+struct Carver {
+    /// Tables to match records against, with their live rows.
+    tables: Vec<(Table, HashSet<String>)>,
+    usable_size: usize,
+    min_confidence: f64,
+    found: Vec<RecoveredRecord>,
+    /// Indexes into `found` by table and `row_key`, as a row can be carved
+    /// from several places: a cell copied to another page by a rebalance
+    /// leaves its old copy behind.
+    seen: HashMap<(usize, String), Vec<usize>>,
+}
+
+impl Carver {
+    /// Adds the tables of recovered `sqlite_schema` rows that no longer exist,
+    /// to match the rows left on their freed pages against.
+    fn add_dropped_tables(&mut self) {
+        let dropped: Vec<Table> = self
+            .found
+            .iter()
+            .filter(|r| r.table == "sqlite_schema")
+            .filter_map(|r| match &r.values[r.values.len().saturating_sub(5)..] {
+                [Value::Text(kind), Value::Text(name), Value::Text(tbl_name), Value::Integer(rootpage), Value::Text(sql)]
+                    if kind == "table" =>
+                {
+                    Table::from_entry(&SchemaEntry {
+                        kind: kind.clone(),
+                        name: name.clone(),
+                        tbl_name: tbl_name.clone(),
+                        rootpage: *rootpage as u32,
+                        sql: Some(sql.clone()),
+                    })
+                    .ok()
+                }
+                _ => None,
+            })
+            .collect();
+        for table in dropped {
+            if !self.tables.iter().any(|(t, _)| t.name.eq_ignore_ascii_case(&table.name)) {
+                self.tables.push((table, HashSet::new()));
+            }
+        }
+    }
+
+    /// Carves the freeblocks and the unallocated space of a table b-tree page.
+    fn carve_page(&mut self, page: &Page, tables: &[usize], freeblock: Source, unallocated: Source) {
+        let usable = self.usable_size.min(page.raw.len());
+        let header_start = if page.number == 1 { 100 } else { 0 };
+        let array_end = header_start + page.header_offset + 2 * page.cell_count as usize;
+        let content_start = (page.cell_start as usize).min(usable);
+        let page_no = page.number as u32;
+        if array_end < content_start {
+            self.carve(&page.raw, page_no, array_end..content_start, tables, unallocated);
+        }
+        for block in &page.free_blocks {
+            // the first 4 bytes now hold the freeblock header
+            let start = block.offset + 4;
+            let end = (block.offset + block.size).min(usable);
+            if start < end {
+                self.carve(&page.raw, page_no, start..end, tables, freeblock);
+            }
+        }
+    }
+
+    /// The cells of a freed table leaf page, which still has its header.
+    /// Overflow pages freed along with it are followed.
+    fn carve_cells(&mut self, db: &mut DB, page: &Page, tables: &[usize]) {
+        let usable = self.usable_size.min(page.raw.len());
+        for &cell_start in &page.cell_ptrs {
+            let bytes = page.raw.slice(0..usable);
+            let Ok(payload) = VarInt::from_mem(&bytes[cell_start..]) else {
+                continue;
+            };
+            let Ok(rowid) = VarInt::from_mem(bytes.get(cell_start + payload.len..).unwrap_or_default()) else {
+                continue;
+            };
+            let start = cell_start + payload.len + rowid.len;
+            let payload_size = payload.val as usize;
+            let local = local_payload_size(self.usable_size, payload_size, &page.page_type);
+            let carved = if local < payload_size {
+                let Some(ptr) = bytes.get(start + local..start + local + 4) else {
+                    continue;
+                };
+                let mut record = bytes[start..start + local].to_vec();
+                // they may have been reused since, which leaves a record that doesn't parse
+                let first = u32::from_be_bytes([ptr[0], ptr[1], ptr[2], ptr[3]]);
+                if read_overflow(db, first, payload_size, &mut record).is_err() {
+                    continue;
+                }
+                carve_intact(&PageBuf::from(record), 0)
+            } else {
+                carve_intact(&bytes.slice(0..usable.min(start + payload_size)), start)
+            };
+            let Some(carved) = carved else {
+                continue;
+            };
+            if let Some((table, confidence)) = self.best_match(tables, &carved, true) {
+                self.push(table, carved, Some(rowid.val as i64), page.number as u32, start, Source::FreePage, confidence);
+            }
+        }
+    }
+
+    /// Scans `range` of a page for records, skipping over every record found.
+    /// A record starting inside a found one replaces it if it scores higher,
+    /// as parsing from a few bytes too early can give plausible garbage.
+    fn carve(&mut self, raw: &PageBuf, page: u32, range: Range<usize>, tables: &[usize], source: Source) {
+        let mut offset = range.start;
+        while offset < range.end {
+            let Some(mut best) = self.candidate(raw, offset, &range, tables) else {
+                offset += 1;
+                continue;
+            };
+            let mut next = offset + 1;
+            while next < offset + best.2.len {
+                match self.candidate(raw, next, &range, tables) {
+                    Some(other) if other.1 > best.1 => {
+                        (offset, best) = (next, other);
+                    }
+                    _ => {}
+                }
+                next += 1;
+            }
+            let (table, confidence, carved, rowid) = best;
+            let len = carved.len;
+            self.push(table, carved, rowid, page, offset, source, confidence);
+            offset += len;
+        }
+    }
+
+    /// The best scoring record starting at `offset`: with an intact header, or
+    /// with one overwritten by the freeblock header in front of it.
+    fn candidate(&self, raw: &PageBuf, offset: usize, range: &Range<usize>, tables: &[usize]) -> Option<Candidate> {
+        let bytes = &raw.slice(0..range.end);
+        let mut best: Option<Candidate> = None;
+        let mut consider = |carved: Option<Carved>, rowid: Option<i64>| {
+            let Some(carved) = carved else {
+                return;
+            };
+            if let Some((table, confidence)) = self.best_match(tables, &carved, rowid.is_some()) {
+                if best.as_ref().map_or(true, |b| confidence > b.1) {
+                    best = Some((table, confidence, carved, rowid));
+                }
+            }
+        };
+
+        let intact = carve_intact(bytes, offset);
+        let rowid = intact.as_ref().and_then(|c| cell_header(bytes, range.start, offset, c.len));
+        consider(intact, rowid);
+        if let Some(size) = freeblock_size(raw, offset, self.usable_size) {
+            // the record has to lie within the freeblock
+            let fits = |carved: Option<Carved>| carved.filter(|c| c.len <= size - 4);
+            let block_end = offset - 4 + size;
+            let ends = record_ends(bytes, offset + 1, block_end, self.usable_size);
+            for &t in tables {
+                let count = self.tables[t].0.columns.len();
+                // a payload size or rowid of two bytes leaves the serial types intact
+                consider(fits(carve_headless(bytes, offset, count)), None);
+                for &end in &ends {
+                    for carved in carve_first_lost(bytes, offset, end, count.saturating_sub(1)) {
+                        consider(Some(carved), None);
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    /// The table among `tables` a record fits best and its confidence, if above the minimum.
+    fn best_match(&self, tables: &[usize], carved: &Carved, has_cell_header: bool) -> Option<(usize, f64)> {
+        tables
+            .iter()
+            .filter_map(|&t| {
+                Some((t, score(&self.tables[t].0, carved, has_cell_header)?))
+            })
+            .filter(|&(_, confidence)| confidence >= self.min_confidence)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Adds a carved record unless it is a live row or was found before.
+    /// Of two copies of a row the one whose rowid is known is kept.
+    #[allow(clippy::too_many_arguments)]
+    fn push(
+        &mut self,
+        table: usize,
+        carved: Carved,
+        rowid: Option<i64>,
+        page: u32,
+        offset: usize,
+        source: Source,
+        confidence: f64,
+    ) {
+        let (t, live) = &self.tables[table];
+        let key = row_key(t, &carved.values);
+        if live.contains(&key) {
+            return;
+        }
+        let copies = self.seen.entry((table, key)).or_default();
+        // with different rowids they are different rows with the same values
+        let copy = copies.iter().copied().find(|&i| {
+            let other = self.found[i].rowid;
+            rowid.is_none() || other.is_none() || other == rowid
+        });
+        if copy.is_some_and(|i| self.found[i].rowid.is_some() || rowid.is_none()) {
+            return;
+        }
+
+        let mut columns = vec![];
+        let mut values = vec![];
+        let fields = &t.columns[..carved.values.len()];
+        if let (Some(rowid), false) = (rowid, fields.iter().any(|c| c.rowid_alias)) {
+            columns.push("rowid".to_string());
+            values.push(Value::Integer(rowid));
+        }
+        for (value, column) in carved.values.into_iter().zip(fields) {
+            columns.push(column.name.clone());
+            values.push(match (column.rowid_alias, rowid) {
+                (true, Some(rowid)) => Value::Integer(rowid),
+                _ => value,
+            });
+        }
+        let record = RecoveredRecord {
+            table: t.name.clone(),
+            rowid,
+            columns,
+            values,
+            page,
+            offset,
+            source,
+            confidence,
+        };
+        match copy {
+            Some(i) => self.found[i] = record,
+            None => {
+                copies.push(self.found.len());
+                self.found.push(record);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Written by sqlite with `secure_delete` off and a page size of 512:
+    /// `scratch(x)` with one row, `log(msg TEXT, at REAL)` with four rows, the
+    /// third of 1200 bytes spilling onto two overflow pages, and
+    /// `fruit(id INTEGER PRIMARY KEY, name TEXT, qty INTEGER)` with four rows
+    /// of which `banana` and the last one, `date`, were deleted. Then
+    /// `scratch` and `log` were dropped.
+    fn dropped() -> DB {
+        DB::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/dropped.db")).unwrap()
+    }
+
+    /// `t(id INTEGER PRIMARY KEY, name TEXT, n INTEGER)` with the 60 rows
+    /// `(i, 'name number ' || i, i * 7)` over four leaves, written by sqlite
+    /// with a page size of 512, and every fifth row deleted.
+    fn deleted() -> DB {
+        DB::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/deleted.db")).unwrap()
+    }
+
+    fn text(s: &str) -> Value {
+        Value::Text(s.to_string())
+    }
+
+    fn rows<'a>(found: &'a [RecoveredRecord], table: &str) -> Vec<&'a RecoveredRecord> {
+        found.iter().filter(|r| r.table == table).collect()
+    }
+
+    #[test]
+    fn recovers_dropped_tables() {
+        let found = recover(&mut dropped(), 0.6).unwrap();
+        let schema: Vec<_> = rows(&found, "sqlite_schema").iter().map(|r| r.values.clone()).collect();
+        assert_eq!(
+            schema,
+            [
+                vec![text("table"), text("log"), text("log"), Value::Integer(3), text("CREATE TABLE log(msg TEXT, at REAL)")],
+                vec![text("table"), text("scratch"), text("scratch"), Value::Integer(2), text("CREATE TABLE scratch(x)")],
+            ]
+        );
+        // the freeblock headers took the schema rows' header sizes and first serial types
+        assert!(rows(&found, "sqlite_schema").iter().all(|r| r.source == Source::FreeBlock && r.confidence < 1.0));
+
+        let log = rows(&found, "log");
+        assert_eq!(log.iter().map(|r| r.rowid).collect::<Vec<_>>(), [Some(1), Some(2), Some(3), Some(4)]);
+        assert!(log.iter().all(|r| r.source == Source::FreePage && r.columns == ["rowid", "msg", "at"]));
+        assert_eq!(log[0].values, [Value::Integer(1), text("started"), Value::Real(1.5)]);
+        assert_eq!(log[3].values, [Value::Integer(4), text("stopped"), Value::Real(4.5)]);
+
+        let scratch = rows(&found, "scratch");
+        assert_eq!(scratch.len(), 1);
+        assert_eq!(scratch[0].values, [Value::Integer(1), Value::Integer(1)]);
+    }
+
+    #[test]
+    fn follows_overflow_pages_of_freed_cells() {
+        let found = recover(&mut dropped(), 0.6).unwrap();
+        let log = rows(&found, "log");
+        assert_eq!(log[2].values[1], text(&"overflowing ".repeat(100)));
+        assert_eq!(log[2].values[2], Value::Real(3.75));
+    }
+
+    #[test]
+    fn recovers_unallocated_space_and_freeblocks() {
+        let found = recover(&mut dropped(), 0.6).unwrap();
+        let fruit = rows(&found, "fruit");
+        assert_eq!(fruit.len(), 2);
+        // the last row inserted was at the start of the cell content area, which just moved past it
+        assert_eq!((fruit[0].source, fruit[0].rowid, fruit[0].confidence), (Source::Unallocated, Some(4), 1.0));
+        assert_eq!(fruit[0].values, [Value::Integer(4), text("date"), Value::Integer(7)]);
+        // the freeblock header took the cell header, rowid included
+        assert_eq!((fruit[1].source, fruit[1].rowid), (Source::FreeBlock, None));
+        assert_eq!(fruit[1].values, [Value::Null, text("banana"), Value::Integer(12)]);
+    }
+
+    #[test]
+    fn keeps_one_copy_of_moved_rows() {
+        let found = recover(&mut deleted(), 0.6).unwrap();
+        let mut names: Vec<_> = found.iter().map(|r| r.values[1].to_string()).collect();
+        names.sort();
+        let mut expected: Vec<_> = (1..=12).map(|i| format!("name number {}", i * 5)).collect();
+        expected.sort();
+        assert_eq!(names, expected);
+        // the rows split off page 2 left their old copies in its unallocated space, with cell headers
+        let twenty = found.iter().find(|r| r.values[1] == text("name number 20")).unwrap();
+        assert_eq!((twenty.page, twenty.source, twenty.rowid), (2, Source::Unallocated, Some(20)));
+    }
+
+    #[test]
+    fn cuts_off_at_min_confidence() {
+        let guessed = GUESSED_HEADER * NO_CELL_HEADER;
+        let all = recover(&mut dropped(), guessed).unwrap();
+        let sure = recover(&mut dropped(), guessed + 0.01).unwrap();
+        assert_eq!(all.len(), 9);
+        // without the schema rows of the dropped tables their rows are left unmatched too
+        assert_eq!(sure.len(), 1);
+        assert_eq!(sure[0].values[1], text("date"));
+    }
+
+    #[test]
+    fn leaves_out_zeroed_space() {
+        let mut raw = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/dropped.db")).unwrap();
+        // zero the freed pages like secure_delete, the trunk page 2 keeps its 3 leaves
+        raw[512 + 20..6 * 512 - 512].fill(0);
+        let mut db = DB::from_storage(Box::new(crate::storage::MemoryStorage::from(raw))).unwrap();
+        let found = recover(&mut db, 0.0).unwrap();
+        assert!(found.iter().all(|r| r.source != Source::FreePage), "{:?}", found);
+
+        let zeros = Carved {
+            types: vec![1, 6, 7, 0],
+            values: vec![Value::Integer(0), Value::Integer(0), Value::Real(0.0), Value::Null],
+            len: 22,
+            guessed: false,
+        };
+        assert_eq!(score(&Table::sqlite_schema(), &zeros, true), None);
+    }
+
+    #[test]
+    fn rebuilds_lost_serial_types() {
+        // a cell of rowid 1 with 60 bytes of text and the integer 7: the
+        // freeblock header took the payload size, rowid, header size and the
+        // first byte of the text's two byte serial type
+        let name = "x".repeat(60);
+        let text_type = 13 + 2 * 60;
+        let mut cell = vec![0, 0, 0, 69, (text_type & 0x7f) as u8, 1];
+        cell.extend(name.as_bytes());
+        cell.push(7);
+        let bytes = PageBuf::from(cell);
+        let table = Table::from_entry(&SchemaEntry {
+            kind: "table".to_string(),
+            name: "t".to_string(),
+            tbl_name: "t".to_string(),
+            rootpage: 2,
+            sql: Some("CREATE TABLE t(name TEXT, n INTEGER)".to_string()),
+        })
+        .unwrap();
+        // read as one byte the serial type leaves a control character in the text
+        let best = carve_first_lost(&bytes, 4, 4 + 63, 1)
+            .into_iter()
+            .max_by(|a, b| score(&table, a, false).unwrap_or(0.0).total_cmp(&score(&table, b, false).unwrap_or(0.0)))
+            .unwrap();
+        assert_eq!(best.types, [text_type, 1]);
+        assert_eq!(best.values, [text(&name), Value::Integer(7)]);
+    }
+}
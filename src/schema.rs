@@ -0,0 +1,184 @@
+use crate::error::{DbError, Result};
+
+use crate::btree::btree_cells;
+use crate::cell::{Content, ContentVariant, Record};
+use crate::db::DB;
+use crate::sql::ast::Expr;
+use crate::sql::Parser;
+use crate::value::{Affinity, Collation};
+
+/// One row of `sqlite_schema`.
+#[derive(Debug, Clone)]
+pub struct SchemaEntry {
+    pub kind: String,
+    pub name: String,
+    pub tbl_name: String,
+    pub rootpage: u32,
+    pub sql: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub name: String,
+    #[allow(dead_code)]
+    pub type_name: String,
+    pub affinity: Affinity,
+    /// `INTEGER PRIMARY KEY` columns are stored as the rowid, the record holds NULL.
+    pub rowid_alias: bool,
+    pub collation: Option<String>,
+    /// The DEFAULT clause, which also fills in fields missing from records
+    /// written before the column was added by ALTER TABLE.
+    pub default: Option<Expr>,
+}
+
+/// A column of the PRIMARY KEY, in key order.
+#[derive(Debug, Clone)]
+pub struct KeyColumn {
+    /// Position in `Table::columns`.
+    pub column: usize,
+    /// `None` for a collation sqlite doesn't have built in.
+    pub collation: Option<Collation>,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub name: String,
+    pub rootpage: u32,
+    pub columns: Vec<Column>,
+    pub primary_key: Vec<KeyColumn>,
+    /// Stored as an index b-tree keyed by the primary key instead of by rowid.
+    pub without_rowid: bool,
+}
+
+fn text(record: &Record) -> Option<String> {
+    match record {
+        Record::String(s) => Some(String::from_utf8_lossy(s).into_owned()),
+        _ => None,
+    }
+}
+
+/// Reads every entry of `sqlite_schema`, which is the table b-tree rooted at page 1.
+pub fn read_schema(db: &mut DB) -> Result<Vec<SchemaEntry>> {
+    let mut entries = vec![];
+    for cell in btree_cells(db, 1, ContentVariant::TableCell)? {
+        if let Content::TableCell(content) = &cell.content {
+            let rootpage = match content.get_rootpage() {
+                Record::Null => 0,
+                r => r.get_numeric_val()? as u32,
+            };
+            entries.push(SchemaEntry {
+                kind: text(content.get_type()).unwrap_or_default(),
+                name: text(content.get_name()).unwrap_or_default(),
+                tbl_name: text(content.get_tbl_name()).unwrap_or_default(),
+                rootpage,
+                sql: text(content.get_sql()),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+impl Table {
+    /// The table holding the schema itself.
+    pub fn sqlite_schema() -> Self {
+        let columns = ["type", "name", "tbl_name", "rootpage", "sql"]
+            .iter()
+            .map(|name| {
+                let type_name = if *name == "rootpage" { "int" } else { "text" };
+                Column {
+                    name: name.to_string(),
+                    type_name: type_name.to_string(),
+                    affinity: Affinity::from_type_name(type_name),
+                    rowid_alias: false,
+                    collation: None,
+                    default: None,
+                }
+            })
+            .collect();
+        Self {
+            name: "sqlite_schema".to_string(),
+            rootpage: 1,
+            columns,
+            primary_key: vec![],
+            without_rowid: false,
+        }
+    }
+
+    pub fn from_entry(entry: &SchemaEntry) -> Result<Self> {
+        let Some(sql) = &entry.sql else {
+            return Err(DbError::Corrupt(format!("table {} has no schema sql", entry.name)));
+        };
+        let create = Parser::parse_create_table(sql).map_err(|e| {
+            DbError::Corrupt(format!("malformed schema for {}: {}", entry.name, e))
+        })?;
+        let mut primary_key: Vec<KeyColumn> = vec![];
+        for key in create.primary_key.and_then(|i| create.keys.get(i)).into_iter().flatten() {
+            let Some(column) = key
+                .name
+                .as_ref()
+                .and_then(|name| create.columns.iter().position(|c| c.name.eq_ignore_ascii_case(name)))
+            else {
+                return Err(DbError::Corrupt(format!("malformed primary key of {}", entry.name)));
+            };
+            // a column repeated in the key is only stored once
+            if primary_key.iter().any(|k| k.column == column) {
+                continue;
+            }
+            let collation = key.collation.as_ref().or(create.columns[column].collation.as_ref());
+            primary_key.push(KeyColumn {
+                column,
+                collation: collation.map_or(Some(Collation::Binary), |name| Collation::from_name(name)),
+                descending: key.descending,
+            });
+        }
+        let columns = create
+            .columns
+            .into_iter()
+            .map(|c| Column {
+                rowid_alias: c.rowid_alias,
+                affinity: Affinity::from_type_name(&c.type_name),
+                name: c.name,
+                type_name: c.type_name,
+                collation: c.collation,
+                default: c.default,
+            })
+            .collect();
+        Ok(Self {
+            name: entry.name.clone(),
+            rootpage: entry.rootpage,
+            columns,
+            primary_key,
+            without_rowid: create.without_rowid,
+        })
+    }
+
+    /// Positions in `columns` of the fields of a stored record. WITHOUT ROWID
+    /// tables store the primary key columns first, then the rest in declared order.
+    pub fn record_order(&self) -> Vec<usize> {
+        if !self.without_rowid {
+            return (0..self.columns.len()).collect();
+        }
+        let mut order: Vec<usize> = self.primary_key.iter().map(|k| k.column).collect();
+        let rest: Vec<usize> = (0..self.columns.len()).filter(|i| !order.contains(i)).collect();
+        order.extend(rest);
+        order
+    }
+}
+
+/// Looks up a table by name, case-insensitively like SQLite.
+pub fn find_table(schema: &[SchemaEntry], name: &str) -> Result<Table> {
+    if ["sqlite_schema", "sqlite_master"]
+        .iter()
+        .any(|n| n.eq_ignore_ascii_case(name))
+    {
+        return Ok(Table::sqlite_schema());
+    }
+    match schema
+        .iter()
+        .find(|e| e.kind == "table" && e.name.eq_ignore_ascii_case(name))
+    {
+        Some(entry) => Table::from_entry(entry),
+        None => Err(DbError::NoSuchTable(name.to_string())),
+    }
+}
@@ -0,0 +1,304 @@
+use crate::sql::tokenizer::Span;
+use crate::value::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Plus,
+    Not,
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitAnd,
+    BitOr,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Concat,
+    Is,
+    IsNot,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Literal(Value),
+    /// A bound parameter by its 1-based index.
+    Parameter(usize),
+    Column {
+        table: Option<String>,
+        name: String,
+    },
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    Function {
+        name: String,
+        args: Vec<Expr>,
+        distinct: bool,
+        /// `count(*)`
+        star: bool,
+        /// `FILTER (WHERE expr)` of an aggregate, which only folds the rows it holds for.
+        filter: Option<Box<Expr>>,
+    },
+    /// `name(args) [FILTER (WHERE expr)] OVER window`
+    Window {
+        name: String,
+        args: Vec<Expr>,
+        star: bool,
+        filter: Option<Box<Expr>>,
+        over: Box<WindowSpec>,
+    },
+    /// A scalar subquery, the first column of its first row.
+    Subquery(Box<Select>),
+    Exists(Box<Select>),
+    /// `expr [NOT] IN (SELECT ...)`
+    InSelect {
+        expr: Box<Expr>,
+        select: Box<Select>,
+        negated: bool,
+    },
+    /// `expr [NOT] IN (list)`
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    /// `CASE [operand] WHEN ... THEN ... [ELSE ...] END`
+    Case {
+        operand: Option<Box<Expr>>,
+        branches: Vec<(Expr, Expr)>,
+        otherwise: Option<Box<Expr>>,
+    },
+    /// `CAST(expr AS type_name)`
+    Cast {
+        expr: Box<Expr>,
+        type_name: String,
+    },
+    /// `expr [NOT] BETWEEN low AND high`
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        negated: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResultColumn {
+    Star,
+    TableStar(String),
+    Expr {
+        expr: Expr,
+        alias: Option<String>,
+        /// The expression as written, used as the column name when there is no alias.
+        text: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableRef {
+    pub name: String,
+    pub alias: Option<String>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FromItem {
+    Table(TableRef),
+    /// A derived table, `(SELECT ...) [AS alias]`.
+    Subquery {
+        select: Box<Select>,
+        alias: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameUnits {
+    Rows,
+    Range,
+    Groups,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameBound {
+    UnboundedPreceding,
+    Preceding(Expr),
+    CurrentRow,
+    Following(Expr),
+    UnboundedFollowing,
+}
+
+/// Rows the EXCLUDE clause leaves out of the frame of the current row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameExclude {
+    #[default]
+    NoOthers,
+    CurrentRow,
+    /// The current row and its peers.
+    Group,
+    /// The peers of the current row but not the row itself.
+    Ties,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub units: FrameUnits,
+    pub start: FrameBound,
+    pub end: FrameBound,
+    pub exclude: FrameExclude,
+}
+
+/// The window of a window function, `OVER name` being a spec with only a `base`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WindowSpec {
+    /// A window of the WINDOW clause this one extends.
+    pub base: Option<String>,
+    pub partition_by: Vec<Expr>,
+    pub order_by: Vec<OrderingTerm>,
+    /// `None` for the default `RANGE BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW`.
+    pub frame: Option<Frame>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderingTerm {
+    pub expr: Expr,
+    pub descending: bool,
+}
+
+/// `name [(columns)] AS (select)` of a WITH clause. The table is recursive
+/// when the last term of a compound select reads from it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cte {
+    pub name: String,
+    /// Column names given after the name, empty to use the names of the select.
+    pub columns: Vec<String>,
+    pub select: Select,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompoundOp {
+    Union,
+    UnionAll,
+    Intersect,
+    Except,
+}
+
+impl std::fmt::Display for CompoundOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            CompoundOp::Union => "UNION",
+            CompoundOp::UnionAll => "UNION ALL",
+            CompoundOp::Intersect => "INTERSECT",
+            CompoundOp::Except => "EXCEPT",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Select {
+    pub with: Vec<Cte>,
+    pub distinct: bool,
+    pub columns: Vec<ResultColumn>,
+    /// Tables of the FROM clause, empty without one. They are inner joined,
+    /// the ON constraints of the joins are part of `where_clause`.
+    pub from: Vec<FromItem>,
+    pub where_clause: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    /// Named windows of the WINDOW clause.
+    pub windows: Vec<(String, WindowSpec)>,
+    /// Further selects combined with this one, left to right. ORDER BY and
+    /// LIMIT then apply to the whole compound.
+    pub compound: Vec<(CompoundOp, Select)>,
+    pub order_by: Vec<OrderingTerm>,
+    pub limit: Option<Expr>,
+    pub offset: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDef {
+    pub name: String,
+    pub type_name: String,
+    pub primary_key: bool,
+    /// An INTEGER PRIMARY KEY of a rowid table, stored as the rowid itself.
+    pub rowid_alias: bool,
+    pub collation: Option<String>,
+    pub default: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateTable {
+    pub name: String,
+    pub columns: Vec<ColumnDef>,
+    /// PRIMARY KEY and UNIQUE constraints in declaration order, which is the
+    /// order sqlite numbers their automatic indexes in.
+    pub keys: Vec<Vec<IndexedColumn>>,
+    /// Position of the PRIMARY KEY in `keys`.
+    pub primary_key: Option<usize>,
+    pub without_rowid: bool,
+}
+
+/// A column of an index, `name` is `None` for an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedColumn {
+    pub name: Option<String>,
+    pub collation: Option<String>,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateIndex {
+    pub name: String,
+    pub table: String,
+    pub unique: bool,
+    pub columns: Vec<IndexedColumn>,
+    /// Set for a partial index.
+    pub where_clause: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateView {
+    pub name: String,
+    /// Column names given after the view name, empty to use the names of the select.
+    pub columns: Vec<String>,
+    pub select: Select,
+}
+
+/// `PRAGMA name`, `PRAGMA name = value` or `PRAGMA name(value)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pragma {
+    pub name: String,
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Select(Box<Select>),
+    Pragma(Pragma),
+}
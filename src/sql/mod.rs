@@ -0,0 +1,5 @@
+pub mod ast;
+pub mod parser;
+pub mod tokenizer;
+
+pub use parser::Parser;
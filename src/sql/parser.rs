@@ -0,0 +1,1402 @@
+use crate::error::{DbError, Result};
+
+use crate::sql::ast::*;
+use crate::sql::tokenizer::{tokenize, Span, Token, TokenKind};
+use crate::value::Value;
+
+/// Words that end an expression or clause and therefore can't be used as implicit aliases.
+const RESERVED: [&str; 38] = [
+    "ALL", "AND", "AS", "ASC", "BY", "CASE", "CROSS", "DESC", "DISTINCT", "ELSE", "END", "EXCEPT", "FROM",
+    "GROUP", "HAVING", "IN", "INNER", "INTERSECT", "IS", "JOIN", "LEFT", "LIMIT", "NATURAL", "NOT", "NULL",
+    "OFFSET", "ON", "OR", "ORDER", "OUTER", "SELECT", "THEN", "UNION", "USING", "WHEN", "WHERE", "WINDOW",
+    "WITH",
+];
+
+/// Keywords that start a column constraint and end the column's type name.
+const COLUMN_CONSTRAINTS: [&str; 11] = [
+    "CONSTRAINT", "PRIMARY", "NOT", "NULL", "UNIQUE", "CHECK", "DEFAULT", "COLLATE", "REFERENCES",
+    "GENERATED", "AS",
+];
+
+/// Highest `?NNN` parameter number, sqlite3's SQLITE_MAX_VARIABLE_NUMBER.
+const MAX_VARIABLE_NUMBER: usize = 250000;
+
+/// Keywords that start a table constraint instead of a column definition.
+const TABLE_CONSTRAINTS: [&str; 5] = ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"];
+
+pub struct Parser<'a> {
+    sql: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Name of each bound parameter by index, `None` for a plain `?`.
+    parameters: Vec<Option<String>>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(sql: &'a str) -> Result<Self> {
+        Ok(Self {
+            sql,
+            tokens: tokenize(sql)?,
+            pos: 0,
+            parameters: vec![],
+        })
+    }
+
+    /// Parses a single statement with an optional trailing `;`.
+    #[cfg(test)]
+    pub fn parse_statement(sql: &str) -> Result<Statement> {
+        Self::parse_statement_with_parameters(sql).map(|(statement, _)| statement)
+    }
+
+    /// Like `parse_statement`, also returning the names of the bound
+    /// parameters, where the parameter with index `i` is at `i - 1`.
+    pub fn parse_statement_with_parameters(sql: &str) -> Result<(Statement, Vec<Option<String>>)> {
+        let mut parser = Parser::new(sql)?;
+        let statement = if parser.peek_select() {
+            Statement::Select(Box::new(parser.parse_select()?))
+        } else if parser.eat_keyword("PRAGMA") {
+            Statement::Pragma(parser.pragma()?)
+        } else {
+            return Err(parser.error());
+        };
+        parser.finish()?;
+        Ok((statement, parser.parameters))
+    }
+
+    pub fn parse_create_table(sql: &str) -> Result<CreateTable> {
+        let mut parser = Parser::new(sql)?;
+        let table = parser.create_table()?;
+        parser.finish()?;
+        Ok(table)
+    }
+
+    pub fn parse_create_index(sql: &str) -> Result<CreateIndex> {
+        let mut parser = Parser::new(sql)?;
+        let index = parser.create_index()?;
+        parser.finish()?;
+        Ok(index)
+    }
+
+    pub fn parse_create_view(sql: &str) -> Result<CreateView> {
+        let mut parser = Parser::new(sql)?;
+        let view = parser.create_view()?;
+        parser.finish()?;
+        Ok(view)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        while self.eat_symbol(";") {}
+        if self.pos < self.tokens.len() {
+            return Err(self.error());
+        }
+        Ok(())
+    }
+
+    pub fn text(&self, span: Span) -> &'a str {
+        &self.sql[span.start..span.end]
+    }
+
+    fn error(&self) -> DbError {
+        let span = self.span();
+        let message = match self.tokens.get(self.pos) {
+            Some(token) => format!("near \"{}\": syntax error", self.text(token.span)),
+            None => "incomplete input".to_string(),
+        };
+        DbError::SyntaxError { message, span }
+    }
+
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|t| &t.kind)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&TokenKind> {
+        self.tokens.get(self.pos + offset).map(|t| &t.kind)
+    }
+
+    fn span(&self) -> Span {
+        self.tokens
+            .get(self.pos)
+            .map(|t| t.span)
+            .unwrap_or(Span {
+                start: self.sql.len(),
+                end: self.sql.len(),
+            })
+    }
+
+    fn prev_span(&self) -> Span {
+        self.tokens[self.pos - 1].span
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(TokenKind::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek_keyword(keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if !self.eat_keyword(keyword) {
+            return Err(self.error());
+        }
+        Ok(())
+    }
+
+    fn peek_select(&self) -> bool {
+        self.peek_keyword("SELECT") || self.peek_keyword("WITH")
+    }
+
+    fn peek_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(TokenKind::Symbol(s)) if *s == symbol)
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        if self.peek_symbol(symbol) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<()> {
+        if !self.eat_symbol(symbol) {
+            return Err(self.error());
+        }
+        Ok(())
+    }
+
+    fn identifier(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(TokenKind::Word(w)) if !is_reserved(w) => {
+                let w = w.clone();
+                self.pos += 1;
+                Ok(w)
+            }
+            Some(TokenKind::QuotedIdent(s)) | Some(TokenKind::String(s)) => {
+                let s = s.clone();
+                self.pos += 1;
+                Ok(s)
+            }
+            _ => Err(self.error()),
+        }
+    }
+
+    /// `[AS] alias`, where `AS` may be omitted unless the alias is a reserved word.
+    fn alias(&mut self) -> Result<Option<String>> {
+        if self.eat_keyword("AS") {
+            return self.identifier().map(Some);
+        }
+        match self.peek() {
+            Some(TokenKind::Word(w)) if !is_reserved(w) => self.identifier().map(Some),
+            Some(TokenKind::QuotedIdent(_)) | Some(TokenKind::String(_)) => {
+                self.identifier().map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn source(&mut self) -> Result<FromItem> {
+        if self.eat_symbol("(") {
+            let select = self.parse_select()?;
+            self.expect_symbol(")")?;
+            return Ok(FromItem::Subquery {
+                select: Box::new(select),
+                alias: self.alias()?,
+            });
+        }
+        let start = self.span();
+        let name = self.identifier()?;
+        let name = if self.eat_symbol(".") {
+            self.identifier()?
+        } else {
+            name
+        };
+        let span = start.to(self.prev_span());
+        let alias = self.alias()?;
+        Ok(FromItem::Table(TableRef { name, alias, span }))
+    }
+
+    /// A parenthesized select, the opening parenthesis already consumed.
+    fn subquery(&mut self) -> Result<Box<Select>> {
+        let select = self.parse_select()?;
+        self.expect_symbol(")")?;
+        Ok(Box::new(select))
+    }
+
+    /// `WITH [RECURSIVE] cte, ...`, the RECURSIVE keyword is optional as in sqlite.
+    fn with(&mut self) -> Result<Vec<Cte>> {
+        self.eat_keyword("RECURSIVE");
+        let mut ctes = vec![];
+        loop {
+            let name = self.identifier()?;
+            let mut columns = vec![];
+            if self.eat_symbol("(") {
+                loop {
+                    columns.push(self.identifier()?);
+                    if !self.eat_symbol(",") {
+                        break;
+                    }
+                }
+                self.expect_symbol(")")?;
+            }
+            self.expect_keyword("AS")?;
+            self.expect_symbol("(")?;
+            let select = self.parse_select()?;
+            self.expect_symbol(")")?;
+            ctes.push(Cte { name, columns, select });
+            if !self.eat_symbol(",") {
+                return Ok(ctes);
+            }
+        }
+    }
+
+    fn compound_operator(&mut self) -> Option<CompoundOp> {
+        if self.eat_keyword("UNION") {
+            Some(if self.eat_keyword("ALL") { CompoundOp::UnionAll } else { CompoundOp::Union })
+        } else if self.eat_keyword("INTERSECT") {
+            Some(CompoundOp::Intersect)
+        } else if self.eat_keyword("EXCEPT") {
+            Some(CompoundOp::Except)
+        } else {
+            None
+        }
+    }
+
+    fn parse_select(&mut self) -> Result<Select> {
+        let with = if self.eat_keyword("WITH") { self.with()? } else { vec![] };
+        let mut select = self.select_core()?;
+        select.with = with;
+        while let Some(op) = self.compound_operator() {
+            select.compound.push((op, self.select_core()?));
+        }
+
+        if self.eat_keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            select.order_by = self.ordering_terms()?;
+        }
+
+        if self.eat_keyword("LIMIT") {
+            let limit = self.expr()?;
+            if self.eat_keyword("OFFSET") {
+                select.limit = Some(limit);
+                select.offset = Some(self.expr()?);
+            } else if self.eat_symbol(",") {
+                select.offset = Some(limit);
+                select.limit = Some(self.expr()?);
+            } else {
+                select.limit = Some(limit);
+            }
+        }
+
+        let span = self.span();
+        if let Some(op) = self.compound_operator() {
+            let clause = if select.order_by.is_empty() { "LIMIT" } else { "ORDER BY" };
+            let message = format!("{} clause should come after {} not before", clause, op);
+            return Err(DbError::SyntaxError { message, span });
+        }
+        Ok(select)
+    }
+
+    /// `SELECT ...` up to HAVING, the part a compound select repeats.
+    fn select_core(&mut self) -> Result<Select> {
+        self.expect_keyword("SELECT")?;
+        let mut select = Select {
+            distinct: self.eat_keyword("DISTINCT"),
+            ..Default::default()
+        };
+        if !select.distinct {
+            self.eat_keyword("ALL");
+        }
+
+        loop {
+            select.columns.push(self.result_column()?);
+            if !self.eat_symbol(",") {
+                break;
+            }
+        }
+
+        let mut constraints = vec![];
+        if self.eat_keyword("FROM") {
+            select.from.push(self.source()?);
+            loop {
+                if self.eat_symbol(",") {
+                    select.from.push(self.source()?);
+                    continue;
+                }
+                let joined = if self.eat_keyword("CROSS") || self.eat_keyword("INNER") {
+                    self.expect_keyword("JOIN")?;
+                    true
+                } else {
+                    self.eat_keyword("JOIN")
+                };
+                if !joined {
+                    break;
+                }
+                select.from.push(self.source()?);
+                if self.eat_keyword("ON") {
+                    constraints.push(self.expr()?);
+                }
+            }
+        }
+
+        if self.eat_keyword("WHERE") {
+            constraints.push(self.expr()?);
+        }
+        select.where_clause = constraints.into_iter().reduce(|left, right| Expr {
+            span: left.span.to(right.span),
+            kind: ExprKind::Binary {
+                op: BinaryOp::And,
+                left: Box::new(left),
+                right: Box::new(right),
+            },
+        });
+
+        if self.eat_keyword("GROUP") {
+            self.expect_keyword("BY")?;
+            select.group_by = self.expr_list()?;
+            if self.eat_keyword("HAVING") {
+                select.having = Some(self.expr()?);
+            }
+        }
+
+        if self.eat_keyword("WINDOW") {
+            loop {
+                let name = self.identifier()?;
+                self.expect_keyword("AS")?;
+                self.expect_symbol("(")?;
+                select.windows.push((name, self.window_spec()?));
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+        Ok(select)
+    }
+
+    fn ordering_terms(&mut self) -> Result<Vec<OrderingTerm>> {
+        let mut terms = vec![];
+        loop {
+            let expr = self.expr()?;
+            let descending = if self.eat_keyword("DESC") {
+                true
+            } else {
+                self.eat_keyword("ASC");
+                false
+            };
+            terms.push(OrderingTerm { expr, descending });
+            if !self.eat_symbol(",") {
+                return Ok(terms);
+            }
+        }
+    }
+
+    /// The inside of a parenthesized window, the opening parenthesis already consumed.
+    fn window_spec(&mut self) -> Result<WindowSpec> {
+        let mut spec = WindowSpec::default();
+        let clause = ["PARTITION", "ORDER", "ROWS", "RANGE", "GROUPS"];
+        if !self.peek_symbol(")") && !clause.iter().any(|k| self.peek_keyword(k)) {
+            spec.base = Some(self.identifier()?);
+        }
+        if self.eat_keyword("PARTITION") {
+            self.expect_keyword("BY")?;
+            spec.partition_by = self.expr_list()?;
+        }
+        if self.eat_keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            spec.order_by = self.ordering_terms()?;
+        }
+        let units = if self.eat_keyword("ROWS") {
+            Some(FrameUnits::Rows)
+        } else if self.eat_keyword("RANGE") {
+            Some(FrameUnits::Range)
+        } else if self.eat_keyword("GROUPS") {
+            Some(FrameUnits::Groups)
+        } else {
+            None
+        };
+        if let Some(units) = units {
+            // a lone start bound ends at the current row
+            let (start, end) = if self.eat_keyword("BETWEEN") {
+                let start = self.frame_bound()?;
+                self.expect_keyword("AND")?;
+                (start, self.frame_bound()?)
+            } else {
+                (self.frame_bound()?, FrameBound::CurrentRow)
+            };
+            let exclude = if !self.eat_keyword("EXCLUDE") {
+                FrameExclude::NoOthers
+            } else if self.eat_keyword("NO") {
+                self.expect_keyword("OTHERS")?;
+                FrameExclude::NoOthers
+            } else if self.eat_keyword("CURRENT") {
+                self.expect_keyword("ROW")?;
+                FrameExclude::CurrentRow
+            } else if self.eat_keyword("GROUP") {
+                FrameExclude::Group
+            } else {
+                self.expect_keyword("TIES")?;
+                FrameExclude::Ties
+            };
+            spec.frame = Some(Frame {
+                units,
+                start,
+                end,
+                exclude,
+            });
+        }
+        self.expect_symbol(")")?;
+        Ok(spec)
+    }
+
+    fn frame_bound(&mut self) -> Result<FrameBound> {
+        if self.eat_keyword("UNBOUNDED") {
+            if self.eat_keyword("PRECEDING") {
+                return Ok(FrameBound::UnboundedPreceding);
+            }
+            self.expect_keyword("FOLLOWING")?;
+            return Ok(FrameBound::UnboundedFollowing);
+        }
+        if self.eat_keyword("CURRENT") {
+            self.expect_keyword("ROW")?;
+            return Ok(FrameBound::CurrentRow);
+        }
+        // the offset binds tighter than the AND of BETWEEN
+        let offset = self.expr_bp(2)?;
+        if self.eat_keyword("PRECEDING") {
+            return Ok(FrameBound::Preceding(offset));
+        }
+        self.expect_keyword("FOLLOWING")?;
+        Ok(FrameBound::Following(offset))
+    }
+
+    fn result_column(&mut self) -> Result<ResultColumn> {
+        if self.eat_symbol("*") {
+            return Ok(ResultColumn::Star);
+        }
+        if matches!(self.peek(), Some(TokenKind::Word(_)) | Some(TokenKind::QuotedIdent(_)))
+            && matches!(self.peek_at(1), Some(TokenKind::Symbol(".")))
+            && matches!(self.peek_at(2), Some(TokenKind::Symbol("*")))
+        {
+            let table = self.identifier()?;
+            self.pos += 2;
+            return Ok(ResultColumn::TableStar(table));
+        }
+        let expr = self.expr()?;
+        let text = self.text(expr.span).to_string();
+        let alias = self.alias()?;
+        Ok(ResultColumn::Expr { expr, alias, text })
+    }
+
+    fn expr_list(&mut self) -> Result<Vec<Expr>> {
+        let mut exprs = vec![self.expr()?];
+        while self.eat_symbol(",") {
+            exprs.push(self.expr()?);
+        }
+        Ok(exprs)
+    }
+
+    pub fn expr(&mut self) -> Result<Expr> {
+        self.expr_bp(0)
+    }
+
+    /// Binding power of the binary operator at the current position.
+    fn binary_op(&self) -> Option<(BinaryOp, u8)> {
+        let op = match self.peek()? {
+            TokenKind::Word(w) if w.eq_ignore_ascii_case("OR") => (BinaryOp::Or, 1),
+            TokenKind::Word(w) if w.eq_ignore_ascii_case("AND") => (BinaryOp::And, 2),
+            TokenKind::Symbol(s) => match *s {
+                "=" | "==" => (BinaryOp::Eq, 4),
+                "!=" | "<>" => (BinaryOp::Ne, 4),
+                "<" => (BinaryOp::Lt, 5),
+                "<=" => (BinaryOp::Le, 5),
+                ">" => (BinaryOp::Gt, 5),
+                ">=" => (BinaryOp::Ge, 5),
+                "&" => (BinaryOp::BitAnd, 6),
+                "|" => (BinaryOp::BitOr, 6),
+                "<<" => (BinaryOp::ShiftLeft, 6),
+                ">>" => (BinaryOp::ShiftRight, 6),
+                "+" => (BinaryOp::Add, 7),
+                "-" => (BinaryOp::Sub, 7),
+                "*" => (BinaryOp::Mul, 8),
+                "/" => (BinaryOp::Div, 8),
+                "%" => (BinaryOp::Mod, 8),
+                "||" => (BinaryOp::Concat, 9),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    fn expr_bp(&mut self, min_bp: u8) -> Result<Expr> {
+        let mut left = self.prefix()?;
+
+        loop {
+            // IN, BETWEEN, LIKE, GLOB and the IS/NULL tests bind like the comparison operators
+            if self.peek_predicate() {
+                if 4 <= min_bp {
+                    break;
+                }
+                left = self.predicate(left)?;
+                continue;
+            }
+
+            let Some((op, bp)) = self.binary_op() else {
+                break;
+            };
+            if bp <= min_bp {
+                break;
+            }
+            self.pos += 1;
+            let right = self.expr_bp(bp)?;
+            left = Expr {
+                span: left.span.to(right.span),
+                kind: ExprKind::Binary {
+                    op,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                },
+            };
+        }
+
+        Ok(left)
+    }
+
+    /// Whether a `[NOT] IN/BETWEEN/LIKE/GLOB`, `IS` or `ISNULL/NOTNULL/NOT NULL` predicate follows.
+    fn peek_predicate(&self) -> bool {
+        let is_keyword = |offset, keywords: &[&str]| {
+            matches!(self.peek_at(offset), Some(TokenKind::Word(w)) if keywords.iter().any(|k| w.eq_ignore_ascii_case(k)))
+        };
+        is_keyword(0, &["IN", "BETWEEN", "LIKE", "GLOB", "IS", "ISNULL", "NOTNULL"])
+            || is_keyword(0, &["NOT"]) && is_keyword(1, &["IN", "BETWEEN", "LIKE", "GLOB", "NULL"])
+    }
+
+    fn predicate(&mut self, left: Expr) -> Result<Expr> {
+        let postfix_null = |parser: &mut Self, left: Expr, op| Expr {
+            span: left.span.to(parser.prev_span()),
+            kind: ExprKind::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(Expr {
+                    kind: ExprKind::Literal(Value::Null),
+                    span: parser.prev_span(),
+                }),
+            },
+        };
+        if self.eat_keyword("ISNULL") {
+            return Ok(postfix_null(self, left, BinaryOp::Is));
+        }
+        if self.eat_keyword("NOTNULL") {
+            return Ok(postfix_null(self, left, BinaryOp::IsNot));
+        }
+        if self.eat_keyword("IS") {
+            let mut negated = self.eat_keyword("NOT");
+            if self.eat_keyword("DISTINCT") {
+                self.expect_keyword("FROM")?;
+                negated = !negated;
+            }
+            let right = self.expr_bp(4)?;
+            return Ok(Expr {
+                span: left.span.to(right.span),
+                kind: ExprKind::Binary {
+                    op: if negated { BinaryOp::IsNot } else { BinaryOp::Is },
+                    left: Box::new(left),
+                    right: Box::new(right),
+                },
+            });
+        }
+
+        let negated = self.eat_keyword("NOT");
+        if self.eat_keyword("NULL") {
+            return Ok(postfix_null(self, left, BinaryOp::IsNot));
+        }
+        let start = left.span;
+        let expr = Box::new(left);
+        let kind = if self.eat_keyword("IN") {
+            self.expect_symbol("(")?;
+            if self.peek_select() {
+                ExprKind::InSelect {
+                    expr,
+                    select: self.subquery()?,
+                    negated,
+                }
+            } else {
+                let mut list = vec![];
+                if !self.peek_symbol(")") {
+                    list = self.expr_list()?;
+                }
+                self.expect_symbol(")")?;
+                ExprKind::InList {
+                    expr,
+                    list,
+                    negated,
+                }
+            }
+        } else if self.eat_keyword("BETWEEN") {
+            let low = self.expr_bp(4)?;
+            self.expect_keyword("AND")?;
+            let high = self.expr_bp(4)?;
+            ExprKind::Between {
+                expr,
+                low: Box::new(low),
+                high: Box::new(high),
+                negated,
+            }
+        } else {
+            // `x LIKE y ESCAPE z` is the function call `like(y, x, z)`, as in sqlite
+            let name = if self.eat_keyword("LIKE") {
+                "like"
+            } else {
+                self.expect_keyword("GLOB")?;
+                "glob"
+            };
+            let mut args = vec![self.expr_bp(4)?, *expr];
+            if self.eat_keyword("ESCAPE") {
+                args.push(self.expr_bp(4)?);
+            }
+            let call = Expr {
+                span: start.to(self.prev_span()),
+                kind: ExprKind::Function {
+                    name: name.to_string(),
+                    args,
+                    distinct: false,
+                    star: false,
+                    filter: None,
+                },
+            };
+            if !negated {
+                return Ok(call);
+            }
+            ExprKind::Unary {
+                op: UnaryOp::Not,
+                expr: Box::new(call),
+            }
+        };
+        Ok(Expr {
+            span: start.to(self.prev_span()),
+            kind,
+        })
+    }
+
+    fn prefix(&mut self) -> Result<Expr> {
+        let start = self.span();
+        let (op, bp) = if self.eat_keyword("NOT") {
+            (UnaryOp::Not, 3)
+        } else if self.eat_symbol("-") {
+            (UnaryOp::Neg, 10)
+        } else if self.eat_symbol("+") {
+            (UnaryOp::Plus, 10)
+        } else if self.eat_symbol("~") {
+            (UnaryOp::BitNot, 10)
+        } else {
+            return self.primary();
+        };
+        let expr = self.expr_bp(bp)?;
+        Ok(Expr {
+            span: start.to(expr.span),
+            kind: ExprKind::Unary {
+                op,
+                expr: Box::new(expr),
+            },
+        })
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        let start = self.span();
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.error());
+        };
+
+        let kind = match token {
+            TokenKind::Integer(i) => {
+                self.pos += 1;
+                ExprKind::Literal(Value::Integer(i))
+            }
+            TokenKind::Float(f) => {
+                self.pos += 1;
+                ExprKind::Literal(Value::Real(f))
+            }
+            TokenKind::String(s) => {
+                self.pos += 1;
+                ExprKind::Literal(Value::Text(s))
+            }
+            TokenKind::Blob(b) => {
+                self.pos += 1;
+                ExprKind::Literal(Value::Blob(b))
+            }
+            TokenKind::Variable(name) => {
+                self.pos += 1;
+                ExprKind::Parameter(self.parameter(name)?)
+            }
+            TokenKind::Symbol("(") => {
+                self.pos += 1;
+                if self.peek_select() {
+                    ExprKind::Subquery(self.subquery()?)
+                } else {
+                    let expr = self.expr()?;
+                    self.expect_symbol(")")?;
+                    return Ok(Expr {
+                        kind: expr.kind,
+                        span: start.to(self.prev_span()),
+                    });
+                }
+            }
+            TokenKind::Word(w) if w.eq_ignore_ascii_case("EXISTS") && matches!(self.peek_at(1), Some(TokenKind::Symbol("("))) => {
+                self.pos += 2;
+                ExprKind::Exists(self.subquery()?)
+            }
+            TokenKind::Word(w) if w.eq_ignore_ascii_case("NULL") => {
+                self.pos += 1;
+                ExprKind::Literal(Value::Null)
+            }
+            TokenKind::Word(w) if w.eq_ignore_ascii_case("TRUE") => {
+                self.pos += 1;
+                ExprKind::Literal(Value::Integer(1))
+            }
+            TokenKind::Word(w) if w.eq_ignore_ascii_case("FALSE") => {
+                self.pos += 1;
+                ExprKind::Literal(Value::Integer(0))
+            }
+            TokenKind::Word(w) if w.eq_ignore_ascii_case("CASE") => {
+                self.pos += 1;
+                self.case()?
+            }
+            TokenKind::Word(w) if w.eq_ignore_ascii_case("CAST") && matches!(self.peek_at(1), Some(TokenKind::Symbol("("))) => {
+                self.pos += 2;
+                let expr = self.expr()?;
+                self.expect_keyword("AS")?;
+                let type_name = self.type_name()?;
+                self.expect_symbol(")")?;
+                ExprKind::Cast {
+                    expr: Box::new(expr),
+                    type_name,
+                }
+            }
+            TokenKind::Word(_) | TokenKind::QuotedIdent(_) => {
+                let name = self.identifier()?;
+                if matches!(token, TokenKind::Word(_)) && self.eat_symbol("(") {
+                    self.function(name)?
+                } else if self.eat_symbol(".") {
+                    ExprKind::Column {
+                        table: Some(name),
+                        name: self.identifier()?,
+                    }
+                } else {
+                    ExprKind::Column { table: None, name }
+                }
+            }
+            _ => return Err(self.error()),
+        };
+
+        Ok(Expr {
+            kind,
+            span: start.to(self.prev_span()),
+        })
+    }
+
+    /// Numbers a parameter like sqlite: `?` takes the next index, `?NNN` index
+    /// NNN, and a name the index of its first use or else the next one.
+    fn parameter(&mut self, name: String) -> Result<usize> {
+        if name == "?" {
+            self.parameters.push(None);
+            return Ok(self.parameters.len());
+        }
+        if let Some(number) = name.strip_prefix('?') {
+            let Some(index) = number.parse().ok().filter(|i| (1..=MAX_VARIABLE_NUMBER).contains(i)) else {
+                return Err(DbError::Query(format!("variable number must be between ?1 and ?{}", MAX_VARIABLE_NUMBER)));
+            };
+            if index > self.parameters.len() {
+                self.parameters.resize(index, None);
+            }
+            self.parameters[index - 1].get_or_insert(name);
+            return Ok(index);
+        }
+        if let Some(i) = self.parameters.iter().position(|p| p.as_deref() == Some(name.as_str())) {
+            return Ok(i + 1);
+        }
+        self.parameters.push(Some(name));
+        Ok(self.parameters.len())
+    }
+
+    /// `[operand] WHEN ... THEN ... [ELSE ...] END`, the CASE keyword already consumed.
+    fn case(&mut self) -> Result<ExprKind> {
+        let operand = if self.peek_keyword("WHEN") { None } else { Some(Box::new(self.expr()?)) };
+        let mut branches = vec![];
+        while self.eat_keyword("WHEN") {
+            let when = self.expr()?;
+            self.expect_keyword("THEN")?;
+            branches.push((when, self.expr()?));
+        }
+        if branches.is_empty() {
+            return Err(self.error());
+        }
+        let otherwise = if self.eat_keyword("ELSE") { Some(Box::new(self.expr()?)) } else { None };
+        self.expect_keyword("END")?;
+        Ok(ExprKind::Case {
+            operand,
+            branches,
+            otherwise,
+        })
+    }
+
+    /// Arguments of a function call, the opening parenthesis already consumed.
+    fn function(&mut self, name: String) -> Result<ExprKind> {
+        let mut args = vec![];
+        let mut star = false;
+        let distinct = self.eat_keyword("DISTINCT");
+        if self.eat_symbol("*") {
+            star = true;
+        } else if !self.peek_symbol(")") {
+            args = self.expr_list()?;
+        }
+        self.expect_symbol(")")?;
+        // FILTER is only a keyword in front of its parenthesis, otherwise it can be an alias
+        let filter = if self.peek_keyword("FILTER") && matches!(self.peek_at(1), Some(TokenKind::Symbol("("))) {
+            self.pos += 2;
+            self.expect_keyword("WHERE")?;
+            let filter = self.expr()?;
+            self.expect_symbol(")")?;
+            Some(Box::new(filter))
+        } else {
+            None
+        };
+        if self.eat_keyword("OVER") {
+            if distinct {
+                return Err(DbError::Query("DISTINCT is not supported for window functions".to_string()));
+            }
+            let over = if self.eat_symbol("(") {
+                self.window_spec()?
+            } else {
+                WindowSpec {
+                    base: Some(self.identifier()?),
+                    ..Default::default()
+                }
+            };
+            return Ok(ExprKind::Window {
+                name: name.to_lowercase(),
+                args,
+                star,
+                filter,
+                over: Box::new(over),
+            });
+        }
+        Ok(ExprKind::Function {
+            name: name.to_lowercase(),
+            args,
+            distinct,
+            star,
+            filter,
+        })
+    }
+
+    fn create_table(&mut self) -> Result<CreateTable> {
+        self.expect_keyword("CREATE")?;
+        if !self.eat_keyword("TEMP") {
+            self.eat_keyword("TEMPORARY");
+        }
+        self.expect_keyword("TABLE")?;
+        if self.eat_keyword("IF") {
+            self.expect_keyword("NOT")?;
+            self.expect_keyword("EXISTS")?;
+        }
+        let mut name = self.identifier()?;
+        if self.eat_symbol(".") {
+            name = self.identifier()?;
+        }
+        self.expect_symbol("(")?;
+
+        let mut columns: Vec<ColumnDef> = vec![];
+        let mut keys = vec![];
+        let mut primary_key = None;
+        loop {
+            if self.peek().is_some_and(|t| matches!(t, TokenKind::Word(w) if TABLE_CONSTRAINTS.iter().any(|c| w.eq_ignore_ascii_case(c)))) {
+                if self.eat_keyword("CONSTRAINT") {
+                    self.identifier()?;
+                }
+                let is_primary = self.eat_keyword("PRIMARY");
+                if is_primary {
+                    self.expect_keyword("KEY")?;
+                    primary_key = Some(keys.len());
+                }
+                if is_primary || self.eat_keyword("UNIQUE") {
+                    self.expect_symbol("(")?;
+                    let key = self.indexed_columns()?;
+                    self.expect_symbol(")")?;
+                    if let (true, [IndexedColumn { name: Some(pk), .. }]) = (is_primary, key.as_slice()) {
+                        for column in columns.iter_mut() {
+                            if column.name.eq_ignore_ascii_case(pk) {
+                                column.primary_key = true;
+                                column.rowid_alias = column.type_name.eq_ignore_ascii_case("INTEGER");
+                            }
+                        }
+                    }
+                    keys.push(key);
+                }
+                self.skip_until(&[",", ")"])?;
+            } else {
+                let (column, column_keys, column_primary_key) = self.column_def()?;
+                if let Some(i) = column_primary_key {
+                    primary_key = Some(keys.len() + i);
+                }
+                keys.extend(column_keys);
+                columns.push(column);
+            }
+            if !self.eat_symbol(",") {
+                break;
+            }
+        }
+        self.expect_symbol(")")?;
+        // table options such as WITHOUT ROWID or STRICT
+        let mut without_rowid = false;
+        while self.pos < self.tokens.len() && !self.peek_symbol(";") {
+            if self.eat_keyword("WITHOUT") {
+                self.expect_keyword("ROWID")?;
+                without_rowid = true;
+            } else {
+                self.pos += 1;
+            }
+        }
+
+        if without_rowid {
+            columns.iter_mut().for_each(|c| c.rowid_alias = false);
+        }
+
+        Ok(CreateTable {
+            name,
+            columns,
+            keys,
+            primary_key,
+            without_rowid,
+        })
+    }
+
+    fn create_index(&mut self) -> Result<CreateIndex> {
+        self.expect_keyword("CREATE")?;
+        let unique = self.eat_keyword("UNIQUE");
+        self.expect_keyword("INDEX")?;
+        if self.eat_keyword("IF") {
+            self.expect_keyword("NOT")?;
+            self.expect_keyword("EXISTS")?;
+        }
+        let mut name = self.identifier()?;
+        if self.eat_symbol(".") {
+            name = self.identifier()?;
+        }
+        self.expect_keyword("ON")?;
+        let table = self.identifier()?;
+        self.expect_symbol("(")?;
+
+        let columns = self.indexed_columns()?;
+        self.expect_symbol(")")?;
+        let where_clause = if self.eat_keyword("WHERE") {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+
+        Ok(CreateIndex {
+            name,
+            table,
+            unique,
+            columns,
+            where_clause,
+        })
+    }
+
+    fn create_view(&mut self) -> Result<CreateView> {
+        self.expect_keyword("CREATE")?;
+        if !self.eat_keyword("TEMP") {
+            self.eat_keyword("TEMPORARY");
+        }
+        self.expect_keyword("VIEW")?;
+        if self.eat_keyword("IF") {
+            self.expect_keyword("NOT")?;
+            self.expect_keyword("EXISTS")?;
+        }
+        let mut name = self.identifier()?;
+        if self.eat_symbol(".") {
+            name = self.identifier()?;
+        }
+        let mut columns = vec![];
+        if self.eat_symbol("(") {
+            loop {
+                columns.push(self.identifier()?);
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+            self.expect_symbol(")")?;
+        }
+        self.expect_keyword("AS")?;
+
+        Ok(CreateView {
+            name,
+            columns,
+            select: self.parse_select()?,
+        })
+    }
+
+    fn pragma(&mut self) -> Result<Pragma> {
+        let mut name = self.identifier()?;
+        if self.eat_symbol(".") {
+            name = self.identifier()?;
+        }
+        let close = if self.eat_symbol("=") {
+            false
+        } else if self.eat_symbol("(") {
+            true
+        } else {
+            return Ok(Pragma { name, value: None });
+        };
+        let negative = self.eat_symbol("-");
+        let value = match self.advance().map(|t| t.kind) {
+            Some(TokenKind::Integer(i)) => Value::Integer(if negative { -i } else { i }),
+            Some(TokenKind::Float(f)) => Value::Real(if negative { -f } else { f }),
+            Some(TokenKind::String(s) | TokenKind::Word(s) | TokenKind::QuotedIdent(s)) if !negative => Value::Text(s),
+            _ => {
+                self.pos -= 1;
+                return Err(self.error());
+            }
+        };
+        if close {
+            self.expect_symbol(")")?;
+        }
+        Ok(Pragma {
+            name,
+            value: Some(value),
+        })
+    }
+
+    /// Comma separated columns or expressions of an index or key constraint.
+    fn indexed_columns(&mut self) -> Result<Vec<IndexedColumn>> {
+        let mut columns = vec![];
+        loop {
+            let expr = self.expr()?;
+            let name = match expr.kind {
+                ExprKind::Column { table: None, name } => Some(name),
+                _ => None,
+            };
+            let collation = if self.eat_keyword("COLLATE") {
+                Some(self.identifier()?)
+            } else {
+                None
+            };
+            let descending = self.eat_keyword("DESC");
+            if !descending {
+                self.eat_keyword("ASC");
+            }
+            columns.push(IndexedColumn {
+                name,
+                collation,
+                descending,
+            });
+            if !self.eat_symbol(",") {
+                return Ok(columns);
+            }
+        }
+    }
+
+    /// A column definition, the PRIMARY KEY or UNIQUE keys it declares and
+    /// which of them is the PRIMARY KEY.
+    /// Words of a type name up to a column constraint, with its `(size)` if any.
+    fn type_name(&mut self) -> Result<String> {
+        let mut type_words = vec![];
+        while let Some(TokenKind::Word(w)) = self.peek() {
+            if COLUMN_CONSTRAINTS.iter().any(|c| w.eq_ignore_ascii_case(c)) {
+                break;
+            }
+            type_words.push(w.clone());
+            self.pos += 1;
+        }
+        let mut type_name = type_words.join(" ");
+        if self.peek_symbol("(") {
+            let start = self.span();
+            self.pos += 1;
+            self.skip_until(&[")"])?;
+            self.expect_symbol(")")?;
+            type_name.push_str(self.text(start.to(self.prev_span())));
+        }
+        Ok(type_name)
+    }
+
+    fn column_def(&mut self) -> Result<(ColumnDef, Vec<Vec<IndexedColumn>>, Option<usize>)> {
+        let name = self.identifier()?;
+
+        let type_name = self.type_name()?;
+
+        let mut primary_key = None;
+        let mut rowid_alias = false;
+        let mut collation = None;
+        let mut default = None;
+        let mut keys = vec![];
+        while !self.peek_symbol(",") && !self.peek_symbol(")") {
+            let unique = self.eat_keyword("UNIQUE");
+            if unique || self.eat_keyword("PRIMARY") {
+                let descending = if unique {
+                    false
+                } else {
+                    self.expect_keyword("KEY")?;
+                    primary_key = Some(keys.len());
+                    let descending = self.eat_keyword("DESC");
+                    // sqlite's quirk: only an ascending INTEGER PRIMARY KEY is an alias for the rowid
+                    rowid_alias = !descending && type_name.eq_ignore_ascii_case("INTEGER");
+                    descending
+                };
+                keys.push(vec![IndexedColumn {
+                    name: Some(name.clone()),
+                    collation: None,
+                    descending,
+                }]);
+            } else if self.eat_keyword("COLLATE") {
+                collation = Some(self.identifier()?);
+            } else if self.eat_keyword("DEFAULT") {
+                // a literal, a signed number, an expression in parentheses or
+                // a bare identifier, which sqlite takes as a string
+                let mut expr = self.prefix()?;
+                if let ExprKind::Column { table: None, name } = expr.kind {
+                    expr.kind = ExprKind::Literal(Value::Text(name));
+                }
+                default = Some(expr);
+            } else if self.eat_symbol("(") {
+                self.skip_until(&[")"])?;
+                self.expect_symbol(")")?;
+            } else if self.advance().is_none() {
+                return Err(self.error());
+            }
+        }
+
+        Ok((
+            ColumnDef {
+                name,
+                type_name,
+                primary_key: primary_key.is_some(),
+                rowid_alias,
+                collation,
+                default,
+            },
+            keys,
+            primary_key,
+        ))
+    }
+
+    /// Skips tokens up to one of `symbols` at the current nesting depth.
+    fn skip_until(&mut self, symbols: &[&str]) -> Result<()> {
+        let mut depth = 0;
+        loop {
+            match self.peek() {
+                None => return Err(self.error()),
+                Some(TokenKind::Symbol(s)) if depth == 0 && symbols.contains(s) => return Ok(()),
+                Some(TokenKind::Symbol("(")) => depth += 1,
+                Some(TokenKind::Symbol(")")) => depth -= 1,
+                _ => (),
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+fn is_reserved(word: &str) -> bool {
+    RESERVED.iter().any(|r| word.eq_ignore_ascii_case(r))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_select() {
+        let Statement::Select(select) =
+            Parser::parse_statement("SELECT name, count(*) AS n FROM apples WHERE color = 'Red' OR id > 1 + 2 * 3 ORDER BY n DESC LIMIT 5;").unwrap()
+        else {
+            panic!("expected a SELECT")
+        };
+        assert_eq!(select.columns.len(), 2);
+        assert!(matches!(&select.from[..], [FromItem::Table(TableRef { name, .. })] if name == "apples"));
+        assert_eq!(select.order_by.len(), 1);
+        assert!(select.order_by[0].descending);
+
+        let ExprKind::Binary { op, right, .. } = select.where_clause.unwrap().kind else {
+            panic!("expected binary expression");
+        };
+        assert_eq!(op, BinaryOp::Or);
+        let ExprKind::Binary { op, right, .. } = right.kind else {
+            panic!("expected comparison");
+        };
+        assert_eq!(op, BinaryOp::Gt);
+        assert!(matches!(right.kind, ExprKind::Binary { op: BinaryOp::Add, .. }));
+    }
+
+    #[test]
+    fn parses_with_compound_and_window() {
+        let Statement::Select(select) = Parser::parse_statement(
+            "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n WHERE x < 5) \
+             SELECT x, sum(x) OVER (PARTITION BY x % 2 ORDER BY x ROWS BETWEEN 1 PRECEDING AND CURRENT ROW) FROM n \
+             EXCEPT SELECT 3 ORDER BY 1 DESC",
+        )
+        .unwrap()
+        else {
+            panic!("expected a SELECT")
+        };
+        assert_eq!((select.with[0].name.as_str(), &select.with[0].columns[..]), ("n", &["x".to_string()][..]));
+        assert!(matches!(&select.with[0].select.compound[..], [(CompoundOp::UnionAll, _)]));
+        assert!(matches!(&select.compound[..], [(CompoundOp::Except, _)]));
+        // ORDER BY belongs to the whole compound, not to its last term
+        assert_eq!(select.order_by.len(), 1);
+        assert!(select.compound[0].1.order_by.is_empty());
+
+        let ResultColumn::Expr { expr, .. } = &select.columns[1] else {
+            panic!("expected an expression");
+        };
+        let ExprKind::Window { name, over, .. } = &expr.kind else {
+            panic!("expected a window function");
+        };
+        assert_eq!(name, "sum");
+        assert_eq!((over.partition_by.len(), over.order_by.len()), (1, 1));
+        let frame = over.frame.as_ref().unwrap();
+        assert_eq!(frame.units, FrameUnits::Rows);
+        assert!(matches!((&frame.start, &frame.end), (FrameBound::Preceding(_), FrameBound::CurrentRow)));
+        assert_eq!(frame.exclude, FrameExclude::NoOthers);
+
+        let Statement::Select(select) = Parser::parse_statement(
+            "SELECT count(*) FILTER (WHERE a > 1) OVER (GROUPS 1 PRECEDING EXCLUDE TIES), max(a) FILTER (WHERE b) FROM t",
+        )
+        .unwrap()
+        else {
+            panic!("expected a SELECT")
+        };
+        let kinds: Vec<&ExprKind> = select
+            .columns
+            .iter()
+            .map(|c| match c {
+                ResultColumn::Expr { expr, .. } => &expr.kind,
+                _ => panic!("expected an expression"),
+            })
+            .collect();
+        let ExprKind::Window { filter: Some(_), over, .. } = kinds[0] else {
+            panic!("expected a filtered window function");
+        };
+        assert_eq!(over.frame.as_ref().unwrap().exclude, FrameExclude::Ties);
+        assert!(matches!(kinds[1], ExprKind::Function { filter: Some(_), .. }));
+        assert!(Parser::parse_statement("SELECT sum(a) OVER (ORDER BY a EXCLUDE CURRENT ROW) FROM t").is_err());
+        assert!(Parser::parse_statement("SELECT sum(a) OVER (ROWS CURRENT ROW EXCLUDE OTHERS) FROM t").is_err());
+        assert!(Parser::parse_statement("SELECT count(*) FILTER (a > 1) FROM t").is_err());
+    }
+
+    #[test]
+    fn parses_case_cast_and_like() {
+        let Statement::Select(select) = Parser::parse_statement(
+            "SELECT CASE a WHEN 1 THEN 'one' ELSE 'many' END, CAST(b AS decimal(10, 2)), c NOT LIKE 'x!%' ESCAPE '!'",
+        )
+        .unwrap()
+        else {
+            panic!("expected a SELECT")
+        };
+        let exprs: Vec<&ExprKind> = select
+            .columns
+            .iter()
+            .map(|c| match c {
+                ResultColumn::Expr { expr, .. } => &expr.kind,
+                _ => panic!("expected an expression"),
+            })
+            .collect();
+
+        let ExprKind::Case { operand, branches, otherwise } = exprs[0] else {
+            panic!("expected CASE");
+        };
+        assert!(operand.is_some() && otherwise.is_some());
+        assert_eq!(branches.len(), 1);
+
+        let ExprKind::Cast { type_name, .. } = exprs[1] else {
+            panic!("expected CAST");
+        };
+        assert_eq!(type_name, "decimal(10, 2)");
+
+        // `c NOT LIKE p ESCAPE e` is `NOT like(p, c, e)`
+        let ExprKind::Unary { op: UnaryOp::Not, expr } = exprs[2] else {
+            panic!("expected NOT");
+        };
+        let ExprKind::Function { name, args, .. } = &expr.kind else {
+            panic!("expected like()");
+        };
+        assert_eq!(name, "like");
+        let args: Vec<&ExprKind> = args.iter().map(|a| &a.kind).collect();
+        assert_eq!(args[0], &ExprKind::Literal(Value::Text("x!%".to_string())));
+        assert!(matches!(args[1], ExprKind::Column { name, .. } if name == "c"));
+        assert_eq!(args[2], &ExprKind::Literal(Value::Text("!".to_string())));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(Parser::parse_statement("SELEC 1").is_err());
+        assert!(Parser::parse_statement("SELECT 1 FROM").is_err());
+        assert!(Parser::parse_statement("SELECT (1").is_err());
+    }
+
+    #[test]
+    fn parses_create_table() {
+        let table = Parser::parse_create_table(
+            "CREATE TABLE \"apples\"\n(\n\tid integer primary key autoincrement,\n\tname text,\n\tprice decimal(10, 2) NOT NULL,\n\tUNIQUE(name)\n)",
+        )
+        .unwrap();
+        assert_eq!(table.name, "apples");
+        let names: Vec<&str> = table.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["id", "name", "price"]);
+        assert!(table.columns[0].primary_key);
+        assert_eq!(table.columns[2].type_name, "decimal(10, 2)");
+        let keys: Vec<_> = table.keys.iter().map(|k| k[0].name.as_deref()).collect();
+        assert_eq!(keys, [Some("id"), Some("name")]);
+        assert!(table.columns[0].rowid_alias);
+
+        assert_eq!(table.primary_key, Some(0));
+
+        let table = Parser::parse_create_table("CREATE TABLE t(a INTEGER, b UNIQUE, PRIMARY KEY(a)) WITHOUT ROWID").unwrap();
+        assert!(table.columns[0].primary_key);
+        assert_eq!(table.primary_key, Some(1));
+        assert!(table.without_rowid && !table.columns[0].rowid_alias);
+        assert_eq!(table.columns[1].type_name, "");
+
+        let table = Parser::parse_create_table("CREATE TABLE t(a INTEGER PRIMARY KEY DESC, b INTEGER)").unwrap();
+        assert!(table.columns[0].primary_key && !table.columns[0].rowid_alias);
+
+        let table = Parser::parse_create_table("CREATE TABLE t(a DEFAULT -1 NOT NULL, b DEFAULT (1 + 2), c DEFAULT abc)").unwrap();
+        let defaults: Vec<_> = table.columns.iter().map(|c| c.default.as_ref().map(|d| d.kind.clone())).collect();
+        assert!(matches!(defaults[0], Some(ExprKind::Unary { op: UnaryOp::Neg, .. })));
+        assert!(matches!(defaults[1], Some(ExprKind::Binary { op: BinaryOp::Add, .. })));
+        assert_eq!(defaults[2], Some(ExprKind::Literal(Value::Text("abc".to_string()))));
+    }
+
+    #[test]
+    fn parses_create_index_and_pragma() {
+        let index = Parser::parse_create_index(
+            "CREATE UNIQUE INDEX i ON t (a COLLATE NOCASE, b DESC, a + b) WHERE b > 0",
+        )
+        .unwrap();
+        assert_eq!((index.name.as_str(), index.table.as_str(), index.unique), ("i", "t", true));
+        assert_eq!(index.columns[0].collation.as_deref(), Some("NOCASE"));
+        assert!(index.columns[1].descending);
+        assert_eq!(index.columns[2].name, None);
+        assert!(index.where_clause.is_some());
+
+        let Statement::Pragma(pragma) = Parser::parse_statement("PRAGMA main.integrity_check(10)").unwrap() else {
+            panic!("expected pragma");
+        };
+        assert_eq!(pragma.name, "integrity_check");
+        assert_eq!(pragma.value, Some(Value::Integer(10)));
+
+        let view = Parser::parse_create_view("CREATE VIEW IF NOT EXISTS v(x, y) AS SELECT a, b FROM t WHERE a > 1").unwrap();
+        assert_eq!((view.name.as_str(), view.columns.len()), ("v", 2));
+        assert!(view.select.where_clause.is_some());
+    }
+}
@@ -0,0 +1,221 @@
+use crate::error::{DbError, Result};
+
+/// Byte range of a token or expression in the original SQL text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    /// Unquoted identifier or keyword, keywords are matched case-insensitively by the parser.
+    Word(String),
+    /// `"name"`, `[name]` or `` `name` ``, never a keyword.
+    QuotedIdent(String),
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Blob(Vec<u8>),
+    /// A bound parameter: `?`, `?NNN`, `:name`, `@name` or `$name`, prefix included.
+    Variable(String),
+    Symbol(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+const SYMBOLS: [&str; 24] = [
+    "||", "==", "!=", "<>", "<=", ">=", "<<", ">>", "=", "<", ">", "+", "-", "*", "/", "%", "(",
+    ")", ",", ".", ";", "~", "&", "|",
+];
+
+fn syntax_error(message: impl Into<String>, start: usize, end: usize) -> DbError {
+    DbError::SyntaxError {
+        message: message.into(),
+        span: Span { start, end },
+    }
+}
+
+pub fn tokenize(sql: &str) -> Result<Vec<Token>> {
+    let bytes = sql.as_bytes();
+    let mut tokens = vec![];
+    let mut ind = 0;
+
+    while ind < bytes.len() {
+        let start = ind;
+        let c = bytes[ind];
+
+        if c.is_ascii_whitespace() {
+            ind += 1;
+            continue;
+        }
+        if sql[ind..].starts_with("--") {
+            ind = sql[ind..].find('\n').map_or(bytes.len(), |i| ind + i);
+            continue;
+        }
+        if sql[ind..].starts_with("/*") {
+            ind = sql[ind + 2..].find("*/").map_or(bytes.len(), |i| ind + i + 4);
+            continue;
+        }
+
+        let kind = if (c == b'x' || c == b'X') && bytes.get(ind + 1) == Some(&b'\'') {
+            let (hex, end) = quoted(sql, ind + 1, b'\'')?;
+            ind = end;
+            if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(syntax_error("malformed blob literal", start, end));
+            }
+            let blob = (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or_default())
+                .collect();
+            TokenKind::Blob(blob)
+        } else if c.is_ascii_alphabetic() || c == b'_' || c >= 0x80 {
+            while ind < bytes.len()
+                && (bytes[ind].is_ascii_alphanumeric() || bytes[ind] == b'_' || bytes[ind] == b'$' || bytes[ind] >= 0x80)
+            {
+                ind += 1;
+            }
+            TokenKind::Word(sql[start..ind].to_string())
+        } else if c.is_ascii_digit() || (c == b'.' && bytes.get(ind + 1).is_some_and(u8::is_ascii_digit)) {
+            let (kind, end) = number(sql, ind)?;
+            ind = end;
+            kind
+        } else if c == b'\'' {
+            let (s, end) = quoted(sql, ind, b'\'')?;
+            ind = end;
+            TokenKind::String(s)
+        } else if c == b'"' || c == b'`' {
+            let (s, end) = quoted(sql, ind, c)?;
+            ind = end;
+            TokenKind::QuotedIdent(s)
+        } else if c == b'[' {
+            let Some(len) = sql[ind..].find(']') else {
+                return Err(syntax_error("unrecognized token", start, bytes.len()));
+            };
+            ind += len + 1;
+            TokenKind::QuotedIdent(sql[start + 1..ind - 1].to_string())
+        } else if c == b'?' {
+            ind += 1;
+            while ind < bytes.len() && bytes[ind].is_ascii_digit() {
+                ind += 1;
+            }
+            TokenKind::Variable(sql[start..ind].to_string())
+        } else if matches!(c, b':' | b'@' | b'$')
+            && bytes
+                .get(ind + 1)
+                .is_some_and(|&b| b.is_ascii_alphanumeric() || b == b'_' || b == b'$' || b >= 0x80)
+        {
+            ind += 1;
+            while ind < bytes.len()
+                && (bytes[ind].is_ascii_alphanumeric() || bytes[ind] == b'_' || bytes[ind] == b'$' || bytes[ind] >= 0x80)
+            {
+                ind += 1;
+            }
+            TokenKind::Variable(sql[start..ind].to_string())
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| sql[ind..].starts_with(**s)) {
+            ind += symbol.len();
+            TokenKind::Symbol(symbol)
+        } else {
+            let end = start + sql[start..].chars().next().map_or(1, char::len_utf8);
+            return Err(syntax_error(
+                format!("unrecognized token: \"{}\"", &sql[start..end]),
+                start,
+                end,
+            ));
+        };
+
+        tokens.push(Token {
+            kind,
+            span: Span { start, end: ind },
+        });
+    }
+
+    Ok(tokens)
+}
+
+/// Reads a quoted string starting at `start`, where a doubled quote escapes itself.
+fn quoted(sql: &str, start: usize, quote: u8) -> Result<(String, usize)> {
+    let bytes = sql.as_bytes();
+    let mut ind = start + 1;
+    let mut s = String::new();
+    let mut chunk = ind;
+    loop {
+        match bytes.get(ind) {
+            None => return Err(syntax_error("unrecognized token", start, bytes.len())),
+            Some(b) if *b == quote => {
+                s.push_str(&sql[chunk..ind]);
+                if bytes.get(ind + 1) == Some(&quote) {
+                    s.push(quote as char);
+                    ind += 2;
+                    chunk = ind;
+                } else {
+                    return Ok((s, ind + 1));
+                }
+            }
+            Some(_) => ind += 1,
+        }
+    }
+}
+
+fn number(sql: &str, start: usize) -> Result<(TokenKind, usize)> {
+    let bytes = sql.as_bytes();
+    let mut ind = start;
+
+    if sql[start..].starts_with("0x") || sql[start..].starts_with("0X") {
+        ind += 2;
+        while ind < bytes.len() && bytes[ind].is_ascii_hexdigit() {
+            ind += 1;
+        }
+        let val = u64::from_str_radix(&sql[start + 2..ind], 16)
+            .map_err(|_| syntax_error(format!("hex literal too big: {}", &sql[start..ind]), start, ind))?;
+        return Ok((TokenKind::Integer(val as i64), ind));
+    }
+
+    let mut is_float = false;
+    while ind < bytes.len() && bytes[ind].is_ascii_digit() {
+        ind += 1;
+    }
+    if bytes.get(ind) == Some(&b'.') {
+        is_float = true;
+        ind += 1;
+        while ind < bytes.len() && bytes[ind].is_ascii_digit() {
+            ind += 1;
+        }
+    }
+    if matches!(bytes.get(ind), Some(b'e') | Some(b'E')) {
+        let mut exp = ind + 1;
+        if matches!(bytes.get(exp), Some(b'+') | Some(b'-')) {
+            exp += 1;
+        }
+        if bytes.get(exp).is_some_and(u8::is_ascii_digit) {
+            is_float = true;
+            ind = exp;
+            while ind < bytes.len() && bytes[ind].is_ascii_digit() {
+                ind += 1;
+            }
+        }
+    }
+
+    let text = &sql[start..ind];
+    let kind = match text.parse::<i64>() {
+        Ok(v) if !is_float => TokenKind::Integer(v),
+        _ => TokenKind::Float(
+            text.parse()
+                .map_err(|_| syntax_error(format!("malformed number: {}", text), start, ind))?,
+        ),
+    };
+    Ok((kind, ind))
+}
@@ -0,0 +1,179 @@
+use std::fs::File;
+use std::io::{self, prelude::*, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::pager::PageBuf;
+
+/// Where the bytes of a database file come from. The pager asks for whole
+/// pages (and the 100 byte header) by their byte offset.
+pub trait Storage {
+    /// Reads `len` bytes starting at `offset`, failing with
+    /// `UnexpectedEof` when the file is shorter.
+    fn read_at(&mut self, offset: u64, len: usize) -> io::Result<PageBuf>;
+
+    /// Length of the file in bytes.
+    fn size(&mut self) -> io::Result<u64>;
+
+    /// Whether `read_at` borrows from memory instead of doing I/O, in which
+    /// case caching its pages gains nothing.
+    fn is_in_memory(&self) -> bool {
+        false
+    }
+}
+
+/// Reads pages from any seekable reader with a seek and a read per page,
+/// e.g. an entry of an uncompressed archive.
+pub struct ReaderStorage<R> {
+    reader: R,
+}
+
+pub type FileStorage = ReaderStorage<File>;
+
+impl<R: Read + Seek> ReaderStorage<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl FileStorage {
+    pub fn open(p: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(File::open(p)?))
+    }
+}
+
+impl<R: Read + Seek> Storage for ReaderStorage<R> {
+    fn read_at(&mut self, offset: u64, len: usize) -> io::Result<PageBuf> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let mut raw = vec![0u8; len];
+        self.reader.read_exact(&mut raw)?;
+        Ok(raw.into())
+    }
+
+    fn size(&mut self) -> io::Result<u64> {
+        self.reader.seek(SeekFrom::End(0))
+    }
+}
+
+/// A database file that is already in memory, e.g. a request body. Pages
+/// are slices of the buffer and never copied.
+#[derive(Clone)]
+pub struct MemoryStorage {
+    data: Arc<dyn AsRef<[u8]> + Send + Sync>,
+}
+
+impl MemoryStorage {
+    pub fn new(data: impl AsRef<[u8]> + Send + Sync + 'static) -> Self {
+        Self { data: Arc::new(data) }
+    }
+
+    /// Copies a borrowed buffer once, every page afterwards is shared.
+    pub fn copy_from_slice(data: &[u8]) -> Self {
+        Self::new(Bytes::copy_from_slice(data))
+    }
+
+    fn bytes(&self) -> &[u8] {
+        (*self.data).as_ref()
+    }
+}
+
+impl From<Vec<u8>> for MemoryStorage {
+    fn from(data: Vec<u8>) -> Self {
+        Self::new(data)
+    }
+}
+
+impl From<Bytes> for MemoryStorage {
+    fn from(data: Bytes) -> Self {
+        Self::new(data)
+    }
+}
+
+impl From<&'static [u8]> for MemoryStorage {
+    fn from(data: &'static [u8]) -> Self {
+        Self::new(data)
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn read_at(&mut self, offset: u64, len: usize) -> io::Result<PageBuf> {
+        let range = slice_range(offset, len, self.bytes().len())?;
+        Ok(PageBuf::shared(self.data.clone(), range))
+    }
+
+    fn size(&mut self) -> io::Result<u64> {
+        Ok(self.bytes().len() as u64)
+    }
+
+    fn is_in_memory(&self) -> bool {
+        true
+    }
+}
+
+/// The byte range `offset..offset + len` if it lies within `size` bytes.
+fn slice_range(offset: u64, len: usize, size: usize) -> io::Result<std::ops::Range<usize>> {
+    let start = usize::try_from(offset).unwrap_or(usize::MAX);
+    match start.checked_add(len) {
+        Some(end) if end <= size => Ok(start..end),
+        _ => Err(io::ErrorKind::UnexpectedEof.into()),
+    }
+}
+
+/// Maps the whole file read-only, pages are slices of the mapping and never copied.
+#[cfg(feature = "mmap")]
+pub struct MmapStorage {
+    map: Arc<memmap2::Mmap>,
+}
+
+#[cfg(feature = "mmap")]
+impl MmapStorage {
+    pub fn open(p: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(p)?;
+        // SAFETY: the mapping is read-only. Another process truncating or
+        // rewriting the file while it is open is undefined behaviour, the same
+        // caveat sqlite documents for its own mmap mode.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Ok(Self { map: Arc::new(map) })
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl Storage for MmapStorage {
+    fn read_at(&mut self, offset: u64, len: usize) -> io::Result<PageBuf> {
+        let range = slice_range(offset, len, self.map.len())?;
+        Ok(PageBuf::shared(self.map.clone(), range))
+    }
+
+    fn size(&mut self) -> io::Result<u64> {
+        Ok(self.map.len() as u64)
+    }
+
+    fn is_in_memory(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_within_bounds() {
+        let mut storage = MemoryStorage::from(vec![1u8, 2, 3, 4]);
+        let buf = storage.read_at(1, 2).unwrap();
+        assert_eq!(&buf[..], [2, 3]);
+        assert!(buf.is_shared());
+        assert_eq!(storage.read_at(3, 2).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(storage.read_at(u64::MAX, 1).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut reader = ReaderStorage::new(io::Cursor::new(vec![1u8, 2, 3, 4]));
+        assert_eq!(&reader.read_at(2, 2).unwrap()[..], [3, 4]);
+        assert_eq!(reader.read_at(3, 2).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::{bail, Result};
+use crate::error::{DbError, Result};
 use std::fmt;
 
 #[derive(Debug)]
@@ -30,14 +30,15 @@ impl VarInt {
             }
 
             if i >= 8 {
-                bail!("Varint is too long");
+                return Err(DbError::Corrupt("varint is too long".to_string()));
             }
         }
-        bail!("Incomplete varint")
+        Err(DbError::Corrupt("incomplete varint".to_string()))
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
 mod tests {
     use super::*;
 
@@ -0,0 +1,467 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::cell::Record;
+use crate::error::{DbError, Result};
+
+/// A decoded column value, collapsed to SQLite's five storage classes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Integer(_) => "integer",
+            Value::Real(_) => "real",
+            Value::Text(_) => "text",
+            Value::Blob(_) => "blob",
+        }
+    }
+
+    /// Numeric value of the longest numeric prefix, 0 if there is none.
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Value::Null => 0.0,
+            Value::Integer(i) => *i as f64,
+            Value::Real(r) => *r,
+            Value::Text(s) => numeric_prefix(s).map_or(0.0, |v| v.to_f64()),
+            Value::Blob(b) => numeric_prefix(&String::from_utf8_lossy(b)).map_or(0.0, |v| v.to_f64()),
+        }
+    }
+
+    pub fn to_i64(&self) -> i64 {
+        match self {
+            Value::Integer(i) => *i,
+            Value::Real(r) => *r as i64,
+            Value::Text(s) => numeric_prefix(s).map_or(0, |v| v.to_i64()),
+            Value::Blob(b) => numeric_prefix(&String::from_utf8_lossy(b)).map_or(0, |v| v.to_i64()),
+            Value::Null => 0,
+        }
+    }
+
+    /// Converts text and blobs to a number the way arithmetic operators do.
+    pub fn to_numeric(&self) -> Value {
+        match self {
+            Value::Null | Value::Integer(_) | Value::Real(_) => self.clone(),
+            Value::Text(s) => numeric_prefix(s).unwrap_or(Value::Integer(0)),
+            Value::Blob(b) => numeric_prefix(&String::from_utf8_lossy(b)).unwrap_or(Value::Integer(0)),
+        }
+    }
+
+    /// Truth value in a boolean context, `None` for NULL.
+    pub fn truthy(&self) -> Option<bool> {
+        match self {
+            Value::Null => None,
+            v => Some(v.to_f64() != 0.0),
+        }
+    }
+
+    /// Sort order across storage classes: NULL, numbers, text, blobs.
+    pub fn compare(&self, other: &Value) -> Ordering {
+        fn rank(v: &Value) -> u8 {
+            match v {
+                Value::Null => 0,
+                Value::Integer(_) | Value::Real(_) => 1,
+                Value::Text(_) => 2,
+                Value::Blob(_) => 3,
+            }
+        }
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::Integer(_) | Value::Real(_), Value::Integer(_) | Value::Real(_)) => {
+                self.to_f64().total_cmp(&other.to_f64())
+            }
+            (Value::Text(a), Value::Text(b)) => a.cmp(b),
+            (Value::Blob(a), Value::Blob(b)) => a.cmp(b),
+            _ => rank(self).cmp(&rank(other)),
+        }
+    }
+}
+
+/// Parses the longest prefix of `s` that looks like a number, ignoring leading spaces.
+pub fn numeric_prefix(s: &str) -> Option<Value> {
+    parse_numeric_prefix(s).map(|(v, _)| v)
+}
+
+/// The number `s` holds, if it is nothing but a number and surrounding spaces.
+pub fn numeric_text(s: &str) -> Option<Value> {
+    let s = s.trim();
+    match parse_numeric_prefix(s) {
+        Some((v, len)) if len == s.len() => Some(v),
+        _ => None,
+    }
+}
+
+/// The integer of the leading digits after spaces and a sign, saturating at
+/// the bounds of i64 like sqlite's `CAST(text AS INTEGER)`.
+fn integer_prefix(s: &str) -> i64 {
+    let s = s.trim_start();
+    let (negative, digits) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    let mut value: i64 = 0;
+    for d in digits.bytes().take_while(u8::is_ascii_digit) {
+        let d = (d - b'0') as i64;
+        value = match value.checked_mul(10).and_then(|v| if negative { v.checked_sub(d) } else { v.checked_add(d) }) {
+            Some(v) => v,
+            None if negative => return i64::MIN,
+            None => return i64::MAX,
+        };
+    }
+    value
+}
+
+/// The longest numeric prefix after leading spaces and its length in bytes.
+fn parse_numeric_prefix(s: &str) -> Option<(Value, usize)> {
+    let s = s.trim_start();
+    let bytes = s.as_bytes();
+    let digits = |mut ind: usize| {
+        while ind < bytes.len() && bytes[ind].is_ascii_digit() {
+            ind += 1;
+        }
+        ind
+    };
+
+    let mut ind = 0;
+    if matches!(bytes.first(), Some(b'+') | Some(b'-')) {
+        ind += 1;
+    }
+    let int_start = ind;
+    ind = digits(ind);
+    let has_int = ind > int_start;
+
+    let mut is_real = false;
+    if bytes.get(ind) == Some(&b'.') {
+        let frac_end = digits(ind + 1);
+        if has_int || frac_end > ind + 1 {
+            is_real = true;
+            ind = frac_end;
+        }
+    }
+    if !has_int && !is_real {
+        return None;
+    }
+
+    if matches!(bytes.get(ind), Some(b'e') | Some(b'E')) {
+        let mut exp = ind + 1;
+        if matches!(bytes.get(exp), Some(b'+') | Some(b'-')) {
+            exp += 1;
+        }
+        if bytes.get(exp).is_some_and(u8::is_ascii_digit) {
+            is_real = true;
+            ind = digits(exp);
+        }
+    }
+
+    if !is_real {
+        if let Ok(i) = s[..ind].parse::<i64>() {
+            return Some((Value::Integer(i), ind));
+        }
+    }
+    s[..ind].parse::<f64>().ok().map(|r| (Value::Real(r), ind))
+}
+
+impl From<&Record> for Value {
+    fn from(record: &Record) -> Self {
+        match record {
+            Record::Null | Record::Reserved => Value::Null,
+            Record::I8(v) => Value::Integer(*v as i64),
+            Record::I16(v) => Value::Integer(*v as i64),
+            Record::I24(v) => Value::Integer(*v as i64),
+            Record::I32(v) => Value::Integer(*v as i64),
+            Record::I48(v) => Value::Integer(*v),
+            Record::I64(v) => Value::Integer(*v),
+            Record::F64(v) => Value::Real(*v),
+            Record::Val0 => Value::Integer(0),
+            Record::Val1 => Value::Integer(1),
+            Record::Blob(b) => Value::Blob(b.to_vec()),
+            Record::String(s) => Value::Text(String::from_utf8_lossy(s).into_owned()),
+        }
+    }
+}
+
+/// Column type affinity, the storage class a column prefers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Affinity {
+    Integer,
+    Text,
+    Blob,
+    Real,
+    Numeric,
+}
+
+impl Affinity {
+    /// SQLite's rules for deriving an affinity from a declared type name.
+    pub fn from_type_name(type_name: &str) -> Self {
+        let t = type_name.to_uppercase();
+        if t.contains("INT") {
+            Affinity::Integer
+        } else if t.contains("CHAR") || t.contains("CLOB") || t.contains("TEXT") {
+            Affinity::Text
+        } else if t.contains("BLOB") || t.is_empty() {
+            Affinity::Blob
+        } else if t.contains("REAL") || t.contains("FLOA") || t.contains("DOUB") {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+
+    /// Converts `value` the way storing it in a column of this affinity does.
+    pub fn apply(self, value: Value) -> Value {
+        match (self, value) {
+            (Affinity::Text, v @ (Value::Integer(_) | Value::Real(_))) => Value::Text(v.to_string()),
+            (Affinity::Integer | Affinity::Real | Affinity::Numeric, Value::Text(s)) => match numeric_text(&s) {
+                Some(n) => self.apply(n),
+                None => Value::Text(s),
+            },
+            (Affinity::Real, Value::Integer(i)) => Value::Real(i as f64),
+            // reals without a fractional part are stored as integers
+            (Affinity::Integer | Affinity::Numeric, Value::Real(r))
+                if r.fract() == 0.0 && (-9.223372036854775e18..9.223372036854775e18).contains(&r) =>
+            {
+                Value::Integer(r as i64)
+            }
+            (_, v) => v,
+        }
+    }
+
+    /// `CAST(value AS type)` for a type of this affinity. Unlike storing into a
+    /// column, text is always converted to a number by its numeric prefix.
+    pub fn cast(self, value: Value) -> Value {
+        let text = |v: &Value| match v {
+            Value::Blob(b) => String::from_utf8_lossy(b).into_owned(),
+            v => v.to_string(),
+        };
+        match (self, value) {
+            (_, Value::Null) => Value::Null,
+            (Affinity::Blob, Value::Blob(b)) => Value::Blob(b),
+            (Affinity::Blob, v) => Value::Blob(text(&v).into_bytes()),
+            (Affinity::Text, v) => Value::Text(text(&v)),
+            (Affinity::Integer, Value::Real(r)) => Value::Integer(r as i64),
+            (Affinity::Integer, Value::Integer(i)) => Value::Integer(i),
+            (Affinity::Integer, v) => Value::Integer(integer_prefix(&text(&v))),
+            (Affinity::Real, v) => Value::Real(v.to_f64()),
+            (Affinity::Numeric, v @ (Value::Integer(_) | Value::Real(_))) => v,
+            // an integer prefix stays an integer, a real one only becomes an
+            // integer when it is exactly representable as one
+            (Affinity::Numeric, v) => match numeric_prefix(&text(&v)) {
+                None => Value::Integer(0),
+                Some(Value::Real(r)) if r.fract() == 0.0 && (-2251799813685248.0..2251799813685248.0).contains(&r) => {
+                    Value::Integer(r as i64)
+                }
+                Some(n) => n,
+            },
+        }
+    }
+}
+
+/// Built-in collating sequences for comparing text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Collation {
+    #[default]
+    Binary,
+    /// ASCII case-insensitive.
+    NoCase,
+    /// Ignores trailing spaces.
+    RTrim,
+}
+
+impl Collation {
+    /// `None` for a collation sqlite doesn't have built in.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "BINARY" => Some(Collation::Binary),
+            "NOCASE" => Some(Collation::NoCase),
+            "RTRIM" => Some(Collation::RTrim),
+            _ => None,
+        }
+    }
+
+    /// Like `Value::compare`, with text compared under this collation.
+    pub fn compare(self, a: &Value, b: &Value) -> Ordering {
+        match (self, a, b) {
+            (Collation::NoCase, Value::Text(a), Value::Text(b)) => {
+                a.bytes().map(|c| c.to_ascii_lowercase()).cmp(b.bytes().map(|c| c.to_ascii_lowercase()))
+            }
+            (Collation::RTrim, Value::Text(a), Value::Text(b)) => a.trim_end_matches(' ').cmp(b.trim_end_matches(' ')),
+            _ => a.compare(b),
+        }
+    }
+}
+
+/// Formats a float the way sqlite3 does (`%!.15g`): at most 15 significant
+/// digits and always a decimal point, e.g. `2.0`, `0.1`, `1.0e+20`.
+pub fn format_real(v: f64) -> String {
+    if v.is_nan() {
+        return "NaN".to_string();
+    }
+    if v.is_infinite() {
+        return if v > 0.0 { "Inf".to_string() } else { "-Inf".to_string() };
+    }
+    if v == 0.0 {
+        return "0.0".to_string();
+    }
+
+    let sci = format!("{:.14e}", v);
+    let (mantissa, exp) = sci.split_once('e').unwrap_or((&sci, "0"));
+    let exp: i32 = exp.parse().unwrap_or(0);
+
+    if !(-4..15).contains(&exp) {
+        let mantissa = trim_fraction(mantissa);
+        let sign = if exp < 0 { '-' } else { '+' };
+        return format!("{}e{}{:02}", mantissa, sign, exp.abs());
+    }
+
+    let decimals = (14 - exp).max(0) as usize;
+    trim_fraction(&format!("{:.*}", decimals, v))
+}
+
+fn trim_fraction(s: &str) -> String {
+    if !s.contains('.') {
+        return format!("{}.0", s);
+    }
+    let trimmed = s.trim_end_matches('0');
+    if trimmed.ends_with('.') {
+        format!("{}0", trimmed)
+    } else {
+        trimmed.to_string()
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Integer(v) => write!(f, "{}", v),
+            Value::Real(v) => write!(f, "{}", format_real(*v)),
+            Value::Text(s) => write!(f, "{}", s),
+            Value::Blob(b) => write!(f, "{}", String::from_utf8_lossy(b)),
+        }
+    }
+}
+
+// Conversions from Rust types into values, used by `Statement::bind`.
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Value::Integer(v)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(v: i32) -> Self {
+        Value::Integer(v as i64)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Integer(v as i64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Real(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Text(v.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::Text(v)
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(v: &[u8]) -> Self {
+        Value::Blob(v.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(v: Vec<u8>) -> Self {
+        Value::Blob(v)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(Value::Null, Into::into)
+    }
+}
+
+/// Conversion from a column value into a Rust type, used by `Row::get_as`.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self>;
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self> {
+        Ok(value.clone())
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Integer(i) => Ok(*i),
+            v => Err(DbError::Query(format!("expected an integer, got {}", v.type_name()))),
+        }
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Integer(i) => Ok(*i as f64),
+            Value::Real(r) => Ok(*r),
+            v => Err(DbError::Query(format!("expected a real, got {}", v.type_name()))),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Text(s) => Ok(s.clone()),
+            v => Err(DbError::Query(format!("expected text, got {}", v.type_name()))),
+        }
+    }
+}
+
+impl FromValue for Vec<u8> {
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Blob(b) => Ok(b.clone()),
+            v => Err(DbError::Query(format!("expected a blob, got {}", v.type_name()))),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Null => Ok(None),
+            v => T::from_value(v).map(Some),
+        }
+    }
+}
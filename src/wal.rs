@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use crate::error::{DbError, Result};
+use crate::pager::PageBuf;
+use crate::storage::Storage;
+
+const WAL_HEADER_SIZE: u64 = 32;
+const FRAME_HEADER_SIZE: usize = 24;
+const WAL_VERSION: u32 = 3007000;
+
+// WAL header, all fields big-endian
+// Offset	Size	Description
+// 0	4	Magic number. 0x377f0682 or 0x377f0683
+// 4	4	File format version. Currently 3007000.
+// 8	4	Database page size. Example: 1024
+// 12	4	Checkpoint sequence number
+// 16	4	Salt-1: random integer incremented with each checkpoint
+// 20	4	Salt-2: a different random number for each checkpoint
+// 24	4	Checksum-1: First part of a checksum on the first 24 bytes of header
+// 28	4	Checksum-2: Second part of the checksum on the first 24 bytes of header
+//
+// Frame header
+// 0	4	Page number
+// 4	4	For commit records, the size of the database file in pages after the commit. For all other records, zero.
+// 8	4	Salt-1 copied from the WAL header
+// 12	4	Salt-2 copied from the WAL header
+// 16	4	Checksum-1: Cumulative checksum up through and including this page
+// 20	4	Checksum-2: Second half of the cumulative checksum.
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+/// sqlite's WAL checksum over `data`, continuing from `sum`. The magic number
+/// decides whether the words are read big- or little-endian.
+fn checksum(big_endian: bool, data: &[u8], sum: (u32, u32)) -> (u32, u32) {
+    let (mut s0, mut s1) = sum;
+    let word = |b: &[u8]| {
+        let b = [b[0], b[1], b[2], b[3]];
+        if big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        }
+    };
+    for chunk in data.chunks_exact(8) {
+        s0 = s0.wrapping_add(word(&chunk[..4])).wrapping_add(s1);
+        s1 = s1.wrapping_add(word(&chunk[4..])).wrapping_add(s0);
+    }
+    (s0, s1)
+}
+
+/// The committed frames of a write-ahead log. Pages found here are newer
+/// than the ones in the main database file.
+pub struct Wal {
+    storage: Box<dyn Storage>,
+    page_size: u32,
+    /// Page number to the offset of the page data of its latest committed frame.
+    frames: HashMap<u64, u64>,
+    /// Size of the database in pages after the last commit.
+    pub database_size: Option<u32>,
+}
+
+impl Wal {
+    /// Reads the WAL header and indexes every frame up to the last valid
+    /// commit frame. Like sqlite, a WAL with a bad header or for another page
+    /// size is treated as empty, and frames after the first one with a
+    /// mismatching salt or checksum are ignored.
+    pub fn open(storage: Box<dyn Storage>, page_size: u32) -> Result<Self> {
+        let mut wal = Self {
+            storage,
+            page_size,
+            frames: HashMap::new(),
+            database_size: None,
+        };
+        wal.index()?;
+        Ok(wal)
+    }
+
+    fn index(&mut self) -> Result<()> {
+        let page_size = self.page_size;
+        let storage = &mut self.storage;
+        let header = match storage.read_at(0, WAL_HEADER_SIZE as usize) {
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            r => r?,
+        };
+        let big_endian = match read_u32(&header, 0) {
+            0x377f0682 => false,
+            0x377f0683 => true,
+            _ => return Ok(()),
+        };
+        let mut sum = checksum(big_endian, &header[..24], (0, 0));
+        if read_u32(&header, 4) != WAL_VERSION
+            || read_u32(&header, 8) != page_size
+            || sum != (read_u32(&header, 24), read_u32(&header, 28))
+        {
+            return Ok(());
+        }
+        let salt = (read_u32(&header, 16), read_u32(&header, 20));
+
+        let frame_size = FRAME_HEADER_SIZE + page_size as usize;
+        let mut offset = WAL_HEADER_SIZE;
+        let mut uncommitted = vec![];
+        loop {
+            let frame = match storage.read_at(offset, frame_size) {
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                r => r?,
+            };
+            let page = read_u32(&frame, 0);
+            if page == 0 || (read_u32(&frame, 8), read_u32(&frame, 12)) != salt {
+                break;
+            }
+            sum = checksum(big_endian, &frame[..8], sum);
+            sum = checksum(big_endian, &frame[FRAME_HEADER_SIZE..], sum);
+            if sum != (read_u32(&frame, 16), read_u32(&frame, 20)) {
+                break;
+            }
+
+            uncommitted.push((page as u64, offset + FRAME_HEADER_SIZE as u64));
+            let commit = read_u32(&frame, 4);
+            if commit != 0 {
+                self.frames.extend(uncommitted.drain(..));
+                self.database_size = Some(commit);
+            }
+            offset += frame_size as u64;
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, page_ind: u64) -> bool {
+        self.frames.contains_key(&page_ind)
+    }
+
+    /// Number of distinct pages with a committed frame.
+    #[cfg(test)]
+    pub fn page_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The latest committed version of page `page_ind`, if the WAL has one.
+    pub fn read_page(&mut self, page_ind: u64) -> Result<Option<PageBuf>> {
+        let Some(&offset) = self.frames.get(&page_ind) else {
+            return Ok(None);
+        };
+        match self.storage.read_at(offset, self.page_size as usize) {
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                Err(DbError::corrupt_page(page_ind, 0, "WAL frame is past the end of the WAL file"))
+            }
+            r => Ok(Some(r?)),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    /// Builds a WAL with big-endian checksums holding `frames` of
+    /// (page number, commit size, page data).
+    pub(crate) fn build_wal(page_size: u32, frames: &[(u32, u32, &[u8])]) -> Vec<u8> {
+        let mut wal = vec![];
+        for word in [0x377f0683, WAL_VERSION, page_size, 0, 7, 9] {
+            wal.extend_from_slice(&u32::to_be_bytes(word));
+        }
+        let mut sum = checksum(true, &wal, (0, 0));
+        wal.extend_from_slice(&sum.0.to_be_bytes());
+        wal.extend_from_slice(&sum.1.to_be_bytes());
+        for (page, commit, data) in frames {
+            let mut header = vec![];
+            for word in [*page, *commit, 7, 9] {
+                header.extend_from_slice(&word.to_be_bytes());
+            }
+            sum = checksum(true, &header[..8], sum);
+            sum = checksum(true, data, sum);
+            header.extend_from_slice(&sum.0.to_be_bytes());
+            header.extend_from_slice(&sum.1.to_be_bytes());
+            wal.extend_from_slice(&header);
+            wal.extend_from_slice(data);
+        }
+        wal
+    }
+
+    #[test]
+    fn indexes_committed_frames() {
+        let (a, b, c) = ([1u8; 512], [2u8; 512], [3u8; 512]);
+        let raw = build_wal(512, &[(2, 0, &a), (3, 3, &b), (2, 3, &c), (4, 0, &a)]);
+        let mut wal = Wal::open(Box::new(MemoryStorage::new(raw.clone())), 512).unwrap();
+        assert_eq!(wal.page_count(), 2);
+        assert_eq!(wal.database_size, Some(3));
+        assert_eq!(wal.read_page(2).unwrap().unwrap()[0], 3);
+        assert!(!wal.contains(4));
+
+        // a flipped bit in the second frame invalidates it and everything after
+        let mut corrupt = raw.clone();
+        corrupt[32 + 536 + 100] ^= 1;
+        let wal = Wal::open(Box::new(MemoryStorage::new(corrupt)), 512).unwrap();
+        assert_eq!(wal.page_count(), 0);
+
+        let wal = Wal::open(Box::new(MemoryStorage::new(raw)), 1024).unwrap();
+        assert_eq!(wal.page_count(), 0);
+    }
+}